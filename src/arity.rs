@@ -0,0 +1,182 @@
+/*!
+ * Work out what routines take as arguments by watching them run.
+ *
+ * Routines get their arguments in registers, so a register a call reads
+ * before writing it (itself or in a routine it calls) was an argument -
+ * except by pushing it, which is saving it for the caller.  Values pushed
+ * beyond the return address are locals, usually those saved registers.
+ * Across every call seen:
+ *
+ * ```text
+ * 1458 (print_string): 3 args (r0 r1 r2), 3 locals, 212 calls
+ * 6049 (teleporter_check): 2 args (r0 r1), 1 local, 1 call
+ * ```
+ *
+ * It only knows the paths that ran - a register read on a branch that was
+ * never taken doesn't count.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::debugger::Frame;
+use crate::superblock::Operand;
+
+// true if the opcode writes to its first operand rather than reading it
+pub fn writes_first(opcode: u16) -> bool {
+    matches!(opcode, 1 | 3..=5 | 9..=15 | 20)
+}
+
+// registers as bits
+fn names(regs: u8) -> Vec<String> {
+    (0..8).filter(|r| regs & 1 << r != 0).map(|r| format!("r{}", r)).collect()
+}
+
+// one call in progress
+struct Call {
+    routine: u16,
+    // registers read before written, written, and pushed before written
+    args: u8,
+    written: u8,
+    saved: u8,
+    // registers as the routine was entered
+    entry: [u16; 8],
+    // stack length just after the call, and the most seen since
+    base: usize,
+    highest: usize,
+}
+
+// what every call of a routine added up to
+#[derive(Default)]
+pub struct Inferred {
+    pub calls: u64,
+    pub args: u8,
+    pub locals: usize,
+}
+
+impl Inferred {
+    pub fn arity(&self) -> u32 {
+        self.args.count_ones()
+    }
+
+    // the args as `r0=1 r1=2`, with their values on entry
+    pub fn describe_args(&self, entry: &[u16; 8]) -> String {
+        let args: Vec<_> = (0..8)
+            .filter(|r| self.args & 1 << r != 0)
+            .map(|r| format!("r{}={}", r, entry[r]))
+            .collect();
+        args.join(" ")
+    }
+}
+
+impl fmt::Display for Inferred {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = |n: u64| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} arg{} ({}), {} local{}, {} call{}",
+            self.arity(),
+            s(self.arity() as u64),
+            names(self.args).join(" "),
+            self.locals,
+            s(self.locals as u64),
+            self.calls,
+            s(self.calls)
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct Arity {
+    calls: Vec<Call>,
+    routines: BTreeMap<u16, Inferred>,
+}
+
+impl Arity {
+    pub fn routines(&self) -> &BTreeMap<u16, Inferred> {
+        &self.routines
+    }
+
+    // registers a frame was entered with, if its call was seen starting
+    pub fn entry(&self, depth: usize) -> Option<&[u16; 8]> {
+        self.calls.get(depth).map(|c| &c.entry)
+    }
+
+    // start or finish calls to match the frames
+    pub fn sync(&mut self, frames: &[Frame], registers: &[u16; 8]) {
+        while self.calls.len() > frames.len() {
+            let call = self.calls.pop().unwrap();
+            self.finish(call);
+        }
+        while self.calls.len() < frames.len() {
+            let frame = &frames[self.calls.len()];
+            self.calls.push(Call {
+                routine: frame.routine,
+                args: 0,
+                written: 0,
+                saved: 0,
+                entry: *registers,
+                base: frame.depth + 1,
+                highest: frame.depth + 1,
+            });
+        }
+    }
+
+    // a call went away without its frame, ie. the frames were replaced
+    pub fn clear_calls(&mut self) {
+        self.calls.clear();
+    }
+
+    // fold a finished call into its routine, and into its caller
+    fn finish(&mut self, call: Call) {
+        let inferred = self.routines.entry(call.routine).or_default();
+        inferred.calls += 1;
+        inferred.args |= call.args;
+        inferred.locals = inferred.locals.max(call.highest - call.base);
+
+        if let Some(caller) = self.calls.last_mut() {
+            caller.args |= call.args & !caller.written;
+            caller.written |= call.written;
+        }
+    }
+
+    // the instruction about to run in the innermost call
+    pub fn note(
+        &mut self,
+        opcode: u16,
+        args: &[Option<Operand>; 3],
+        stack: usize,
+    ) {
+        let Some(call) = self.calls.last_mut() else {
+            return;
+        };
+        call.highest = call.highest.max(stack);
+
+        // reads happen before the write, ie. `add r0 r0 1` reads r0
+        let reg = |arg: &Option<Operand>| match arg {
+            Some(Operand::Reg(r)) => Some(1u8 << r),
+            _ => None,
+        };
+        let skip = writes_first(opcode) as usize;
+        for bit in args.iter().skip(skip).filter_map(reg) {
+            if call.written & bit != 0 {
+                continue;
+            }
+            if opcode == 2 {
+                call.saved |= bit;
+            } else {
+                call.args |= bit;
+            }
+        }
+        if skip == 1
+            && let Some(bit) = reg(&args[0])
+        {
+            if opcode == 3 && call.saved & bit != 0 {
+                // popping a saved register puts back the caller's value
+                call.written &= !bit;
+            } else {
+                call.written |= bit;
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+/*!
+ * A small assembler for writing patches and test programs.
+ *
+ * Instructions are separated by newlines or `;` and written as the mnemonic
+ * followed by its operands:
+ *
+ * ```text
+ * loop: out 'x'
+ *       add r0 r0 1
+ *       jmp loop
+ * ```
+ *
+ * Operands are registers (`r0` - `r7`), numbers, character literals or the
+ * names of labels and symbols.  `#` starts a comment, except in a character
+ * literal, so `out '#'`, `out ';'` and `out ' '` work as they read.
+ *
+ * Everything is checked before anything is written, so a bad patch fails
+ * here and not deep inside the VM: literals must be 0 - 32767, there are only
+ * eight registers, and instructions that store a result need a register to
+ * put it in.  Errors give the line and column, and a likely fix:
+ *
+ * ```text
+ * line 2, column 12: no register r8, there are r0 - r7
+ * line 5, column 7: unknown label or symbol 'lop', did you mean 'loop'?
+ * ```
+ */
+
+use std::collections::HashMap;
+
+use crate::symbols::Symbols;
+use crate::vm::{MNEMONICS, OPERANDS};
+
+// opcodes whose first operand is the register the result goes in
+const WRITES_REGISTER: [u16; 12] = [1, 3, 4, 5, 9, 10, 11, 12, 13, 14, 15, 20];
+
+// split s at the characters matching at, except inside character literals
+// so `out ';'` and `out ' '` stay whole
+fn split_unquoted(s: &str, at: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if !quoted && at(c) {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+// split source into (line number, line, statement) triples
+fn statements(src: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    src.lines().enumerate().flat_map(|(i, line)| {
+        let code = split_unquoted(line, |c| c == '#')[0];
+        split_unquoted(code, |c| c == ';')
+            .into_iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(move |s| (i + 1, line, s))
+    })
+}
+
+// the mnemonic and operands of a statement
+fn fields(stmt: &str) -> Vec<&str> {
+    split_unquoted(stmt, char::is_whitespace)
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+// the column (from 1) that s, a slice of line, starts at
+fn column(line: &str, s: &str) -> usize {
+    let offset = s.as_ptr() as usize - line.as_ptr() as usize;
+    line[..offset].chars().count() + 1
+}
+
+// strip any `label:` prefixes off a statement
+fn labels(stmt: &str) -> (Vec<&str>, &str) {
+    let mut labels = vec![];
+    let mut rest = stmt;
+    while let Some((label, after)) = rest.split_once(':') {
+        let label = label.trim();
+        if label.is_empty() || label.contains(char::is_whitespace) {
+            break;
+        }
+        labels.push(label);
+        rest = after.trim();
+    }
+    (labels, rest)
+}
+
+// edit distance, for suggesting what a typo meant
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// ", did you mean 'x'?" for the closest name, if any is close
+fn suggest<'a>(s: &str, names: impl Iterator<Item = &'a str>) -> String {
+    let best = names
+        .map(|name| (distance(s, name), name.len().abs_diff(s.len()), name))
+        .filter(|(d, _, _)| *d <= 2 && *d < s.len())
+        .min();
+    match best {
+        Some((_, _, name)) => format!(", did you mean '{}'?", name),
+        None => String::new(),
+    }
+}
+
+fn operand(
+    s: &str,
+    labels: &HashMap<&str, u16>,
+    symbols: &Symbols,
+) -> Result<u16, String> {
+    if let Some(n) = s.strip_prefix('r')
+        && let Ok(n) = n.parse::<u32>()
+    {
+        return match n {
+            0..=7 => Ok(32768 + n as u16),
+            _ => Err(format!("no register r{}, there are r0 - r7", n)),
+        };
+    }
+
+    if let Some(c) = s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        return match c {
+            "\\n" => Ok(10),
+            c if c.chars().count() == 1 => match c.chars().next().unwrap() {
+                c if (c as u32) < 32768 => Ok(c as u16),
+                c => {
+                    Err(format!("{} is character {}, past 32767", s, c as u32))
+                }
+            },
+            _ => Err(format!("invalid character literal {}", s)),
+        };
+    }
+
+    if let Ok(n) = s.parse::<i64>() {
+        return match n {
+            0..=32767 => Ok(n as u16),
+            // registers, if written out by hand
+            32768..=32775 => Ok(n as u16),
+            -32768..=-1 => Err(format!(
+                "{} is negative, numbers wrap at 32768 so use {}",
+                n,
+                n + 32768
+            )),
+            _ => Err(format!(
+                "{} is too big, literals are 0 - 32767 (and 32768 - 32775 are \
+                 r0 - r7)",
+                n
+            )),
+        };
+    }
+
+    if let Some(addr) = labels.get(s).copied().or_else(|| symbols.get(s)) {
+        return Ok(addr);
+    }
+    let names = labels.keys().copied().chain(symbols.iter().map(|(n, _)| n));
+    Err(format!("unknown label or symbol '{}'{}", s, suggest(s, names)))
+}
+
+// assemble src as if it were loaded at origin.  errors say where, as
+// "line 3, column 9: ..."
+pub fn assemble(
+    src: &str,
+    origin: u16,
+    symbols: &Symbols,
+) -> Result<Vec<u16>, String> {
+    let at = |line: usize, col: usize, e: String| {
+        format!("line {}, column {}: {}", line, col, e)
+    };
+
+    // first pass - find where every label lands
+    let mut addrs = HashMap::new();
+    let mut addr = origin as usize;
+    for (line, text, stmt) in statements(src) {
+        let (names, rest) = labels(stmt);
+        for name in names {
+            if addr > 32767 {
+                let e =
+                    format!("label {} would be past the end of memory", name);
+                return Err(at(line, column(text, name), e));
+            }
+            if addrs.insert(name, addr as u16).is_some() {
+                let e = format!("duplicate label {}", name);
+                return Err(at(line, column(text, name), e));
+            }
+        }
+
+        let mnemonic = fields(rest).first().copied();
+        if let Some(op) =
+            mnemonic.and_then(|m| MNEMONICS.iter().position(|n| *n == m))
+        {
+            addr += 1 + OPERANDS[op] as usize;
+        }
+    }
+
+    // second pass - encode
+    let mut words = vec![];
+    for (line, text, stmt) in statements(src) {
+        let (_, rest) = labels(stmt);
+        let fields = fields(rest);
+        let Some((&mnemonic, args)) = fields.split_first() else {
+            continue;
+        };
+
+        let op =
+            MNEMONICS.iter().position(|n| *n == mnemonic).ok_or_else(|| {
+                let e = format!(
+                    "unknown instruction '{}'{}",
+                    mnemonic,
+                    suggest(mnemonic, MNEMONICS.iter().copied())
+                );
+                at(line, column(text, mnemonic), e)
+            })?;
+
+        let n = OPERANDS[op] as usize;
+        if args.len() != n {
+            let e = format!(
+                "{} takes {} operands, found {}",
+                mnemonic,
+                n,
+                args.len()
+            );
+            let col = match args.get(n) {
+                Some(extra) => column(text, extra),
+                None => column(text, mnemonic),
+            };
+            return Err(at(line, col, e));
+        }
+
+        if origin as usize + words.len() + 1 + n > 32768 {
+            let e = "code runs past the end of memory".to_string();
+            return Err(at(line, column(text, mnemonic), e));
+        }
+
+        words.push(op as u16);
+        for (i, &arg) in args.iter().enumerate() {
+            let col = column(text, arg);
+            let word =
+                operand(arg, &addrs, symbols).map_err(|e| at(line, col, e))?;
+            if i == 0 && WRITES_REGISTER.contains(&(op as u16)) && word < 32768
+            {
+                let e = format!(
+                    "{} stores its result in a register, found {}",
+                    mnemonic, arg
+                );
+                return Err(at(line, col, e));
+            }
+            words.push(word);
+        }
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asm(src: &str) -> Result<Vec<u16>, String> {
+        assemble(src, 0, &Symbols::default())
+    }
+
+    #[test]
+    fn separators_in_character_literals() {
+        assert_eq!(asm("out '#'"), Ok(vec![19, 35]));
+        assert_eq!(asm("out ';'"), Ok(vec![19, 59]));
+        assert_eq!(asm("out ' '"), Ok(vec![19, 32]));
+        assert_eq!(asm("at: out ' ' # a space; not code"), Ok(vec![19, 32]));
+        assert_eq!(asm("out ';'; out '#'; halt"), Ok(vec![19, 59, 19, 35, 0]));
+    }
+
+    #[test]
+    fn comments_and_statements() {
+        assert_eq!(asm("noop # out 'x'\nhalt"), Ok(vec![21, 0]));
+        assert_eq!(asm("top: jmp top; halt"), Ok(vec![6, 0, 0]));
+        assert_eq!(asm("out '\\n'"), Ok(vec![19, 10]));
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let e = asm("noop\nset r8 1").unwrap_err();
+        assert_eq!(e, "line 2, column 5: no register r8, there are r0 - r7");
+        let e = asm("out ' ' 'x'").unwrap_err();
+        assert_eq!(e, "line 1, column 9: out takes 1 operands, found 2");
+    }
+}
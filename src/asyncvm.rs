@@ -0,0 +1,102 @@
+/*!
+ * Run a VM as a task on an async runtime, with the `tokio` feature.
+ *
+ * `run_async` plays a game between an async reader and writer, handing the
+ * thread back to the runtime every YIELD_EVERY instructions and whenever it
+ * waits on I/O, so one runtime can host any number of games without a
+ * thread each - long computations included:
+ *
+ * ```
+ * use synacor_challenge::asm;
+ * use synacor_challenge::symbols::Symbols;
+ * use synacor_challenge::vm::VM;
+ *
+ * // echo a line back after a lot of thinking
+ * let src = "
+ *     spin: add r1 r1 1; jt r1 spin
+ *     read: in r0; out r0; eq r2 r0 '\\n'; jf r2 read; halt
+ * ";
+ * let words = asm::assemble(src, 0, &Symbols::default()).unwrap();
+ * let rom: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+ *
+ * // both games share the one thread
+ * let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+ * rt.block_on(async {
+ *     let games: Vec<_> = ["hello\n", "there\n"]
+ *         .into_iter()
+ *         .map(|line| {
+ *             let mut vm = VM::new(rom.clone());
+ *             tokio::spawn(async move {
+ *                 let mut output = vec![];
+ *                 vm.run_async(line.as_bytes(), &mut output).await.unwrap();
+ *                 output
+ *             })
+ *         })
+ *         .collect();
+ *     for (game, line) in games.into_iter().zip(["hello\n", "there\n"]) {
+ *         assert_eq!(game.await.unwrap(), line.as_bytes());
+ *     }
+ * });
+ * ```
+ *
+ * Breakpoints and other events are dropped, a game run like this is meant
+ * to be played rather than debugged.
+ */
+
+use tokio::io::{
+    self, AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt,
+};
+use tokio::task;
+
+use crate::vm::VM;
+
+// instructions to run before letting other tasks have a turn
+pub const YIELD_EVERY: u64 = 10_000;
+
+impl VM {
+    // play the game, reading a line from input whenever it wants one and
+    // writing its output, until it halts or input runs out
+    pub async fn run_async<R, W>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut checked = self.steps();
+        while !self.is_halted() {
+            if self.wants_input() {
+                output.write_all(&self.take_output()).await?;
+                output.flush().await?;
+                let mut line = vec![];
+                if input.read_until(b'\n', &mut line).await? == 0 {
+                    break;
+                }
+                self.queue_input(&line);
+                continue;
+            }
+
+            self.step().map_err(io::Error::other)?;
+            self.take_events();
+            self.take_echo();
+
+            // the count can go backwards if the VM is restored elsewhere
+            let ran = self.steps().saturating_sub(checked);
+            if ran >= YIELD_EVERY || self.steps() < checked {
+                checked = self.steps();
+                // pass output along as it comes, not only at prompts
+                let out = self.take_output();
+                if !out.is_empty() {
+                    output.write_all(&out).await?;
+                    output.flush().await?;
+                }
+                task::yield_now().await;
+            }
+        }
+
+        output.write_all(&self.take_output()).await?;
+        output.flush().await
+    }
+}
@@ -0,0 +1,72 @@
+/*!
+ * Check the VM's arithmetic against the spec as it runs.
+ *
+ * With `--audit`, every eq, gt, add, mult, mod, and, or and not works its
+ * result out a second time the slow way - in 64 bits, straight from the
+ * spec, with the modulo taken at the end - and panics if the VM's answer is
+ * different.  That covers both the interpreter and the superblocks of the
+ * fast engine, so a shortcut in either that overflows or wraps the wrong way
+ * (ie. a `b + c` done in 16 bits) shows up straight away instead of as a
+ * strange bug a million instructions later.
+ *
+ * The conformance test ROMs run clean with it on, in both engines:
+ *
+ * ```
+ * use synacor_challenge::conformance;
+ * use synacor_challenge::vm::VM;
+ *
+ * for test in conformance::tests() {
+ *     for fast in [false, true] {
+ *         let mut vm = VM::new(test.rom.clone());
+ *         vm.set_audit(true);
+ *         vm.set_fast(fast);
+ *         vm.queue_input(&test.input);
+ *         while !vm.is_halted() && !vm.wants_input() {
+ *             vm.step().unwrap();
+ *         }
+ *     }
+ * }
+ * ```
+ */
+
+use crate::vm::MNEMONICS;
+
+// instructions whose result is checked
+pub fn is_audited(opcode: u16) -> bool {
+    matches!(opcode, 4 | 5 | 9..=14)
+}
+
+// what the spec says the instruction leaves in its register, given the
+// values of its operands
+pub fn expected(opcode: u16, b: u16, c: u16) -> Option<u64> {
+    let (b, c) = (b as u64, c as u64);
+    let result = match opcode {
+        4 => (b == c) as u64,
+        5 => (b > c) as u64,
+        9 => b + c,
+        10 => b * c,
+        11 => b.checked_rem(c)?,
+        12 => b & c,
+        13 => b | c,
+        14 => !b,
+        _ => return None,
+    };
+    Some(result % 32768)
+}
+
+// panic if the VM got something other than the spec's answer
+pub fn check(addr: u16, opcode: u16, b: u16, c: u16, got: u16) {
+    let Some(want) = expected(opcode, b, c) else {
+        return;
+    };
+    assert!(
+        got as u64 == want,
+        "audit: {} at {} with operands {} and {} gave {}, the spec says {}",
+        MNEMONICS[opcode as usize],
+        addr,
+        b,
+        c,
+        got,
+        want
+    );
+}
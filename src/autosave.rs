@@ -0,0 +1,68 @@
+/*!
+ * Save the game every so often without being asked.
+ *
+ * With `--autosave DIR` the shell writes the whole state to
+ * DIR/autosave-1.snap, autosave-2.snap and so on (see `binsave`) every
+ * AUTOSAVE_EVERY instructions (or `--autosave-every N`), going back round
+ * to the first after AUTOSAVE_KEEP of them.  So when an input goes badly
+ * there's a state from not long before it to `/load`, and `saves DIR` tells
+ * them apart.
+ */
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::binsave;
+use crate::saves;
+use crate::vm::VM;
+
+// instructions between autosaves
+pub const AUTOSAVE_EVERY: u64 = 10_000_000;
+
+// autosaves kept before the oldest is written over
+pub const AUTOSAVE_KEEP: usize = 5;
+
+pub struct Autosave {
+    dir: PathBuf,
+    every: u64,
+    // the instruction count to save at next, and saves made so far
+    next: u64,
+    count: usize,
+}
+
+impl Autosave {
+    pub fn new(dir: &str, every: u64, vm: &VM) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            every,
+            next: vm.steps() + every,
+            count: 0,
+        })
+    }
+
+    // save if it's time to
+    pub fn check(&mut self, vm: &VM) {
+        let steps = vm.steps();
+        // the count goes backwards with /undo and /load
+        if steps + self.every < self.next {
+            self.next = steps + self.every;
+        }
+        if steps < self.next {
+            return;
+        }
+        self.next = steps + self.every;
+
+        let name = format!(
+            "autosave-{}{}",
+            self.count % AUTOSAVE_KEEP + 1,
+            binsave::EXTENSION
+        );
+        let file = self.dir.join(name);
+        let file = file.to_string_lossy();
+        self.count += 1;
+        if let Err(e) = saves::write(&file, &binsave::export(vm), true) {
+            println!("autosave: {}", e);
+        }
+    }
+}
@@ -1,323 +1,97 @@
 /*!
  * Dissasmble that thing
  *
+ * Instructions are decoded by synacor_challenge::disasm, the same as the
+ * VM's own listings; words that don't decode are skipped one at a time.
+ *
+ * `dis --stable <rom>` prints a listing meant for diffing instead, see
+ * synacor_challenge::listing
+ *
  * Author: Dave Eddy <ysap@daveeddy.com>
  * Date: December 21, 2025
  * License: MIT
  */
 
-use log::{debug, info, trace};
 use std::env;
 use std::fs;
-use std::io::{self, Read, Write};
-
-#[derive(Default)]
-struct VM {
-    ram: Vec<u8>,
-    registers: [u16; 8],
-    addr: u16, // addr pointer
-    stack: Vec<u16>,
-    running: bool,
-    level: usize,
-    pub input_buffer: Vec<u8>,
-}
+use synacor_challenge::disasm::{self, Instruction};
+use synacor_challenge::listing;
+use synacor_challenge::ram::Ram;
 
-enum ValueType {
-    Register(u16),
-    Literal(u16),
+// the register number of an operand meant to be one
+fn register(word: u16) -> u16 {
+    word % 32768
 }
 
-#[allow(dead_code)]
-impl VM {
-    fn new(rom: Vec<u8>) -> Self {
-        Self { ram: rom, running: true, ..Default::default() }
-    }
-
-    fn get_ram(&self, addr: u16) -> u16 {
-        let ptr = (addr * 2) as usize;
-        let low = self.ram[ptr] as u16;
-        let high = self.ram[ptr + 1] as u16;
-
-        let num = (high << 8) + low;
-        trace!(
-            "self.get_ram: ptr={} (low={} high={}) num={}",
-            ptr, low, high, num
-        );
-
-        num
-    }
-
-    // get the raw number from the rom
-    fn get_ram_value(&self, addr: u16) -> ValueType {
-        let num = self.get_ram(addr);
-
-        if num < 32768 {
-            // it's a literal value
-            ValueType::Literal(num)
-        } else if num < 32776 {
-            // it's a register
-            ValueType::Register(num % 32768)
-        } else {
-            // it's invalid
-            panic!("get_value found invalid number at addr {}: {}", addr, num);
-        }
-    }
-
-    // get the register at the address - fails if not a register
-    fn get_register(&self, addr: u16) -> u16 {
-        match self.get_ram_value(addr) {
-            ValueType::Register(n) => n,
-            ValueType::Literal(_) => panic!(),
+fn describe(ins: &Instruction) -> String {
+    let ops = &ins.operands;
+    match ins.opcode {
+        // halt: 0
+        0 => "halt".to_string(),
+        // set: 1 a b
+        1 => format!("set <{}> = {}", register(ops[0]), ops[1]),
+        // push: 2 a
+        2 => format!("push {}", ops[0]),
+        // pop: 3 a
+        3 => "pop".to_string(),
+        // eq: 4 a b c
+        4 => format!("eq {}=({} == {})", ops[0], ops[1], ops[2]),
+        // gt: 5 a b c
+        5 => format!("gt {}=({} > {})", ops[0], ops[1], ops[2]),
+        // jmp: 6 a
+        6 => format!("jmp {}", ops[0]),
+        // jt: 7 a b
+        7 => format!("jt ({} != 0 -> {})", ops[0], ops[1]),
+        // jf: 8 a b
+        8 => format!("jf ({} == 0 -> {})", ops[0], ops[1]),
+        // add, mult, mod, and, or: a b c
+        9..=13 => {
+            let sign = ["+", "*", "%", "&", "|"][ins.opcode as usize - 9];
+            format!(
+                "{} <{}> = {} {} {}",
+                ins.mnemonic(),
+                register(ops[0]),
+                ops[1],
+                sign,
+                ops[2]
+            )
         }
+        // not: 14 a b
+        14 => format!("not <{}> = ~{}", ops[0], ops[1]),
+        // rmem: 15 a b
+        15 => format!("rmem {}", ops[1]),
+        // wmem: 16 a b
+        16 => format!("wmem {} = {}", ops[0], ops[1]),
+        // call: 17 a
+        17 => format!("call {}", ops[0]),
+        // ret, out, in
+        18..=20 => ins.mnemonic().to_string(),
+        // noop: 21
+        _ => "no-op".to_string(),
     }
+}
 
-    // get the value at the address - either grabbing the literal value or
-    // traversing into the register itself and using that value
-    fn get_value(&self, addr: u16) -> u16 {
-        match self.get_ram_value(addr) {
-            ValueType::Register(r) => {
-                //                info!("(addr={}) register {} read: {}", addr, r, self.registers[r as usize]);
-                self.registers[r as usize]
-            }
-            ValueType::Literal(n) => n,
-        }
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    if args.len() == 2 && args[0] == "--stable" {
+        print!("{}", listing::stable(&fs::read(&args[1]).unwrap()));
+        return;
     }
+    let bin_file = &args[0];
+    let words = Ram::new(&fs::read(bin_file).unwrap()).words();
 
-    fn step(&self, mut addr: u16) -> u16 {
-        // grab the instruction to process
-        let instruction = self.get_value(addr);
-
-        match instruction {
-            0 => {
-                // halt
-                // stop execution and terminate the program
-                log_assembly(addr, "halt");
-                addr += 1;
-            }
-            1 => {
-                // set: 1 a b
-                // set register <a> to the value of <b>
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-
-                log_assembly(addr, &format!("set <{}> = {}", a, b));
-
-                addr += 3;
-            }
-            2 => {
-                // push: 2 a
-                // push <a> onto the stack
-                let a = self.get_ram(addr + 1);
-
-                log_assembly(addr, &format!("push {}", a));
-
-                addr += 2;
-            }
-            3 => {
-                // pop: 3 a
-                // remove the top element from the stack and write it into <a>;
-                // empty stack = error
-                log_assembly(addr, "pop");
-
-                addr += 2;
-            }
-            4 => {
-                // eq: 4 a b c
-                // set <a> to 1 if <b> is equal to <c>; set it to 0 otherwise
-                let a = self.get_ram(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("eq {}=({} == {})", a, b, c));
-
-                addr += 4;
-            }
-            5 => {
-                // gt: 5 a b c
-                // set <a> to 1 if <b> is greater than <c>; set it to 0 otherwise
-                let a = self.get_ram(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("gt {}=({} > {})", a, b, c));
-
-                addr += 4;
-            }
-            6 => {
-                // jmp: 6 a
-                // jump to <a>
-                let a = self.get_ram(addr + 1);
-                log_assembly(addr, &format!("jmp {}", a));
-
-                addr += 2;
-            }
-            7 => {
-                // jt: 7 a b
-                // if <a> is nonzero, jump to <b>
-                let a = self.get_ram(addr + 1);
-                let b = self.get_ram(addr + 2);
-
-                log_assembly(addr, &format!("jt ({} != 0 -> {})", a, b));
-
-                addr += 3;
-            }
-            8 => {
-                // jf: 8 a b
-                // if <a> is zero, jump to <b>
-                let a = self.get_ram(addr + 1);
-                let b = self.get_ram(addr + 2);
-
-                log_assembly(addr, &format!("jf ({} == 0 -> {})", a, b));
-
-                addr += 3;
-            }
-            9 => {
-                // add: 9 a b c
-                // assign into <a> the sum of <b> and <c> (modulo 32768)
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("add <{}> = {} + {}", a, b, c));
-
-                addr += 4;
-            }
-            10 => {
-                // mult: 10 a b c
-                // store into <a> the product of <b> and <c> (modulo 32768)
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("mult <{}> = {} * {}", a, b, c));
-
-                addr += 4;
-            }
-            11 => {
-                // mod: 11 a b c
-                // store into <a> the remainder of <b> divided by <c>
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("mod <{}> = {} % {}", a, b, c));
-
-                addr += 4;
-            }
-            12 => {
-                // and: 12 a b c
-                // stores into <a> the bitwise and of <b> and <c>
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("and <{}> = {} & {}", a, b, c));
-
-                addr += 4;
-            }
-            13 => {
-                // or: 13 a b c
-                // stores into <a> the bitwise or of <b> and <c>
-                let a = self.get_register(addr + 1);
-                let b = self.get_ram(addr + 2);
-                let c = self.get_ram(addr + 3);
-
-                log_assembly(addr, &format!("or <{}> = {} | {}", a, b, c));
-
-                addr += 4;
-            }
-            14 => {
-                // not: 14 a b
-                // stores 15-bit bitwise inverse of <b> in <a>
-                let a = self.get_ram(addr + 1);
-                let b = self.get_value(addr + 2);
-
-                log_assembly(addr, &format!("not <{}> = ~{}", a, b));
-
-                addr += 3;
-            }
-            15 => {
-                // rmem: 15 a b
-                // read memory at address <b> and write it to <a>
-                let b = self.get_ram(addr + 2);
-
-                log_assembly(addr, &format!("rmem {}", b));
-
-                addr += 3;
-            }
-            16 => {
-                // wmem: 16 a b
-                // write the value from <b> into memory at address <a>
-                let a = self.get_ram(addr + 1);
-                let b = self.get_ram(addr + 2);
-
-                // this is how we made a big number
-                // (high << 8) + low
-
-                log_assembly(addr, &format!("wmem {} = {}", a, b));
-
-                addr += 3;
-            }
-            17 => {
-                // call: 17 a
-                // write the address of the next instruction to the stack and
-                // jump to <a>
-                let a = self.get_ram(addr + 1);
-                log_assembly(addr, &format!("call {}", a));
-                addr += 2;
-            }
-            18 => {
-                // ret: 18
-                // remove the top element from the stack and jump to it; empty
-                // stack = halt
-                log_assembly(addr, "ret");
-                addr += 1;
-            }
-            19 => {
-                // out: 19 a
-                // write the character represented by ascii code <a> to the
-                // terminal
-                log_assembly(addr, "out");
-                addr += 2;
-            }
-            20 => {
-                // in: 20 a
-                // read a character from the terminal and write its ascii
-                // code to <a>; it can be assumed that once input starts, it
-                // will continue until a newline is encountered; this means
-                // that you can safely read whole lines from the keyboard
-                // instead of having to figure out how to read individual
-                // characters
-                log_assembly(addr, "in");
-                addr += 2;
-            }
-            21 => {
-                // no-op
-                // no operation
-                log_assembly(addr, "no-op");
-                addr += 1;
+    let mut addr = 0;
+    while (addr as usize) < words.len() {
+        match disasm::decode(&words, addr) {
+            Some(ins) => {
+                println!("{} {}", addr, describe(&ins));
+                addr += ins.len() as u16;
             }
-            n => {
+            None => {
                 // uh oh
-                eprintln!("unknown instruction: {}", n);
+                eprintln!("unknown instruction: {}", words[addr as usize]);
                 addr += 1;
             }
         }
-        addr
-    }
-}
-
-fn log_assembly(addr: u16, op: &str) {
-    println!("{} {}", addr, op);
-}
-
-fn main() {
-    let args: Vec<_> = env::args().skip(1).collect();
-    let bin_file = &args[0];
-    let mut vm = VM::new(fs::read(bin_file).unwrap());
-
-    let mut addr = 0;
-    loop {
-        addr = vm.step(addr);
     }
 }
@@ -0,0 +1,187 @@
+/*!
+ * Run the same walkthrough against a directory of ROMs
+ *
+ * Author: Dave Eddy <ysap@daveeddy.com>
+ * Date: December 21, 2025
+ * License: MIT
+ */
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+
+use synacor_challenge::codes;
+use synacor_challenge::vm::VM;
+
+// give up on a ROM that runs this long without asking for input
+static MAX_STEPS: u64 = 1_000_000_000;
+
+// per-ROM tweaks - one line per ROM file name followed by `key=value` pairs:
+//
+//   challenge-2.bin r7=25734 script=alt-walkthrough.txt
+//
+// `r0` through `r7` set a register before the ROM starts and `script`
+// replaces the walkthrough for that ROM
+#[derive(Default, Clone)]
+struct Overrides {
+    registers: Vec<(u16, u16)>,
+    script: Option<String>,
+}
+
+fn parse_overrides(data: &str) -> Result<HashMap<String, Overrides>, String> {
+    let mut all = HashMap::new();
+
+    for (i, line) in data.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(rom) = fields.next() else { continue };
+        if rom.starts_with('#') {
+            continue;
+        }
+
+        let mut overrides = Overrides::default();
+        for field in fields {
+            let err =
+                || format!("line {}: invalid override '{}'", i + 1, field);
+            let (key, value) = field.split_once('=').ok_or_else(err)?;
+            match key {
+                "script" => overrides.script = Some(value.to_string()),
+                r if r.starts_with('r') => {
+                    let reg: u16 = r[1..].parse().map_err(|_| err())?;
+                    let value: u16 = value.parse().map_err(|_| err())?;
+                    if reg > 7 || value > 32767 {
+                        return Err(err());
+                    }
+                    overrides.registers.push((reg, value));
+                }
+                _ => return Err(err()),
+            }
+        }
+        all.insert(rom.to_string(), overrides);
+    }
+
+    Ok(all)
+}
+
+struct Report {
+    passed: bool,
+    reason: String,
+    codes: Vec<String>,
+}
+
+// feed the script one line per prompt, passing if every line is consumed
+// without the VM halting or crashing
+fn run(rom: Vec<u8>, script: &str, overrides: &Overrides) -> Report {
+    let mut vm = VM::new(rom);
+    for (reg, value) in &overrides.registers {
+        vm.set_register(*reg, *value);
+    }
+
+    let mut transcript = vec![];
+    let mut lines = script.lines();
+    let mut steps = 0;
+
+    let reason = loop {
+        if vm.is_halted() {
+            match lines.next() {
+                Some(line) => break format!("halted before input '{}'", line),
+                None => break String::new(),
+            }
+        }
+
+        if vm.wants_input() {
+            match lines.next() {
+                Some(line) => {
+                    vm.queue_input(line.as_bytes());
+                    vm.queue_input(b"\n");
+                }
+                None => break String::new(),
+            }
+        }
+
+        if let Err(e) = vm.step() {
+            break e.to_string();
+        }
+        transcript.extend(vm.take_output());
+
+        steps += 1;
+        if steps >= MAX_STEPS {
+            break format!("gave up after {} instructions", steps);
+        }
+    };
+
+    let codes = codes::find_codes(&String::from_utf8_lossy(&transcript));
+    Report { passed: reason.is_empty(), reason, codes }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("usage: matrix <rom-dir> <script> [overrides]");
+        process::exit(2);
+    }
+
+    let script = fs::read_to_string(&args[1]).unwrap();
+    let overrides = match args.get(2) {
+        Some(f) => parse_overrides(&fs::read_to_string(f).unwrap())
+            .unwrap_or_else(|e| {
+                eprintln!("{}: {}", f, e);
+                process::exit(1);
+            }),
+        None => HashMap::new(),
+    };
+
+    let mut roms: Vec<_> = fs::read_dir(&args[0])
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bin"))
+        .collect();
+    roms.sort();
+
+    let mut failed = 0;
+    for path in &roms {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let overrides = overrides.get(&name).cloned().unwrap_or_default();
+        let script = match &overrides.script {
+            Some(f) => fs::read_to_string(Path::new(f)).unwrap(),
+            None => script.clone(),
+        };
+
+        let rom = fs::read(path).unwrap();
+        let report = panic::catch_unwind(AssertUnwindSafe(|| {
+            run(rom, &script, &overrides)
+        }))
+        .unwrap_or_else(|_| Report {
+            passed: false,
+            reason: "VM crashed".to_string(),
+            codes: vec![],
+        });
+
+        if !report.passed {
+            failed += 1;
+        }
+
+        println!(
+            "{:<24} {:<4} {:>2} codes  {}",
+            name,
+            if report.passed { "pass" } else { "FAIL" },
+            report.codes.len(),
+            report.codes.join(" ")
+        );
+        if !report.passed {
+            println!("{:<24} {}", "", report.reason);
+        }
+    }
+
+    println!(
+        "{} ROMs, {} passed, {} failed",
+        roms.len(),
+        roms.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
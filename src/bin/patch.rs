@@ -0,0 +1,121 @@
+/*!
+ * Apply assembly patches to a ROM
+ *
+ * A patch file has one patch per line, giving where to write (an address or
+ * a symbol) and the code to assemble there:
+ *
+ *   # skip the teleporter confirmation
+ *   at teleporter_check: set r0 6; ret
+ *
+ * Each `at` takes a single line, with `;` between instructions; a patch
+ * can't continue on the next line.
+ *
+ * Author: Dave Eddy <ysap@daveeddy.com>
+ * Date: December 21, 2025
+ * License: MIT
+ */
+
+use std::env;
+use std::fs;
+use std::process;
+
+use synacor_challenge::asm;
+use synacor_challenge::symbols::Symbols;
+
+fn usage() -> ! {
+    eprintln!("usage: patch [--symbols <file>] <rom> <patch-file> <out-rom>");
+    eprintln!();
+    eprintln!("one patch per line: at <addr|symbol>: <code>; <code>; ...");
+    process::exit(2);
+}
+
+fn fail(msg: String) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+// assemble one `at <addr>: <code>` line into ram
+fn apply(
+    line: &str,
+    symbols: &Symbols,
+    ram: &mut [u8],
+) -> Result<String, String> {
+    let Some((target, code)) =
+        line.trim_start().strip_prefix("at ").and_then(|l| l.split_once(':'))
+    else {
+        return Err(format!("expected `at <addr>: <code>`, found '{}'", line));
+    };
+
+    let target = target.trim();
+    let addr = match target.parse::<u16>() {
+        Ok(addr) => addr,
+        Err(_) => symbols
+            .get(target)
+            .ok_or_else(|| format!("unknown symbol '{}'", target))?,
+    };
+
+    // the code is a single line so the assembler's line numbers are noise,
+    // and its columns are counted from the start of the code
+    let words = asm::assemble(code, addr, symbols).map_err(|e| {
+        let e = e.trim_start_matches("line 1, ");
+        let Some((col, msg)) = e
+            .strip_prefix("column ")
+            .and_then(|e| e.split_once(": "))
+            .and_then(|(col, msg)| Some((col.parse::<usize>().ok()?, msg)))
+        else {
+            return e.to_string();
+        };
+        let offset = line.len() - code.len();
+        format!("column {}: {}", col + offset, msg)
+    })?;
+
+    let end = (addr as usize + words.len()) * 2;
+    if end > ram.len() {
+        return Err(format!("patch at {} runs past the end of the rom", addr));
+    }
+    for (j, word) in words.iter().enumerate() {
+        let ptr = (addr as usize + j) * 2;
+        ram[ptr] = (word & 0xff) as u8;
+        ram[ptr + 1] = (word >> 8) as u8;
+    }
+
+    Ok(format!("patched {} words at {} ({})", words.len(), addr, target))
+}
+
+fn main() {
+    let mut symbols = Symbols::default();
+    let mut args = vec![];
+
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--symbols" => {
+                let file = argv.next().unwrap_or_else(|| usage());
+                symbols = Symbols::load(&file).unwrap_or_else(|e| fail(e));
+            }
+            _ => args.push(arg),
+        }
+    }
+    let [rom, patch, out] = &args[..] else { usage() };
+
+    let mut ram = fs::read(rom)
+        .unwrap_or_else(|e| fail(format!("failed to read {}: {}", rom, e)));
+    let patches = fs::read_to_string(patch)
+        .unwrap_or_else(|e| fail(format!("failed to read {}: {}", patch, e)));
+
+    for (i, line) in patches.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim_start().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        match apply(line, &symbols, &mut ram) {
+            Ok(msg) => println!("{}", msg),
+            Err(e) => fail(format!("{}:{}: {}", patch, i + 1, e)),
+        }
+    }
+
+    fs::write(out, &ram)
+        .unwrap_or_else(|e| fail(format!("failed to write {}: {}", out, e)));
+    println!("wrote {}", out);
+}
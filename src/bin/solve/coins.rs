@@ -0,0 +1,70 @@
+use std::process;
+
+// the coins found in the ruins and the number of dots on each
+static COINS: &[(&str, u32)] =
+    &[("red", 2), ("corroded", 3), ("shiny", 5), ("concave", 7), ("blue", 9)];
+
+// the equation carved into the wall of the ruins
+// _ + _ * _^2 + _^3 - _ = 399
+static TARGET: u32 = 399;
+
+fn equation(v: &[u32]) -> u32 {
+    (v[0] + v[1] * v[2].pow(2) + v[3].pow(3)).wrapping_sub(v[4])
+}
+
+// heap's algorithm - call f with every ordering of items
+fn permutations<T: Clone>(items: &mut [T], k: usize, f: &mut impl FnMut(&[T])) {
+    if k <= 1 {
+        f(items);
+        return;
+    }
+
+    for i in 0..k - 1 {
+        permutations(items, k - 1, f);
+        if k.is_multiple_of(2) {
+            items.swap(i, k - 1);
+        } else {
+            items.swap(0, k - 1);
+        }
+    }
+    permutations(items, k - 1, f);
+}
+
+fn usage() -> ! {
+    eprintln!("usage: solve coins [<name>=<value> ...]");
+    process::exit(2);
+}
+
+pub fn main(args: impl Iterator<Item = String>) {
+    let mut coins: Vec<(String, u32)> =
+        COINS.iter().map(|(name, v)| (name.to_string(), *v)).collect();
+
+    // allow the coin values to be overridden, ie. `shiny=5`
+    for arg in args {
+        let Some((name, value)) = arg.split_once('=') else { usage() };
+        let value = value.parse().unwrap_or_else(|_| usage());
+        match coins.iter_mut().find(|(n, _)| n == name) {
+            Some(coin) => coin.1 = value,
+            None => {
+                eprintln!("unknown coin: {}", name);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut found = false;
+    let n = coins.len();
+    permutations(&mut coins, n, &mut |order| {
+        let values: Vec<_> = order.iter().map(|(_, v)| *v).collect();
+        if equation(&values) == TARGET {
+            let names: Vec<_> = order.iter().map(|(n, _)| n.as_str()).collect();
+            println!("{}", names.join(" "));
+            found = true;
+        }
+    });
+
+    if !found {
+        eprintln!("no ordering of the coins solves the equation");
+        process::exit(1);
+    }
+}
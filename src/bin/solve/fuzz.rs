@@ -0,0 +1,211 @@
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::process;
+
+use synacor_challenge::driver::Game;
+use synacor_challenge::novelty::Novelty;
+use synacor_challenge::parser;
+use synacor_challenge::saves;
+use synacor_challenge::vm::{Snapshot, VM};
+
+// verbs tried when no --verbs file is given.  the game's help lists some of
+// these, the rest are common in text adventures
+static VERBS: &[&str] = &[
+    "look", "go", "take", "drop", "use", "inv", "help", "talk", "open",
+    "close", "read", "push", "pull", "climb", "eat", "drink", "wait", "jump",
+    "say", "give", "light", "xyzzy", "plugh",
+];
+
+// nouns tried on top of what the room and inventory list
+static NOUNS: &[&str] = &["me", "self", "door", "wall", "floor", "up", "down"];
+
+// input the game surely doesn't know, to compare everything else against.
+// state that differs between the two is where the game keeps the line
+// typed, so differences there don't count - they're different lengths, and
+// longer than anything tried, so the whole buffer and its length show up
+static GIBBERISH: [&str; 2] = [
+    "qzqzqzqzqzqzqzqzqzqzqzqzqzqzqzqzqzqzqzqz",
+    "jvjvjvjvjvjvjvjvjvjvjvjvjvjvjvjvjvjvjvj",
+];
+
+// lines of each new output to show
+static EXCERPT: usize = 4;
+
+// one line per word, blank lines and #comments skipped
+fn load_words(file: &str) -> Vec<String> {
+    let data = fs::read_to_string(file).unwrap_or_else(|e| {
+        eprintln!("{}: {}", file, e);
+        process::exit(1);
+    });
+    data.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+// play the input from the state, returning the output and the registers
+// and memory after
+fn try_input(snapshot: &Snapshot, input: &str) -> (String, Vec<u16>) {
+    let mut vm = VM::new(vec![]);
+    vm.restore(snapshot.clone());
+    let mut game = Game::from_vm(vm);
+    let output = game.command(input);
+    let mut state = game.vm().registers().to_vec();
+    state.extend(game.vm().ram().words());
+    (output, state)
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: solve fuzz [--verbs <file>] [--nouns <file>] [--threads <n>] <rom|save.json>"
+    );
+    process::exit(2);
+}
+
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut verbs: Vec<String> = VERBS.iter().map(|v| v.to_string()).collect();
+    let mut nouns: Vec<String> = NOUNS.iter().map(|n| n.to_string()).collect();
+    let mut threads = 0;
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verbs" => {
+                verbs = load_words(&args.next().unwrap_or_else(|| usage()))
+            }
+            "--nouns" => {
+                nouns = load_words(&args.next().unwrap_or_else(|| usage()))
+            }
+            "--threads" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                threads = n.parse().unwrap_or_else(|_| usage());
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage());
+
+    // 0 leaves it up to rayon, one thread per cpu
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .unwrap();
+
+    let vm = saves::load(&file).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let game = Game::from_vm(vm);
+    if game.is_over() {
+        eprintln!("the game is over, nothing to fuzz");
+        process::exit(1);
+    }
+    let snapshot = game.vm().snapshot();
+
+    // what's around to try the verbs on
+    let mut probe = Game::from_vm({
+        let mut vm = VM::new(vec![]);
+        vm.restore(snapshot.clone());
+        vm
+    });
+    let look = probe.command("look");
+    if let Some(room) = parser::parse_room(&look) {
+        nouns.extend(room.items);
+        nouns.extend(room.exits);
+    }
+    nouns.extend(probe.inventory());
+
+    // text that's already been seen where the fuzzing starts
+    let mut novelty = Novelty::default();
+    novelty.add_text(game.intro());
+    novelty.add_text(&look);
+    nouns.sort();
+    nouns.dedup();
+
+    let mut inputs = vec![];
+    for verb in &verbs {
+        inputs.push(verb.clone());
+        for noun in &nouns {
+            inputs.push(format!("{} {}", verb, noun));
+        }
+    }
+
+    let (baseline, baseline_state) = try_input(&snapshot, GIBBERISH[0]);
+    let (_, other) = try_input(&snapshot, GIBBERISH[1]);
+    novelty.add_text(&baseline);
+    let typed: HashSet<usize> = (0..baseline_state.len())
+        .filter(|i| baseline_state.get(*i) != other.get(*i))
+        .collect();
+    eprintln!(
+        "trying {} inputs ({} verbs, {} nouns)",
+        inputs.len(),
+        verbs.len(),
+        nouns.len()
+    );
+
+    // what each input printed, and if it changed registers or memory other
+    // than the line typed compared to nonsense
+    let results: Vec<(String, String, bool)> = inputs
+        .par_iter()
+        .map(|input| {
+            let (output, after) = try_input(&snapshot, input);
+            let changed = after.len() != baseline_state.len()
+                || (0..after.len()).any(|i| {
+                    after[i] != baseline_state[i] && !typed.contains(&i)
+                });
+            (input.clone(), output, changed)
+        })
+        .collect();
+
+    // inputs by what they printed, for everything the game didn't answer
+    // like it does nonsense
+    let mut outputs: BTreeMap<&str, (Vec<&str>, bool)> = BTreeMap::new();
+    for (input, output, changed) in &results {
+        if output == &baseline && !changed {
+            continue;
+        }
+        let entry = outputs.entry(output).or_default();
+        entry.0.push(input);
+        entry.1 |= changed;
+    }
+
+    let mut novel = 0;
+    for (output, (inputs, changed)) in &outputs {
+        println!(
+            "{}{}",
+            inputs.join(", "),
+            if *changed { "  (changes state)" } else { "" }
+        );
+
+        // every line not seen before, marked, or failing that a few lines
+        // to say what it was
+        let new: Vec<_> =
+            output.lines().filter(|l| novelty.is_new(l)).collect();
+        if !new.is_empty() {
+            novel += 1;
+            for line in new {
+                println!("  * {}", line);
+            }
+            continue;
+        }
+        match output.lines().find_map(parser::title) {
+            Some(title) => println!("    == {} ==", title),
+            None => {
+                let lines = output.lines().filter(|l| !l.trim().is_empty());
+                for line in lines.take(EXCERPT) {
+                    println!("    {}", line);
+                }
+            }
+        }
+    }
+    println!(
+        "{} of {} inputs did something, {} different responses, {} with new text",
+        outputs.values().map(|(i, _)| i.len()).sum::<usize>(),
+        results.len(),
+        outputs.len(),
+        novel
+    );
+}
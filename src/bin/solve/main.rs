@@ -0,0 +1,34 @@
+/*!
+ * Solvers for the puzzles found along the way
+ *
+ * Author: Dave Eddy <ysap@daveeddy.com>
+ * Date: December 21, 2025
+ * License: MIT
+ */
+
+use std::env;
+use std::process;
+
+mod coins;
+mod fuzz;
+mod maze;
+mod orb;
+mod teleporter;
+
+fn usage() -> ! {
+    eprintln!("usage: solve <orb|teleporter|coins|maze|fuzz> [args...]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+
+    match args.next().as_deref() {
+        Some("orb") => orb::main(args),
+        Some("teleporter") => teleporter::main(args),
+        Some("coins") => coins::main(args),
+        Some("maze") => maze::main(args),
+        Some("fuzz") => fuzz::main(args),
+        _ => usage(),
+    }
+}
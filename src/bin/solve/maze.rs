@@ -0,0 +1,454 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use synacor_challenge::driver::Game;
+use synacor_challenge::parser::{self, Room};
+use synacor_challenge::roomgraph::{self, Graph};
+use synacor_challenge::saves;
+use synacor_challenge::vm::{Snapshot, VM};
+
+// moves to explore from the starting room
+static DEPTH: usize = 20;
+
+// state hashes to remember before forgetting the least recently seen
+static MAX_STATES: usize = 1_000_000;
+
+// states showing the same text to explore, 0 for no limit
+static SIMILAR: usize = 0;
+
+// states to carry from one level of the search to the next.  memory is
+// copy-on-write (see `ram`), so each one only costs a pointer per page
+// and the pages its moves have written to, a few KB - 10,000 of them is tens
+// of MB.  the cap is really on time: every state in the frontier is played
+// forward once per exit at the next level
+static MAX_FRONTIER: usize = 10_000;
+
+// a state reached by the search and how it got there
+struct Node {
+    snapshot: Snapshot,
+    path: Vec<String>,
+    room: Room,
+}
+
+// state hashes seen so far.  when full the least recently seen are
+// forgotten so memory stays bounded, at the cost of maybe exploring some
+// states twice
+struct Visited {
+    seen: HashMap<u64, u64>,
+    tick: u64,
+    max: usize,
+    // how many states have shown each text, by its hash.  states that differ
+    // only in a move counter or a timer all look the same at the prompt, so
+    // past `similar` of them the rest are pruned
+    texts: HashMap<u64, usize>,
+    similar: usize,
+    pruned: usize,
+}
+
+impl Visited {
+    fn new(max: usize, similar: usize) -> Self {
+        Self {
+            seen: HashMap::new(),
+            tick: 0,
+            max,
+            texts: HashMap::new(),
+            similar,
+            pruned: 0,
+        }
+    }
+
+    // true if a new state showing text should still be explored
+    fn alike(&mut self, text: &str) -> bool {
+        if self.similar == 0 {
+            return true;
+        }
+        let mut h = DefaultHasher::new();
+        text.hash(&mut h);
+        let count = self.texts.entry(h.finish()).or_default();
+        if *count == self.similar {
+            self.pruned += 1;
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    // true if the state hasn't been seen (or has been forgotten)
+    fn insert(&mut self, hash: u64) -> bool {
+        self.tick += 1;
+        let new = self.seen.insert(hash, self.tick).is_none();
+
+        if self.seen.len() > self.max {
+            // drop the oldest quarter
+            let mut ticks: Vec<_> = self.seen.values().copied().collect();
+            ticks.sort_unstable();
+            let cutoff = ticks[ticks.len() / 4];
+            self.seen.retain(|_, t| *t > cutoff);
+        }
+
+        new
+    }
+}
+
+// progress of a search, saved after every level so it can be picked up
+// again with --resume.  states are kept as the moves to them from the start
+// rather than their memory, and played again when resuming.  the states
+// seen before aren't kept, so a few may be explored twice
+#[derive(Default, Serialize, Deserialize)]
+struct Checkpoint {
+    // levels searched so far, and the states found
+    level: usize,
+    states: usize,
+    // the rooms found, by title and description
+    rooms: HashSet<(String, String)>,
+    graph: Graph,
+    frontier: Vec<Vec<String>>,
+}
+
+impl Checkpoint {
+    // None if there's no checkpoint yet
+    fn load(file: &str) -> Option<Self> {
+        let data = fs::read_to_string(file).ok()?;
+        let checkpoint = serde_json::from_str(&data).unwrap_or_else(|e| {
+            eprintln!("invalid checkpoint {}: {}", file, e);
+            process::exit(1);
+        });
+        Some(checkpoint)
+    }
+
+    fn save(&mut self, file: &str, frontier: &[Node]) -> Result<(), String> {
+        self.frontier = frontier.iter().map(|n| n.path.clone()).collect();
+        let data = serde_json::to_string(self).unwrap();
+        saves::write(file, data.as_bytes(), true)
+    }
+}
+
+fn resume(snapshot: &Snapshot) -> Game {
+    let mut vm = VM::new(vec![]);
+    vm.restore(snapshot.clone());
+    Game::from_vm(vm)
+}
+
+// try every exit of the room, returning the states not seen before and
+// where each exit went
+fn expand(
+    node: &Node,
+    visited: &Mutex<Visited>,
+    volatile: &[Range<u16>],
+) -> (Vec<Node>, Vec<(String, Room)>) {
+    let mut children = vec![];
+    let mut exits = vec![];
+
+    for exit in &node.room.exits {
+        let mut game = resume(&node.snapshot);
+        let output = game.command(&format!("go {}", exit));
+        if game.is_over() {
+            continue;
+        }
+        let Some(room) = parser::parse_room(&output) else {
+            continue;
+        };
+        exits.push((exit.clone(), room.clone()));
+
+        let hash = game.vm().state_hash_excluding(volatile);
+        let mut visited = visited.lock().unwrap();
+        if !visited.insert(hash) || !visited.alike(&output) {
+            continue;
+        }
+        drop(visited);
+
+        let mut path = node.path.clone();
+        path.push(exit.clone());
+        children.push(Node { snapshot: game.vm().snapshot(), path, room });
+    }
+
+    (children, exits)
+}
+
+// the state at the end of path, played from the start again
+fn replay(start: &Snapshot, path: Vec<String>) -> Option<Node> {
+    let mut game = resume(start);
+    let mut output = String::new();
+    for exit in &path {
+        output = game.command(&format!("go {}", exit));
+        if game.is_over() {
+            return None;
+        }
+    }
+    let room = parser::parse_room(&output)?;
+    Some(Node { snapshot: game.vm().snapshot(), path, room })
+}
+
+fn progress_bar(len: usize, level: usize) -> ProgressBar {
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{elapsed_precise} [{bar:40}] {pos}/{len} states at depth {msg}",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+    bar.set_message(level.to_string());
+    bar
+}
+
+// an inclusive range of addresses, <start>-<end>
+fn parse_volatile(s: &str) -> Result<Range<u16>, String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| "expected <start>-<end>".to_string())?;
+    let parse = |n: &str| {
+        n.parse::<u16>()
+            .ok()
+            .filter(|n| *n < 32768)
+            .ok_or_else(|| format!("{} isn't an address (0 - 32767)", n))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        return Err(format!("{} is after {}", start, end));
+    }
+    Ok(start..end + 1)
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: solve maze [--depth <n>] [--max-states <n>] [--max-frontier <n>] [--threads <n>]"
+    );
+    eprintln!(
+        "                 [--similar <n>] [--volatile <start>-<end> ...]"
+    );
+    eprintln!(
+        "                 [--html <file> [--here <save|dir>]] [--resume <file>]"
+    );
+    eprintln!("                 <rom|save.json>");
+    process::exit(2);
+}
+
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut depth = DEPTH;
+    let mut max_states = MAX_STATES;
+    let mut max_frontier = MAX_FRONTIER;
+    let mut threads = 0;
+    let mut similar = SIMILAR;
+    let mut volatile = vec![];
+    let mut html = None;
+    let mut here = None;
+    let mut checkpoint_file = None;
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        let mut number = || -> usize {
+            let n = args.next().unwrap_or_else(|| usage());
+            n.parse().unwrap_or_else(|_| usage())
+        };
+        match arg.as_str() {
+            "--depth" => depth = number(),
+            "--max-states" => max_states = number(),
+            "--max-frontier" => max_frontier = number(),
+            "--threads" => threads = number(),
+            // explore at most this many states that print the same thing
+            // when entered, however different their memory is
+            "--similar" => similar = number(),
+            "--volatile" => {
+                // memory that changes without mattering, ie. a move counter
+                let range = args.next().unwrap_or_else(|| usage());
+                volatile.push(parse_volatile(&range).unwrap_or_else(|e| {
+                    eprintln!("--volatile {}: {}", range, e);
+                    process::exit(2);
+                }));
+            }
+            // write the rooms found to a web page, see `roomgraph`
+            "--html" => html = Some(args.next().unwrap_or_else(|| usage())),
+            "--here" => here = Some(args.next().unwrap_or_else(|| usage())),
+            // save progress to a file after each level, and carry on from
+            // it if it's there
+            "--resume" => {
+                checkpoint_file = Some(args.next().unwrap_or_else(|| usage()))
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage());
+
+    // 0 leaves it up to rayon, one thread per cpu
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .unwrap();
+
+    let vm = saves::load(&file).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let mut game = Game::from_vm(vm);
+    let room = parser::parse_room(game.intro()).or_else(|| game.look());
+    let Some(room) = room else {
+        eprintln!("no room to start from");
+        process::exit(1);
+    };
+
+    let visited = Mutex::new(Visited::new(max_states, similar));
+    let hash = game.vm().state_hash_excluding(&volatile);
+    visited.lock().unwrap().insert(hash);
+    let start = game.vm().snapshot();
+
+    let resumed = checkpoint_file.as_deref().and_then(Checkpoint::load);
+    let (mut checkpoint, mut frontier) = match resumed {
+        Some(mut checkpoint) => {
+            let paths = std::mem::take(&mut checkpoint.frontier);
+            eprintln!(
+                "resuming at depth {} with {} states to explore",
+                checkpoint.level,
+                paths.len()
+            );
+            let frontier: Vec<_> = paths
+                .into_par_iter()
+                .filter_map(|path| replay(&start, path))
+                .collect();
+            for node in &frontier {
+                let game = resume(&node.snapshot);
+                let hash = game.vm().state_hash_excluding(&volatile);
+                visited.lock().unwrap().insert(hash);
+            }
+            (checkpoint, frontier)
+        }
+        None => {
+            let mut checkpoint = Checkpoint { states: 1, ..Default::default() };
+            checkpoint
+                .rooms
+                .insert((room.title.clone(), room.description.clone()));
+            println!("{}: (start)", room.title);
+            checkpoint.graph.room(&room);
+            (checkpoint, vec![Node { snapshot: start, path: vec![], room }])
+        }
+    };
+
+    // ctrl-c stops the search and saves the level it was on, so it starts
+    // that level again when resumed
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .unwrap();
+    }
+
+    for level in checkpoint.level + 1..=depth {
+        if frontier.is_empty() {
+            break;
+        }
+
+        // rayon splits the frontier between the threads and lets idle ones
+        // steal work from busy ones
+        let bar = progress_bar(frontier.len(), level);
+        let expanded: Vec<_> = frontier
+            .par_iter()
+            .map(|node| {
+                if interrupted.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let (children, exits) = expand(node, &visited, &volatile);
+                bar.inc(1);
+                Some((node, children, exits))
+            })
+            .collect();
+        bar.finish_and_clear();
+
+        if interrupted.load(Ordering::SeqCst) {
+            match &checkpoint_file {
+                Some(file) => {
+                    if let Err(e) = checkpoint.save(file, &frontier) {
+                        eprintln!("{}", e);
+                        process::exit(1);
+                    }
+                    eprintln!(
+                        "interrupted at depth {}, progress saved to {}",
+                        level, file
+                    );
+                }
+                None => eprintln!(
+                    "interrupted at depth {}, use --resume <file> to keep progress",
+                    level
+                ),
+            }
+            process::exit(130);
+        }
+
+        let graph = &mut checkpoint.graph;
+        let mut next = vec![];
+        for (node, children, exits) in expanded.into_iter().flatten() {
+            let from = graph.room(&node.room);
+            for (exit, room) in exits {
+                let to = graph.room(&room);
+                graph.edge(from, &exit, to);
+            }
+            next.extend(children);
+        }
+
+        checkpoint.states += next.len();
+        for node in &next {
+            let key = (node.room.title.clone(), node.room.description.clone());
+            if checkpoint.rooms.insert(key) {
+                println!("{}: {}", node.room.title, node.path.join(", "));
+            }
+        }
+
+        if next.len() > max_frontier {
+            eprintln!(
+                "depth {}: dropping {} of {} states",
+                level,
+                next.len() - max_frontier,
+                next.len()
+            );
+            next.truncate(max_frontier);
+        }
+        frontier = next;
+        checkpoint.level = level;
+
+        if let Some(file) = &checkpoint_file
+            && let Err(e) = checkpoint.save(file, &frontier)
+        {
+            eprintln!("{}", e);
+        }
+    }
+
+    println!("{} states, {} rooms", checkpoint.states, checkpoint.rooms.len());
+    let pruned = visited.lock().unwrap().pruned;
+    if pruned > 0 {
+        println!("{} states pruned as alike", pruned);
+    }
+
+    let graph = &checkpoint.graph;
+    if let Some(html) = html {
+        if let Err(e) = write_html(graph, &html, here.as_deref()) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        println!("map written to {}", html);
+    }
+}
+
+fn write_html(
+    graph: &Graph,
+    file: &str,
+    here: Option<&str>,
+) -> Result<(), String> {
+    let location = match here {
+        Some(save) => roomgraph::location(save)?,
+        None => None,
+    };
+    if let (Some(save), None) = (here, &location) {
+        println!("{}: not in a room, nothing to highlight", save);
+    }
+    fs::write(file, roomgraph::html(graph, location.as_deref()))
+        .map_err(|e| format!("{}: {}", file, e))
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+use std::fs;
+use std::process;
+
+static TARGET: i64 = 30;
+
+// give up looking for a shortest path after this many moves - the search is
+// exponential in the path length
+static MAX_SEARCH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tile {
+    Op(Op),
+    Num(i64),
+}
+
+impl Op {
+    // the orb's weight after stepping on n, None if it doesn't fit in an i64
+    // (which no path worth walking gets near)
+    fn apply(self, orb: i64, n: i64) -> Option<i64> {
+        match self {
+            Op::Add => orb.checked_add(n),
+            Op::Mul => orb.checked_mul(n),
+            Op::Sub => orb.checked_sub(n),
+        }
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tile::Op(Op::Add) => write!(f, "+"),
+            Tile::Op(Op::Sub) => write!(f, "-"),
+            Tile::Op(Op::Mul) => write!(f, "*"),
+            Tile::Num(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+struct Grid {
+    tiles: Vec<Vec<Tile>>,
+    width: usize,
+    height: usize,
+}
+
+impl Grid {
+    // parse a grid - one row per line, tiles separated by whitespace
+    fn parse(s: &str) -> Result<Self, String> {
+        let mut tiles = vec![];
+
+        for (y, line) in s.lines().filter(|l| !l.trim().is_empty()).enumerate()
+        {
+            let mut row = vec![];
+            for (x, tile) in line.split_whitespace().enumerate() {
+                let tile = match tile {
+                    "+" => Tile::Op(Op::Add),
+                    "-" => Tile::Op(Op::Sub),
+                    "*" => Tile::Op(Op::Mul),
+                    n => match n.parse() {
+                        Ok(n) => Tile::Num(n),
+                        Err(_) => {
+                            return Err(format!(
+                                "row {}, col {}: unexpected tile '{}' \
+                                 (expected +, -, * or a number)",
+                                y + 1,
+                                x + 1,
+                                n
+                            ));
+                        }
+                    },
+                };
+                row.push(tile);
+            }
+            tiles.push(row);
+        }
+
+        let height = tiles.len();
+        if height == 0 {
+            return Err("grid is empty".to_string());
+        }
+
+        let width = tiles[0].len();
+        for (y, row) in tiles.iter().enumerate() {
+            if row.len() != width {
+                return Err(format!(
+                    "row {} has {} tiles, expected {}",
+                    y + 1,
+                    row.len(),
+                    width
+                ));
+            }
+        }
+
+        let grid = Self { tiles, width, height };
+        grid.validate()?;
+
+        Ok(grid)
+    }
+
+    // make sure every walk through the grid alternates between numbers and
+    // operators, otherwise the orb expression can't be evaluated
+    fn validate(&self) -> Result<(), String> {
+        let start = self.start();
+        let end = self.end();
+        if start == end {
+            return Err("grid is too small to have a distinct door".into());
+        }
+
+        for (name, (x, y)) in [("start", start), ("door", end)] {
+            if !matches!(self.get(x, y), Tile::Num(_)) {
+                return Err(format!(
+                    "{} tile ({},{}) must be a number, found '{}'",
+                    name,
+                    x,
+                    y,
+                    self.get(x, y)
+                ));
+            }
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tile = self.get(x, y);
+                for (nx, ny, _) in self.neighbors(x, y) {
+                    let other = self.get(nx, ny);
+                    let same = matches!(
+                        (tile, other),
+                        (Tile::Op(_), Tile::Op(_))
+                            | (Tile::Num(_), Tile::Num(_))
+                    );
+                    if same {
+                        return Err(format!(
+                            "tiles ({},{}) '{}' and ({},{}) '{}' are \
+                             adjacent but must alternate between number and \
+                             operator",
+                            x, y, tile, nx, ny, other
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, x: usize, y: usize) -> Tile {
+        self.tiles[y][x]
+    }
+
+    // the orb starts in the bottom left corner
+    fn start(&self) -> (usize, usize) {
+        (0, self.height - 1)
+    }
+
+    // the vault door is in the top right corner
+    fn end(&self) -> (usize, usize) {
+        (self.width - 1, 0)
+    }
+
+    fn orb(&self) -> i64 {
+        let (x, y) = self.start();
+        match self.get(x, y) {
+            Tile::Num(n) => n,
+            Tile::Op(_) => unreachable!("validated in Grid::parse"),
+        }
+    }
+
+    // all tiles reachable in one move from (x, y)
+    fn neighbors(
+        &self,
+        x: usize,
+        y: usize,
+    ) -> Vec<(usize, usize, &'static str)> {
+        let mut v = vec![];
+        if x + 1 < self.width {
+            v.push((x + 1, y, "east"));
+        }
+        if x > 0 {
+            v.push((x - 1, y, "west"));
+        }
+        if y > 0 {
+            v.push((x, y - 1, "north"));
+        }
+        if y + 1 < self.height {
+            v.push((x, y + 1, "south"));
+        }
+        v
+    }
+}
+
+// count how often the direction changes along a path - fewer turns are easier
+// to walk in game
+fn turns(moves: &[&str]) -> usize {
+    moves.windows(2).filter(|w| w[0] != w[1]).count()
+}
+
+// walk every path of at most limit moves, collecting the ones that reach the
+// door with the orb at the target weight
+fn walk(
+    grid: &Grid,
+    target: i64,
+    limit: usize,
+    state: ((usize, usize), i64, Option<Op>),
+    moves: &mut Vec<&'static str>,
+    solutions: &mut Vec<Vec<&'static str>>,
+) {
+    let ((x, y), orb, op) = state;
+
+    if (x, y) == grid.end() {
+        if orb == target {
+            solutions.push(moves.clone());
+        }
+        return;
+    }
+
+    if moves.len() == limit {
+        return;
+    }
+
+    // try to move in all 4 directions - walking back onto the start tile
+    // resets the orb so it's never part of a solution
+    for (nx, ny, dir) in grid.neighbors(x, y) {
+        if (nx, ny) == grid.start() {
+            continue;
+        }
+
+        let state = match grid.get(nx, ny) {
+            Tile::Op(tile) => {
+                assert!(op.is_none(), "validated in Grid::parse");
+                ((nx, ny), orb, Some(tile))
+            }
+            Tile::Num(n) => {
+                let op = op.expect("validated in Grid::parse");
+                // a weight too big to track is a dead end
+                let Some(orb) = op.apply(orb, n) else {
+                    continue;
+                };
+                ((nx, ny), orb, None)
+            }
+        };
+
+        moves.push(dir);
+        walk(grid, target, limit, state, moves, solutions);
+        moves.pop();
+    }
+}
+
+// find every path to the door that leaves the orb at the target weight.  the
+// search is iteratively deepened so only the shortest solutions are returned,
+// unless max_len asks for longer paths as well
+fn solve(
+    grid: &Grid,
+    target: i64,
+    max_len: Option<usize>,
+) -> Vec<Vec<&'static str>> {
+    let start = (grid.start(), grid.orb(), None);
+    let mut solutions = vec![];
+
+    let cap = max_len.unwrap_or(MAX_SEARCH);
+
+    for limit in 1..=cap {
+        walk(grid, target, limit, start, &mut vec![], &mut solutions);
+        if !solutions.is_empty() {
+            break;
+        }
+    }
+
+    if let Some(max_len) = max_len {
+        solutions.clear();
+        walk(grid, target, max_len, start, &mut vec![], &mut solutions);
+    }
+
+    // shortest first, then the ones with the fewest turns
+    solutions.sort_by_key(|moves| (moves.len(), turns(moves)));
+
+    solutions
+}
+
+// where a path goes and the weight of the orb after each move, starting
+// with the start tile
+fn trace(grid: &Grid, moves: &[&str]) -> Vec<((usize, usize), i64)> {
+    let (mut x, mut y) = grid.start();
+    let mut orb = grid.orb();
+    let mut op = None;
+    let mut steps = vec![((x, y), orb)];
+
+    for dir in moves {
+        let (nx, ny, _) = grid
+            .neighbors(x, y)
+            .into_iter()
+            .find(|(_, _, d)| d == dir)
+            .expect("moves come from Grid::neighbors");
+        match grid.get(nx, ny) {
+            Tile::Op(tile) => op = Some(tile),
+            Tile::Num(n) => {
+                let op = op.take().expect("validated in Grid::parse");
+                orb = op.apply(orb, n).expect("solutions don't overflow");
+            }
+        }
+        (x, y) = (nx, ny);
+        steps.push(((x, y), orb));
+    }
+
+    steps
+}
+
+// draw the grid with the tiles a path walks over in brackets, then each
+// move with the orb weight it should leave, to check against the game
+fn print_route(grid: &Grid, moves: &[&str]) {
+    let steps = trace(grid, moves);
+
+    for y in 0..grid.height {
+        let row: Vec<_> = (0..grid.width)
+            .map(|x| {
+                let tile = grid.get(x, y).to_string();
+                match steps.iter().any(|(pos, _)| *pos == (x, y)) {
+                    true => format!("{:^6}", format!("[{}]", tile)),
+                    false => format!("{:^6}", tile),
+                }
+            })
+            .collect();
+        println!("  {}", row.join("").trim_end());
+    }
+    println!();
+
+    println!("  {:>4}  {:<6} {:>5}  {:>4}  orb", "step", "move", "tile", "x,y");
+    for (i, ((x, y), orb)) in steps.iter().enumerate() {
+        let dir = match i {
+            0 => "start",
+            i => moves[i - 1],
+        };
+        let tile = grid.get(*x, *y);
+        let orb = match tile {
+            Tile::Num(_) => orb.to_string(),
+            Tile::Op(_) => format!("({})", orb),
+        };
+        println!(
+            "  {:>4}  {:<6} {:>5}  {:>4}  {}",
+            i,
+            dir,
+            tile.to_string(),
+            format!("{},{}", x, y),
+            orb
+        );
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: solve orb [--max-len <k>] <grid-file> [target]");
+    process::exit(2);
+}
+
+pub fn main(mut argv: impl Iterator<Item = String>) {
+    let mut max_len = None;
+    let mut args = vec![];
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--max-len" => {
+                let k = argv.next().unwrap_or_else(|| usage());
+                max_len = Some(k.parse().unwrap_or_else(|_| usage()));
+            }
+            "-h" | "--help" => usage(),
+            _ => args.push(arg),
+        }
+    }
+
+    // grid file as arg1 (as written by the VM's /vault command) and an
+    // optional target weight as arg2
+    let Some(file) = args.first() else { usage() };
+    let data = fs::read_to_string(file).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", file, e);
+        process::exit(1);
+    });
+    let target = match args.get(1) {
+        Some(s) => s.parse().unwrap_or_else(|_| {
+            eprintln!("invalid target weight: {}", s);
+            process::exit(1);
+        }),
+        None => TARGET,
+    };
+
+    let grid = Grid::parse(&data).unwrap_or_else(|e| {
+        eprintln!("invalid grid: {}", e);
+        process::exit(1);
+    });
+
+    let solutions = solve(&grid, target, max_len);
+    if solutions.is_empty() {
+        eprintln!(
+            "no path of at most {} moves reaches the door with weight {}",
+            max_len.unwrap_or(MAX_SEARCH),
+            target
+        );
+        process::exit(1);
+    }
+
+    println!("found {} solution(s)", solutions.len());
+    for (i, moves) in solutions.iter().enumerate() {
+        println!(
+            "#{} ({} moves, {} turns): {}",
+            i + 1,
+            moves.len(),
+            turns(moves),
+            moves.join(" ")
+        );
+    }
+
+    println!();
+    println!("route #1:");
+    print_route(&grid, &solutions[0]);
+}
@@ -0,0 +1,475 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::fs;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use synacor_challenge::offsets;
+use synacor_challenge::ram::Ram;
+use synacor_challenge::rominfo;
+use synacor_challenge::saves;
+use synacor_challenge::vm::{VM, VmError};
+
+// how many candidates to test between checkpoint writes
+static CHECKPOINT_EVERY: u16 = 256;
+
+// the confirmation routine in the challenge ROM, when it can't be found
+static ROUTINE: u16 = 6049;
+
+// steps the interpreter takes between checking the clock
+static CLOCK_EVERY: u64 = 1 << 20;
+
+// the memo solver recurses deeply, give the bench threads room for it
+static BENCH_STACK: usize = 512 * 1024 * 1024;
+
+type Cache = HashMap<(u16, u16), u16>;
+
+fn fn6049(mut r0: u16, mut r1: u16, r7: u16, cache: &mut Cache) -> u16 {
+    if r0 == 0 {
+        r0 = (r1 + 1) % 32768;
+        return r0;
+    }
+
+    if r1 == 0 {
+        r0 = (r0 + 32767) % 32768; // dec by 1
+        r1 = r7;
+        return if let Some(v) = cache.get(&(r0, r1)) {
+            *v
+        } else {
+            let v = fn6049(r0, r1, r7, cache);
+            cache.insert((r0, r1), v);
+            v
+        };
+    }
+
+    let tmp = r0;
+    r1 = (r1 + 32767) % 32768; // decrement by 1
+    r0 = if let Some(v) = cache.get(&(r0, r1)) {
+        *v
+    } else {
+        let v = fn6049(r0, r1, r7, cache);
+        cache.insert((r0, r1), v);
+        v
+    };
+
+    r1 = r0;
+    r0 = tmp;
+
+    r0 = (r0 + 32767) % 32768; // decrement by 1
+
+    if let Some(v) = cache.get(&(r0, r1)) {
+        *v
+    } else {
+        let v = fn6049(r0, r1, r7, cache);
+        cache.insert((r0, r1), v);
+        v
+    }
+}
+
+// the routine at 6049 is an ackermann function modulo 32768 where r7 takes
+// the place of the usual 1 in A(m, 0) = A(m - 1, r7).  the low levels reduce
+// to plain arithmetic:
+//
+//   A(0, n) = n + 1
+//   A(1, n) = n + r7 + 1
+//   A(2, n) = (n + 2) * (r7 + 1) - 1
+//
+// and every level above that is n + 1 applications of the level below it,
+// so A(4, 1) takes two passes over A(3, n) instead of millions of calls
+fn closed_form(r0: u16, r1: u16, r7: u16) -> u16 {
+    let n = r1 as u32;
+    let k = r7 as u32;
+
+    match r0 {
+        0 => ((n + 1) % 32768) as u16,
+        1 => ((n + k + 1) % 32768) as u16,
+        2 => (((n + 2) * (k + 1) + 32767) % 32768) as u16,
+        m => {
+            let mut v = closed_form(m - 1, r7, r7);
+            for _ in 0..n {
+                v = closed_form(m - 1, v, r7);
+            }
+            v
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Method {
+    Memo,
+    ClosedForm,
+}
+
+impl Method {
+    fn eval(self, r7: u16) -> u16 {
+        match self {
+            Method::Memo => fn6049(4, 1, r7, &mut Cache::new()),
+            Method::ClosedForm => closed_form(4, 1, r7),
+        }
+    }
+}
+
+// progress of a search, saved so it can be picked up again with --resume.
+// the file has a `next <n>` line for the first untested candidate and a
+// `found <n>` line for every candidate that worked
+struct Checkpoint {
+    next: u16,
+    found: Vec<u16>,
+}
+
+// r7 = 0 skips the check altogether, so the search starts at 1
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Self { next: 1, found: vec![] }
+    }
+}
+
+impl Checkpoint {
+    fn load(file: &str) -> Self {
+        let Ok(data) = fs::read_to_string(file) else {
+            return Self::default();
+        };
+
+        let mut checkpoint = Self::default();
+        for line in data.lines() {
+            let parsed = match line.split_once(' ') {
+                Some(("next", n)) => n.parse().map(|n| checkpoint.next = n),
+                Some(("found", n)) => {
+                    n.parse().map(|n| checkpoint.found.push(n))
+                }
+                _ => Ok(()),
+            };
+            if parsed.is_err() {
+                eprintln!("invalid checkpoint line in {}: {}", file, line);
+                process::exit(1);
+            }
+        }
+        checkpoint
+    }
+
+    fn save(&self, file: &str) -> Result<(), String> {
+        let mut data = format!("next {}\n", self.next);
+        for n in &self.found {
+            data += &format!("found {}\n", n);
+        }
+        saves::write(file, data.as_bytes(), true)
+    }
+}
+
+// a way of running the confirmation check for --bench
+#[derive(Clone, Copy)]
+enum Engine {
+    // step the routine in the ROM instruction by instruction
+    Interpreter,
+    // the same, with hot code run as superblocks
+    Superblock,
+    Native(Method),
+}
+
+impl Engine {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "interpreter" => Some(Engine::Interpreter),
+            "superblock" => Some(Engine::Superblock),
+            "memo" => Some(Engine::Native(Method::Memo)),
+            "closed-form" => Some(Engine::Native(Method::ClosedForm)),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Engine::Interpreter => "interpreter",
+            Engine::Superblock => "superblock",
+            Engine::Native(Method::Memo) => "memo",
+            Engine::Native(Method::ClosedForm) => "closed-form",
+        }
+    }
+}
+
+// how a run of the confirmation check ended: with r0, or why not
+enum Outcome {
+    Value(u16),
+    TimedOut,
+    Fault(VmError),
+    Panicked,
+}
+
+// run the routine in the ROM with r0=4 r1=1 and the given r7, giving up
+// after the timeout.  returns how it ended and the number of instructions
+// executed
+fn interpret(
+    rom: &[u8],
+    routine: u16,
+    r7: u16,
+    timeout: Duration,
+    fast: bool,
+) -> (Outcome, u64) {
+    let start = Instant::now();
+
+    let mut vm = VM::new(rom.to_vec());
+    vm.set_fast(fast);
+    vm.set_register(0, 4);
+    vm.set_register(1, 1);
+    vm.set_register(7, r7);
+    vm.call(routine);
+
+    // a superblock runs many instructions in one step, so count steps here
+    // rather than going by vm.steps()
+    let mut n: u64 = 0;
+    while vm.depth() > 0 {
+        // ie. --routine isn't the start of a routine
+        if let Err(e) = vm.step() {
+            return (Outcome::Fault(e), vm.steps());
+        }
+        n += 1;
+        if n.is_multiple_of(CLOCK_EVERY) && start.elapsed() > timeout {
+            return (Outcome::TimedOut, vm.steps());
+        }
+    }
+
+    (Outcome::Value(vm.register(0)), vm.steps())
+}
+
+// the confirmation routine: from the offsets database if the ROM is in it,
+// otherwise found by its code
+fn find_routine(rom: &[u8]) -> u16 {
+    let info = rominfo::identify(&Ram::new(rom));
+    let db = offsets::Database::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        offsets::Database::default()
+    });
+    db.find(&info)
+        .and_then(|known| known.symbols.get("teleporter_check"))
+        .or(info.teleporter)
+        .unwrap_or(ROUTINE)
+}
+
+// run every engine at once on its own thread and report how long each took
+fn bench(
+    rom_file: &str,
+    engines: &[Engine],
+    routine: Option<u16>,
+    r7: u16,
+    timeout: Duration,
+) {
+    let rom = fs::read(rom_file).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", rom_file, e);
+        process::exit(1);
+    });
+    let routine = routine.unwrap_or_else(|| find_routine(&rom));
+
+    println!(
+        "confirming r7={} with routine {} (timeout {}s)",
+        r7,
+        routine,
+        timeout.as_secs_f64()
+    );
+
+    thread::scope(|s| {
+        let handles: Vec<_> = engines
+            .iter()
+            .map(|engine| {
+                let rom = &rom;
+                let handle = thread::Builder::new()
+                    .stack_size(BENCH_STACK)
+                    .spawn_scoped(s, move || {
+                        let start = Instant::now();
+                        let (outcome, steps) = match engine {
+                            Engine::Interpreter => {
+                                interpret(rom, routine, r7, timeout, false)
+                            }
+                            Engine::Superblock => {
+                                interpret(rom, routine, r7, timeout, true)
+                            }
+                            Engine::Native(method) => {
+                                (Outcome::Value(method.eval(r7)), 0)
+                            }
+                        };
+                        (outcome, steps, start.elapsed())
+                    })
+                    .unwrap();
+                (engine, handle)
+            })
+            .collect();
+
+        for (engine, handle) in handles {
+            let (outcome, steps, elapsed) =
+                handle.join().unwrap_or((Outcome::Panicked, 0, Duration::ZERO));
+            let result = match outcome {
+                Outcome::Value(6) => "6 (confirmed)".to_string(),
+                Outcome::Value(v) => format!("{} (rejected)", v),
+                Outcome::TimedOut => "timed out".to_string(),
+                Outcome::Fault(e) => match e.pc() {
+                    Some(pc) => format!("fault {} at {}", e.code(), pc),
+                    None => format!("fault {}", e.code()),
+                },
+                Outcome::Panicked => "panicked".to_string(),
+            };
+            let steps = if steps > 0 {
+                format!("  {} instructions", steps)
+            } else {
+                String::new()
+            };
+            println!(
+                "  {:<12} {:>10.3}s  {}{}",
+                engine.name(),
+                elapsed.as_secs_f64(),
+                result,
+                steps
+            );
+        }
+    });
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: solve teleporter [--method memo|closed-form] [--r7 <n>] [--resume <file>]"
+    );
+    eprintln!(
+        "       solve teleporter --bench <rom> [--engines <a,b,..>] [--r7 <n>] [--timeout <secs>] [--routine <addr>]"
+    );
+    eprintln!("engines: interpreter, superblock, memo, closed-form");
+    process::exit(2);
+}
+
+pub fn main(mut args: impl Iterator<Item = String>) {
+    let mut method = Method::Memo;
+    let mut only = None;
+    let mut resume = None;
+    let mut bench_rom = None;
+    let mut engines = vec![
+        Engine::Interpreter,
+        Engine::Superblock,
+        Engine::Native(Method::Memo),
+        Engine::Native(Method::ClosedForm),
+    ];
+    let mut timeout = Duration::from_secs(10);
+    let mut routine = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--method" => {
+                method = match args.next().as_deref() {
+                    Some("memo") => Method::Memo,
+                    Some("closed-form") => Method::ClosedForm,
+                    _ => usage(),
+                }
+            }
+            "--r7" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                only = Some(n.parse().unwrap_or_else(|_| usage()));
+            }
+            "--resume" => {
+                resume = Some(args.next().unwrap_or_else(|| usage()));
+            }
+            "--bench" => {
+                bench_rom = Some(args.next().unwrap_or_else(|| usage()));
+            }
+            "--engines" => {
+                let list = args.next().unwrap_or_else(|| usage());
+                engines = list
+                    .split(',')
+                    .map(|e| Engine::parse(e).unwrap_or_else(|| usage()))
+                    .collect();
+            }
+            "--timeout" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                let secs: f64 = n.parse().unwrap_or_else(|_| usage());
+                timeout = Duration::from_secs_f64(secs);
+            }
+            "--routine" => {
+                let n = args.next().unwrap_or_else(|| usage());
+                routine = Some(n.parse().unwrap_or_else(|_| usage()));
+            }
+            _ => usage(),
+        }
+    }
+
+    if let Some(rom) = bench_rom {
+        bench(&rom, &engines, routine, only.unwrap_or(1), timeout);
+        return;
+    }
+
+    // evaluate a single candidate, useful to cross-check the methods
+    if let Some(r7) = only {
+        println!("r7={} -> {}", r7, method.eval(r7));
+        return;
+    }
+
+    let mut checkpoint = match &resume {
+        Some(file) => Checkpoint::load(file),
+        None => Checkpoint::default(),
+    };
+
+    // ctrl-c stops the search after the current candidate so progress can be
+    // saved
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .unwrap();
+    }
+
+    let bar = ProgressBar::new(32767);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{elapsed_precise} [{bar:40}] {pos}/{len} r7 candidates ({eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+    bar.set_position(checkpoint.next.saturating_sub(1) as u64);
+    for i in &checkpoint.found {
+        bar.suspend(|| eprintln!("{} worked! (from checkpoint)", i));
+    }
+
+    while checkpoint.next < 32768 {
+        if interrupted.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let i = checkpoint.next;
+        let value = method.eval(i);
+        if value == 6 {
+            bar.suspend(|| eprintln!("{} worked!", i));
+            checkpoint.found.push(i);
+        }
+
+        checkpoint.next += 1;
+        bar.inc(1);
+
+        if let Some(file) = &resume
+            && checkpoint.next % CHECKPOINT_EVERY == 0
+            && let Err(e) = checkpoint.save(file)
+        {
+            bar.suspend(|| eprintln!("{}", e));
+        }
+    }
+
+    if let Some(file) = &resume
+        && let Err(e) = checkpoint.save(file)
+    {
+        bar.suspend(|| eprintln!("{}", e));
+    }
+
+    if interrupted.load(Ordering::SeqCst) {
+        bar.abandon();
+        match &resume {
+            Some(file) => eprintln!(
+                "interrupted at r7={}, progress saved to {}",
+                checkpoint.next, file
+            ),
+            None => eprintln!(
+                "interrupted at r7={}, use --resume <file> to keep progress",
+                checkpoint.next
+            ),
+        }
+        process::exit(130);
+    }
+
+    bar.finish();
+}
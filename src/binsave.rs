@@ -0,0 +1,191 @@
+/*!
+ * Saves in a compact binary format.
+ *
+ * A JSON save writes memory as a list of numbers, several hundred KB that
+ * take a while to write and read back.  `/export game.snap` (any file ending
+ * in .snap) writes the same state as bytes instead, little-endian
+ * throughout:
+ *
+ * ```text
+ * magic      8 bytes, "SYNSAVE\0"
+ * version    u16, VERSION
+ * steps      u64
+ * pc         u16
+ * running    u8, 0 or 1
+ * registers  8 x u16
+ * stack      u32 count, then that many u16, bottom first
+ * input      u32 count, then the bytes of input not yet read
+ * location   u32 length, then UTF-8 (empty for none)
+ * excerpt    u32 length, then UTF-8
+ * memory     u32 length, then the bytes of memory
+ * ```
+ *
+ * The location and excerpt are the save's thumbnail, see `saves`.  Loading
+ * any file starting with the magic number reads it, whatever it's called,
+ * and a version newer than this one is refused rather than misread.
+ */
+
+use crate::saves::Thumbnail;
+use crate::vm::VM;
+
+pub const EXTENSION: &str = ".snap";
+
+pub const MAGIC: &[u8; 8] = b"SYNSAVE\0";
+
+// bumped whenever the layout changes
+pub const VERSION: u16 = 1;
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    out.extend((len as u32).to_le_bytes());
+}
+
+fn put_words(out: &mut Vec<u8>, words: &[u16]) {
+    for w in words {
+        out.extend(w.to_le_bytes());
+    }
+}
+
+pub fn export(vm: &VM) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_le_bytes());
+    out.extend(vm.steps.to_le_bytes());
+    out.extend(vm.pc().to_le_bytes());
+    out.push(vm.running as u8);
+    put_words(&mut out, &vm.registers);
+
+    put_len(&mut out, vm.stack.len());
+    put_words(&mut out, &vm.stack);
+    put_len(&mut out, vm.input_buffer.len());
+    out.extend(&vm.input_buffer);
+
+    let thumbnail = vm.thumbnail();
+    let location = thumbnail.location.as_deref().unwrap_or("");
+    put_len(&mut out, location.len());
+    out.extend(location.as_bytes());
+    put_len(&mut out, thumbnail.excerpt.len());
+    out.extend(thumbnail.excerpt.as_bytes());
+
+    put_len(&mut out, vm.ram.len());
+    out.extend(vm.ram.bytes());
+    out
+}
+
+// reads a save from the front, running out is an error
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.data.len() {
+            return Err("cut short".to_string());
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap());
+        Ok(len as usize)
+    }
+
+    fn words(&mut self, n: usize) -> Result<Vec<u16>, String> {
+        (0..n).map(|_| self.u16()).collect()
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|_| "bad text in the thumbnail".to_string())
+    }
+}
+
+// true if data is a binary save, of any version
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// check the magic number and version, returning the instruction count
+fn header(r: &mut Reader) -> Result<u64, String> {
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err("not a binary save".to_string());
+    }
+    let version = r.u16()?;
+    if version > VERSION {
+        return Err(format!(
+            "save format version {} is newer than this VM's ({})",
+            version, VERSION
+        ));
+    }
+    r.u64()
+}
+
+// the instruction count and thumbnail, for listing saves without loading
+// them
+pub fn info(data: &[u8]) -> Option<(u64, Thumbnail)> {
+    let mut r = Reader { data };
+    let steps = header(&mut r).ok()?;
+    r.take(2 + 1 + 16).ok()?;
+    let stack = r.len().ok()?;
+    r.take(stack * 2).ok()?;
+    r.bytes().ok()?;
+
+    let mut thumbnail = Thumbnail::default();
+    let location = r.string().ok()?;
+    thumbnail.location = (!location.is_empty()).then_some(location);
+    thumbnail.excerpt = r.string().ok()?;
+    Some((steps, thumbnail))
+}
+
+// a VM from a binary save, named file in errors
+pub fn import(file: &str, data: &[u8]) -> Result<VM, String> {
+    let err = |e: String| format!("{}: {}", file, e);
+    let mut r = Reader { data };
+
+    let steps = header(&mut r).map_err(err)?;
+    let pc = r.u16().map_err(err)?;
+    let running = r.u8().map_err(err)? != 0;
+    let registers: [u16; 8] = r.words(8).map_err(err)?.try_into().unwrap();
+    let stack = r.len().and_then(|n| r.words(n)).map_err(err)?;
+    let input = r.bytes().map_err(err)?.to_vec();
+
+    let mut thumbnail = Thumbnail::default();
+    let location = r.string().map_err(err)?;
+    thumbnail.location = (!location.is_empty()).then_some(location);
+    thumbnail.excerpt = r.string().map_err(err)?;
+
+    let ram = r.bytes().map_err(err)?;
+    if ram.len() > 65536 {
+        return Err(err(format!(
+            "{} bytes of memory, more than the 65536 there are",
+            ram.len()
+        )));
+    }
+
+    let mut vm = VM::new(ram.to_vec());
+    vm.registers = registers;
+    vm.stack = stack;
+    vm.set_pc(pc);
+    vm.running = running;
+    vm.input_buffer = input;
+    vm.steps = steps;
+    vm.thumbnail = thumbnail;
+    Ok(vm)
+}
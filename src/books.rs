@@ -0,0 +1,180 @@
+/*!
+ * Pull the long texts - the strange book, the journal - out of memory.
+ *
+ * `synacor-challenge books challenge.bin` writes each one to a Markdown
+ * file, so the teleporter instructions can be read somewhere other than the
+ * game terminal:
+ *
+ * ```text
+ * wrote books/strange-book.md, 1433 characters from 25974
+ * wrote books/the-cover-of-this-journal-is.md, 688 characters from 26917
+ * ```
+ *
+ * A book is any length-prefixed string of at least MIN_LEN characters, plus
+ * the strings at symbols named `book_<name>` (from a symbols file or the
+ * offsets database) however short.  The challenge keeps some of its text
+ * xor'd with a key until it's printed, so strings that aren't text as they
+ * are get tried with every key that makes them printable, keeping the one
+ * that reads most like English.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use crate::strings::MAX_LEN;
+use crate::symbols::Symbols;
+
+// shorter strings are room descriptions and the like, not books
+pub const MIN_LEN: usize = 300;
+
+// words of a name made from the start of the text
+const NAME_WORDS: usize = 6;
+
+pub struct Book {
+    pub addr: u16,
+    // 0 if the text isn't xor'd
+    pub key: u16,
+    pub name: String,
+    pub text: String,
+}
+
+fn printable(c: u16) -> bool {
+    (32..127).contains(&c) || c == 10
+}
+
+// how much like English text is.  letters and spaces count, anything else
+// counts against it
+fn score(text: &[u16]) -> i64 {
+    text.iter()
+        .map(|c| match *c as u8 {
+            b'a'..=b'z' | b' ' => 2,
+            b'A'..=b'Z' | b'\n' | b'.' | b',' | b'\'' => 1,
+            _ => -1,
+        })
+        .sum()
+}
+
+// the string at addr and the key it was xor'd with, if it is (or can be
+// made) text
+pub fn decode(words: &[u16], addr: u16) -> Option<(String, u16)> {
+    let addr = addr as usize;
+    let len = *words.get(addr)?;
+    if len == 0 || len > MAX_LEN {
+        return None;
+    }
+    let chars = words.get(addr + 1..addr + 1 + len as usize)?;
+
+    // a key has to make the first character printable, which leaves only
+    // a few to try
+    let keys = (10..11).chain(32..127).map(|c| chars[0] ^ c);
+    let (key, _) = keys
+        .filter(|key| chars.iter().all(|c| printable(c ^ key)))
+        .map(|key| {
+            let text: Vec<u16> = chars.iter().map(|c| c ^ key).collect();
+            (key, score(&text))
+        })
+        // plain text wins a tie
+        .max_by_key(|(key, score)| (*score, *key == 0))?;
+
+    let text = chars.iter().map(|c| ((c ^ key) as u8) as char).collect();
+    Some((text, key))
+}
+
+// "strange-book" from the first few words of text
+fn name_for(text: &str) -> String {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|w| !w.is_empty())
+        .take(NAME_WORDS)
+        .collect();
+    words.join("-")
+}
+
+// every book in memory, in address order
+pub fn find(words: &[u16], symbols: &Symbols) -> Vec<Book> {
+    let mut books = vec![];
+
+    for (name, addr) in symbols.iter() {
+        let Some(name) = name.strip_prefix("book_") else { continue };
+        if let Some((text, key)) = decode(words, addr) {
+            let name = name.replace('_', "-");
+            books.push(Book { addr, key, name, text });
+        }
+    }
+
+    let mut addr = 0;
+    while addr < words.len() {
+        let len = words[addr] as usize;
+        if books.iter().any(|b| b.addr as usize == addr) {
+            addr += len + 1;
+        } else if len >= MIN_LEN
+            && let Some((text, key)) = decode(words, addr as u16)
+        {
+            let name = match name_for(&text) {
+                name if name.is_empty() => format!("book-{}", addr),
+                name => name,
+            };
+            books.push(Book { addr: addr as u16, key, name, text });
+            addr += len + 1;
+        } else {
+            addr += 1;
+        }
+    }
+
+    books.sort_by_key(|b| b.addr);
+    books
+}
+
+// the book as a Markdown document, titled with its name
+pub fn markdown(book: &Book, source: &str) -> String {
+    let mut title = book.name.replace('-', " ");
+    if let Some(first) = title.get(..1) {
+        title = first.to_uppercase() + &title[1..];
+    }
+    let key = match book.key {
+        0 => String::new(),
+        key => format!(", xor'd with {}", key),
+    };
+    format!(
+        "# {}\n\n<!-- {} at {}{} -->\n\n{}\n",
+        title,
+        source,
+        book.addr,
+        key,
+        book.text.trim_end()
+    )
+}
+
+// write each book to <dir>/<name>.md
+pub fn extract(
+    words: &[u16],
+    symbols: &Symbols,
+    source: &str,
+    dir: &str,
+) -> Result<(), String> {
+    let books = find(words, symbols);
+    if books.is_empty() {
+        println!("no strings of {} characters or more", MIN_LEN);
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    for book in &books {
+        let file = Path::new(dir).join(format!("{}.md", book.name));
+        fs::write(&file, markdown(book, source))
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+        println!(
+            "wrote {}, {} characters from {}",
+            file.display(),
+            book.text.len(),
+            book.addr
+        );
+    }
+    Ok(())
+}
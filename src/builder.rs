@@ -0,0 +1,425 @@
+/*!
+ * Configure a VM in one go.
+ *
+ * ```no_run
+ * use synacor_challenge::builder::VmBuilder;
+ *
+ * let vm = VmBuilder::new()
+ *     .rom(std::fs::read("challenge.bin").unwrap())
+ *     .script(b"take tablet\nuse tablet\n".to_vec())
+ *     .breakpoints([6049])
+ *     .strict(true)
+ *     .build()
+ *     .unwrap();
+ * ```
+ *
+ * Input, output, output filters, progress tracking, command history, key
+ * bindings, plugins, shutdown handling, autosaves, rewinding, rate limits,
+ * read-only mode, recording and status reporting belong to the shell rather than the VM, so
+ * a builder using them has to finish with `shell()`.
+ */
+
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use crate::codes::Progress;
+use crate::history::History;
+use crate::hooks::Hook;
+use crate::keys::Keys;
+use crate::plugin::Plugin;
+use crate::sampler::{Every, Sampler};
+use crate::script::Script;
+use crate::shell::Shell;
+use crate::shutdown::Shutdown;
+use crate::symbols::Symbols;
+use crate::trace::ChromeTrace;
+use crate::tracefilter::TraceFilter;
+use crate::tracesink::TraceSink;
+use crate::vm::VM;
+
+#[derive(Default)]
+pub struct VmBuilder {
+    vm: Option<VM>,
+    reader: Option<Box<dyn BufRead + Send>>,
+    writer: Option<Box<dyn Write + Send>>,
+    script: Vec<u8>,
+    trace: Option<String>,
+    trace_sinks: Vec<Box<dyn TraceSink>>,
+    sample: Option<(String, Every)>,
+    trace_filter: Option<TraceFilter>,
+    breakpoints: Vec<u16>,
+    hooks: Vec<Hook>,
+    filters: Vec<String>,
+    symbols: Option<Symbols>,
+    progress: Option<Progress>,
+    history: Option<History>,
+    keys: Option<Keys>,
+    strict: bool,
+    audit: bool,
+    debug_port: Option<u16>,
+    stack_limit: Option<usize>,
+    stack_flag: Option<u16>,
+    stack_check: bool,
+    fast: bool,
+    show_steps: bool,
+    plugins: Vec<Box<dyn Plugin>>,
+    shutdown: Option<Arc<Shutdown>>,
+    autosave: Option<(String, u64)>,
+    rewind: Option<usize>,
+    max_ips: Option<u64>,
+    max_inputs: Option<u64>,
+    read_only: Option<String>,
+    explain: bool,
+    record: Option<(String, bool)>,
+    status_file: Option<String>,
+    notify: Option<String>,
+}
+
+impl VmBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // start from a fresh ROM image
+    pub fn rom(mut self, rom: Vec<u8>) -> Self {
+        self.vm = Some(VM::new(rom));
+        self
+    }
+
+    // start from an existing VM, ie. a restored save
+    pub fn state(mut self, vm: VM) -> Self {
+        self.vm = Some(vm);
+        self
+    }
+
+    // where to read game input and commands from instead of stdin
+    pub fn input(mut self, reader: impl BufRead + Send + 'static) -> Self {
+        self.reader = Some(Box::new(reader));
+        self
+    }
+
+    // where to write game output instead of stderr
+    pub fn output(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.writer = Some(Box::new(writer));
+        self
+    }
+
+    // input to play before reading any, see `script` for the directives a
+    // shell will check along the way
+    pub fn script(mut self, input: Vec<u8>) -> Self {
+        self.script.extend(input);
+        self
+    }
+
+    // write call/ret events to this file in the Chrome trace format
+    pub fn trace(mut self, file: &str) -> Self {
+        self.trace = Some(file.to_string());
+        self
+    }
+
+    // see `tracesink`
+    pub fn trace_sink(mut self, sink: impl TraceSink + 'static) -> Self {
+        self.trace_sinks.push(Box::new(sink));
+        self
+    }
+
+    // count call stacks, see `sampler`
+    pub fn sample(mut self, file: &str, every: Every) -> Self {
+        self.sample = Some((file.to_string(), every));
+        self
+    }
+
+    pub fn trace_filter(mut self, filter: TraceFilter) -> Self {
+        self.trace_filter = Some(filter);
+        self
+    }
+
+    pub fn breakpoints(mut self, addrs: impl IntoIterator<Item = u16>) -> Self {
+        self.breakpoints.extend(addrs);
+        self
+    }
+
+    pub fn hooks(mut self, hooks: Vec<Hook>) -> Self {
+        self.hooks.extend(hooks);
+        self
+    }
+
+    // add an output filter spec, see `filters`
+    pub fn filter(mut self, spec: &str) -> Self {
+        self.filters.push(spec.to_string());
+        self
+    }
+
+    pub fn symbols(mut self, symbols: Symbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    // the line editor's key bindings, see `keys`
+    pub fn keys(mut self, keys: Keys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    // panic on memory accesses outside the loaded image
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    // check arithmetic against the spec as it runs, see `audit`
+    pub fn audit(mut self, on: bool) -> Self {
+        self.audit = on;
+        self
+    }
+
+    // lines the program writes to addr come out as debug events, see
+    // `VM::set_debug_port`
+    pub fn debug_port(mut self, addr: u16) -> Self {
+        self.debug_port = Some(addr);
+        self
+    }
+
+    // `VM::set_stack_limit`
+    pub fn stack_limit(mut self, limit: usize, flag: Option<u16>) -> Self {
+        self.stack_limit = Some(limit);
+        self.stack_flag = flag;
+        self
+    }
+
+    // report calls that return with the stack unbalanced
+    pub fn stack_check(mut self, on: bool) -> Self {
+        self.stack_check = on;
+        self
+    }
+
+    // run hot straight-line code as superblocks
+    pub fn fast(mut self, fast: bool) -> Self {
+        self.fast = fast;
+        self
+    }
+
+    // show the instruction count in the shell's prompt
+    pub fn show_steps(mut self, on: bool) -> Self {
+        self.show_steps = on;
+        self
+    }
+
+    // load a plugin into the shell, see `plugin`
+    pub fn plugin(mut self, plugin: Box<dyn Plugin>) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    // save the game when the process is told to stop, see `shutdown`
+    pub fn shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    // save the game to dir every so many instructions, see `autosave`
+    pub fn autosave(mut self, dir: &str, every: u64) -> Self {
+        self.autosave = Some((dir.to_string(), every));
+        self
+    }
+
+    // keep the last max instructions run for /rewind, see `journal`
+    pub fn rewind(mut self, max: usize) -> Self {
+        self.rewind = Some(max);
+        self
+    }
+
+    // run at most this many instructions a second, see `throttle`
+    pub fn max_ips(mut self, ips: u64) -> Self {
+        self.max_ips = Some(ips);
+        self
+    }
+
+    // take at most this many lines of input a minute
+    pub fn max_inputs(mut self, n: u64) -> Self {
+        self.max_inputs = Some(n);
+        self
+    }
+
+    // turn off the shell commands that change the VM, and never write over
+    // file (the one being run)
+    pub fn read_only(mut self, file: &str) -> Self {
+        self.read_only = Some(file.to_string());
+        self
+    }
+
+    // explain each instruction in the shell as it runs, see `explain`
+    pub fn explain(mut self, on: bool) -> Self {
+        self.explain = on;
+        self
+    }
+
+    // record the session to file as a script, see `script`.  an existing
+    // file is only replaced with force
+    pub fn record(mut self, file: &str, force: bool) -> Self {
+        self.record = Some((file.to_string(), force));
+        self
+    }
+
+    // keep file up to date with whether the game is thinking, waiting for
+    // input or halted, see `watchdog`
+    pub fn status_file(mut self, file: &str) -> Self {
+        self.status_file = Some(file.to_string());
+        self
+    }
+
+    // run cmd with `sh -c` when the game stops thinking after a long time
+    pub fn notify(mut self, cmd: &str) -> Self {
+        self.notify = Some(cmd.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<VM, String> {
+        if self.reader.is_some()
+            || self.writer.is_some()
+            || !self.filters.is_empty()
+            || self.progress.is_some()
+            || self.history.is_some()
+            || self.keys.is_some()
+            || !self.plugins.is_empty()
+            || self.shutdown.is_some()
+            || self.autosave.is_some()
+            || self.rewind.is_some()
+            || self.max_ips.is_some()
+            || self.max_inputs.is_some()
+            || self.read_only.is_some()
+            || self.record.is_some()
+            || self.status_file.is_some()
+            || self.notify.is_some()
+        {
+            return Err("input, output, filters, progress, history, keys, \
+                        plugins, shutdown, autosaves, rewinding, limits, \
+                        read-only, recording and status are handled by the shell, use shell() instead \
+                        of build()"
+                .to_string());
+        }
+        self.build_vm()
+    }
+
+    // build the VM wrapped in an interactive shell
+    pub fn shell(mut self) -> Result<Shell, String> {
+        let reader = self.reader.take();
+        let writer = self.writer.take();
+        let filters = std::mem::take(&mut self.filters);
+        let progress = self.progress.take();
+        let history = self.history.take();
+        let keys = self.keys.take();
+        let show_steps = self.show_steps;
+        let plugins = std::mem::take(&mut self.plugins);
+        let shutdown = self.shutdown.take();
+        let autosave = self.autosave.take();
+        let rewind = self.rewind;
+        let (max_ips, max_inputs) = (self.max_ips, self.max_inputs);
+        let read_only = self.read_only.take();
+        let explain = self.explain;
+        let record = self.record.take();
+        let status_file = self.status_file.take();
+        let notify = self.notify.take();
+        let script = Script::parse(&std::mem::take(&mut self.script))?;
+
+        let mut shell = Shell::new(self.build_vm()?);
+        shell.set_script(script);
+        if let Some(reader) = reader {
+            shell.set_reader(reader);
+        }
+        if let Some(writer) = writer {
+            shell.set_writer(writer);
+        }
+        for spec in &filters {
+            shell.add_filter(spec)?;
+        }
+        if let Some(progress) = progress {
+            shell.set_progress(progress);
+        }
+        if let Some(history) = history {
+            shell.set_history(history);
+        }
+        if let Some(keys) = keys {
+            shell.set_keys(keys);
+        }
+        shell.set_show_steps(show_steps);
+        for plugin in plugins {
+            shell.add_plugin(plugin)?;
+        }
+        if let Some(shutdown) = shutdown {
+            shell.set_shutdown(shutdown);
+        }
+        if let Some((dir, every)) = autosave {
+            shell.set_autosave(&dir, every)?;
+        }
+        if let Some(max) = rewind {
+            shell.set_rewind(max);
+        }
+        if let Some(ips) = max_ips {
+            shell.set_step_limit(ips);
+        }
+        if let Some(n) = max_inputs {
+            shell.set_input_limit(n);
+        }
+        if let Some(file) = read_only {
+            shell.set_read_only(&file)?;
+        }
+        shell.set_explain(explain);
+        if let Some((file, force)) = record {
+            shell.record(&file, force)?;
+        }
+        if let Some(file) = status_file {
+            shell.set_status_file(&file);
+        }
+        if let Some(cmd) = notify {
+            shell.set_notify(&cmd);
+        }
+
+        Ok(shell)
+    }
+
+    fn build_vm(self) -> Result<VM, String> {
+        let mut vm = self.vm.ok_or("no ROM or state given")?;
+
+        vm.queue_input(&Script::parse(&self.script)?.input());
+        if let Some(file) = self.trace {
+            let trace = ChromeTrace::create(&file)
+                .map_err(|e| format!("{}: {}", file, e))?;
+            vm.set_chrome_trace(trace);
+        }
+        for sink in self.trace_sinks {
+            vm.add_trace_sink(sink);
+        }
+        if let Some((file, every)) = self.sample {
+            vm.set_sampler(Sampler::new(&file, every, vm.steps())?);
+        }
+        if self.trace_filter.is_some() {
+            vm.set_trace_filter(self.trace_filter);
+        }
+        for addr in self.breakpoints {
+            vm.add_breakpoint(addr);
+        }
+        vm.add_hooks(self.hooks);
+        if let Some(symbols) = self.symbols {
+            vm.set_symbols(symbols);
+        }
+        vm.set_strict(self.strict);
+        vm.set_audit(self.audit);
+        vm.set_debug_port(self.debug_port);
+        vm.set_stack_limit(self.stack_limit, self.stack_flag);
+        vm.set_stack_check(self.stack_check);
+        vm.set_fast(self.fast);
+
+        Ok(vm)
+    }
+}
@@ -0,0 +1,104 @@
+/*!
+ * Spot challenge codes in the game's output.
+ *
+ * Codes are 12 character alphanumeric strings mixing upper and lower case
+ * letters, ie. `NBlOGjsNhDmQ`, which is unlike any regular word the game
+ * prints.
+ */
+
+use std::fs;
+use std::io;
+
+pub const CODE_LEN: usize = 12;
+
+// true if the word looks like a challenge code
+pub fn is_code(word: &str) -> bool {
+    word.len() == CODE_LEN
+        && word.chars().all(|c| c.is_ascii_alphanumeric())
+        && word.chars().any(|c| c.is_ascii_uppercase())
+        && word.chars().any(|c| c.is_ascii_lowercase())
+}
+
+// every code found in a chunk of output
+pub fn find_codes(output: &str) -> Vec<String> {
+    output
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| is_code(word))
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// codes hidden in the challenge
+pub const TOTAL: usize = 8;
+
+// md5 of a code as lowercase hex, the form code lists are published in
+pub fn md5_hex(code: &str) -> String {
+    format!("{:x}", md5::compute(code.as_bytes()))
+}
+
+// the ways a code can look wrong, empty if it looks plausible
+pub fn problems(code: &str) -> Vec<String> {
+    let mut problems = vec![];
+
+    if code.len() != CODE_LEN {
+        problems.push(format!(
+            "is {} characters, codes are {}",
+            code.len(),
+            CODE_LEN
+        ));
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        problems.push("has characters that aren't letters or digits".into());
+    }
+    if !code.chars().any(|c| c.is_ascii_uppercase())
+        || !code.chars().any(|c| c.is_ascii_lowercase())
+    {
+        problems.push("doesn't mix upper and lower case".into());
+    }
+
+    problems
+}
+
+// the codes found so far for a ROM, kept in a file with one code per line in
+// the order they were found
+pub struct Progress {
+    file: String,
+    found: Vec<String>,
+}
+
+impl Progress {
+    // a missing file is no progress yet
+    pub fn load(file: &str) -> Result<Self, String> {
+        let found = match fs::read_to_string(file) {
+            Ok(data) => data
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("failed to read {}: {}", file, e)),
+        };
+        Ok(Self { file: file.to_string(), found })
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn found(&self) -> &[String] {
+        &self.found
+    }
+
+    // record a code, returning false if it was already known
+    pub fn add(&mut self, code: &str) -> bool {
+        if self.found.iter().any(|c| c == code) {
+            return false;
+        }
+        self.found.push(code.to_string());
+
+        let data: String =
+            self.found.iter().map(|c| format!("{}\n", c)).collect();
+        fs::write(&self.file, data).unwrap();
+        true
+    }
+}
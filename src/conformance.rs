@@ -0,0 +1,344 @@
+/*!
+ * Check another Synacor VM against this one.
+ *
+ * `conformance verify --other "./their-vm"` generates a small ROM for each
+ * instruction, runs it on this VM and then on theirs (as `their-vm <rom>`,
+ * or with `{}` in the command replaced by the ROM), and compares what the
+ * two print:
+ *
+ * ```text
+ * halt   pass
+ * add    FAIL  case 3: expected "32766", got "65534"
+ * ...
+ * 21 of 22 instructions pass
+ * ```
+ *
+ * Each case prints one line, so a failure points at the case that went
+ * wrong.  The cases use fixed pseudo-random operands, the same every run.
+ *
+ * The tests for halt, out, noop and the jumps only print characters, but
+ * the rest print numbers with a routine using gt, add, mult, call, ret,
+ * push and pop - if everything fails, look at those first.
+ */
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::asm;
+use crate::symbols::Symbols;
+use crate::vm::VM;
+
+// random cases generated for each instruction
+const CASES: usize = 16;
+
+// instructions a test can run on this VM before it's considered stuck
+const STEP_MAX: u64 = 1_000_000;
+
+// how long the other VM gets for each test
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// prints r0 as five decimal digits and a newline, clobbering r0.  there's
+// no divide, so each digit is the number of times its power of ten can be
+// taken away
+const PRINT: &str = "
+print: push r1
+       set r1 10000
+       call digit
+       set r1 1000
+       call digit
+       set r1 100
+       call digit
+       set r1 10
+       call digit
+       set r1 1
+       call digit
+       out '\\n'
+       pop r1
+       ret
+digit: push r2
+       push r3
+       set r2 '0'
+dloop: gt r3 r1 r0
+       jt r3 dout
+       add r2 r2 1
+       mult r3 r1 32767
+       add r0 r0 r3
+       jmp dloop
+dout:  out r2
+       pop r3
+       pop r2
+       ret
+";
+
+pub struct Test {
+    pub name: &'static str,
+    pub rom: Vec<u8>,
+    pub input: Vec<u8>,
+}
+
+// xorshift, so the cases are the same every time without a dependency
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u16 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 % 32768) as u16
+    }
+
+    // a value, often one near the edges where mistakes are
+    fn value(&mut self) -> u16 {
+        match self.next() % 4 {
+            0 => [0, 1, 32767, 32766, 16384][self.next() as usize % 5],
+            _ => self.next(),
+        }
+    }
+}
+
+// the body of each test, before the print routine is added
+fn source(name: &str, rng: &mut Rng) -> String {
+    let mut src = String::new();
+    let mut line = |s: String| {
+        src += &s;
+        src.push('\n');
+    };
+
+    for i in 0..CASES {
+        let (a, b) = (rng.value(), rng.value());
+        let r = rng.next() % 8;
+        match name {
+            "halt" if i == 0 => {
+                line("out 'o'; out 'k'; out '\\n'; halt; out 'x'".into())
+            }
+            "out" => line(format!(
+                "out {}; set r{r} {}; out r{r}; out '\\n'",
+                33 + a % 94,
+                33 + b % 94,
+                r = r
+            )),
+            "noop" => line("noop; out 'o'; noop; out '\\n'".into()),
+            "jmp" => line(format!(
+                "jmp j{i}; out 'x'; j{i}: out 'y'; out '\\n'",
+                i = i
+            )),
+            "jt" | "jf" => line(format!(
+                "set r{r} {v}; {name} r{r} j{i}; out 'n'; jmp d{i}; \
+                 j{i}: out 'y'; d{i}: out '\\n'",
+                r = r,
+                v = [0, 1, a][i % 3],
+                name = name,
+                i = i
+            )),
+            "set" => {
+                line(format!("set r{} {}; set r0 r{}; call print", r, a, r))
+            }
+            "push" | "pop" => line(format!(
+                "set r1 {}; push r1; push {}; pop r0; call print; \
+                 pop r0; call print",
+                a, b
+            )),
+            "eq" | "gt" => {
+                // equal values need testing too
+                let b = if i % 4 == 0 { a } else { b };
+                line(format!(
+                    "set r1 {}; set r2 {}; {} r0 r1 r2; call print",
+                    a, b, name
+                ))
+            }
+            "add" | "mult" | "and" | "or" => {
+                line(format!("set r1 {}; {} r0 r1 {}; call print", a, name, b))
+            }
+            "mod" => line(format!(
+                "set r1 {}; mod r0 r1 {}; call print",
+                a,
+                b.max(1)
+            )),
+            "not" => line(format!("not r0 {}; call print", a)),
+            "wmem" | "rmem" => {
+                let addr = 20000 + a % 10000;
+                line(format!(
+                    "set r1 {}; wmem r1 {}; rmem r0 {}; call print",
+                    addr, b, addr
+                ));
+                // code is memory too
+                if i == 0 {
+                    line("rmem r0 1; call print".into());
+                }
+            }
+            "call" | "ret" => line(format!(
+                "set r1 c{i}; call r1; call e{i}; jmp d{i}; \
+                 c{i}: out 'c'; ret; e{i}: out 'e'; call c{i}; ret; \
+                 d{i}: out '\\n'",
+                i = i
+            )),
+            "in" => line("in r0; out r0; call print".into()),
+            _ => (),
+        }
+    }
+
+    // a ret with nothing on the stack halts
+    match name {
+        "ret" => src += "out 'z'; out '\\n'; ret; out 'x'\n",
+        _ => src += "halt\n",
+    }
+    src + PRINT
+}
+
+// one test ROM for each instruction
+pub fn tests() -> Vec<Test> {
+    let mut rng = Rng(0x5eed);
+    crate::vm::MNEMONICS
+        .iter()
+        .map(|name| {
+            let src = source(name, &mut rng);
+            let words = asm::assemble(&src, 0, &Symbols::default())
+                .unwrap_or_else(|e| panic!("{} test: {}", name, e));
+            let input = match *name {
+                // a line, for VMs that read a line at a time
+                "in" => b"abcdefghijklmno\n".to_vec(),
+                _ => vec![],
+            };
+            Test {
+                name,
+                rom: words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+                input,
+            }
+        })
+        .collect()
+}
+
+// what this VM prints for a test
+pub fn expected(test: &Test) -> Result<Vec<u8>, String> {
+    let mut vm = VM::new(test.rom.clone());
+    vm.queue_input(&test.input);
+    let mut output = vec![];
+    while !vm.is_halted() && !vm.wants_input() {
+        if vm.steps() >= STEP_MAX {
+            return Err(format!("{} test didn't finish", test.name));
+        }
+        vm.step().map_err(|e| format!("{} test: {}", test.name, e))?;
+        output.extend(vm.take_output());
+    }
+    Ok(output)
+}
+
+// run the other VM on a ROM file, giving it the test's input.  stderr
+// instead of stdout for VMs (like this one) that print the game there
+pub fn run_other(
+    cmd: &str,
+    rom: &str,
+    input: &[u8],
+    stderr: bool,
+) -> Result<Vec<u8>, String> {
+    let cmd = match cmd.contains("{}") {
+        true => cmd.replace("{}", rom),
+        false => format!("{} {}", cmd, rom),
+    };
+    let (out, err) = match stderr {
+        true => (Stdio::null(), Stdio::piped()),
+        false => (Stdio::piped(), Stdio::null()),
+    };
+    let mut child = Command::new("sh")
+        .args(["-c", &cmd])
+        .stdin(Stdio::piped())
+        .stdout(out)
+        .stderr(err)
+        .spawn()
+        .map_err(|e| format!("{}: {}", cmd, e))?;
+
+    // a VM that doesn't read its input mustn't block the write
+    child.stdin.take().unwrap().write_all(input).ok();
+
+    let mut pipe: Box<dyn Read + Send> = match stderr {
+        true => Box::new(child.stderr.take().unwrap()),
+        false => Box::new(child.stdout.take().unwrap()),
+    };
+    let reader = thread::spawn(move || {
+        let mut output = vec![];
+        pipe.read_to_end(&mut output).ok();
+        output
+    });
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if start.elapsed() > TIMEOUT => {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(format!("timed out after {:?}", TIMEOUT));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(reader.join().unwrap())
+}
+
+// where two outputs first differ, by line
+pub fn diff(expected: &[u8], got: &[u8]) -> Option<String> {
+    if expected == got {
+        return None;
+    }
+    let expected = String::from_utf8_lossy(expected);
+    let got = String::from_utf8_lossy(got);
+    let mut theirs = got.lines();
+    for (i, line) in expected.lines().enumerate() {
+        match theirs.next() {
+            Some(other) if other == line => continue,
+            Some(other) => {
+                return Some(format!(
+                    "case {}: expected {:?}, got {:?}",
+                    i + 1,
+                    line,
+                    other
+                ));
+            }
+            None => {
+                return Some(format!(
+                    "case {}: expected {:?}, got nothing",
+                    i + 1,
+                    line
+                ));
+            }
+        }
+    }
+    let mut extra = String::new();
+    for line in theirs.take(3) {
+        write!(extra, " {:?}", line).unwrap();
+    }
+    Some(format!("printed more than expected:{}", extra))
+}
+
+// run every test on the other VM, printing a line for each.  the ROMs are
+// written to dir.  true if they all pass
+pub fn verify(cmd: &str, dir: &str, stderr: bool) -> Result<bool, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+
+    let tests = tests();
+    let mut passed = 0;
+    for test in &tests {
+        let file = format!("{}/{}.bin", dir, test.name);
+        fs::write(&file, &test.rom).map_err(|e| format!("{}: {}", file, e))?;
+
+        let expected = expected(test)?;
+        let result = run_other(cmd, &file, &test.input, stderr)
+            .map(|got| diff(&expected, &got));
+        match result {
+            Ok(None) => {
+                println!("{:<6} pass", test.name);
+                passed += 1;
+            }
+            Ok(Some(diff)) => println!("{:<6} FAIL  {}", test.name, diff),
+            Err(e) => println!("{:<6} FAIL  {}", test.name, e),
+        }
+    }
+
+    println!("{} of {} instructions pass", passed, tests.len());
+    Ok(passed == tests.len())
+}
@@ -0,0 +1,225 @@
+/*!
+ * Find code that never runs, and stores that are never read.
+ *
+ * Three kinds of finding:
+ *
+ * - unreachable: a run of valid instructions ending in a jump, ret or halt
+ *   that nothing jumps or calls to and that was never seen executing
+ * - untaken: a `jt`/`jf` that ran but only ever went one way, with the side
+ *   that never ran (needs coverage data)
+ * - dead store: a register written and then written again before anything
+ *   could read it
+ *
+ * None of these are certain - jumps through registers can't be followed
+ * (bar `push x; ret`, see `disasm::indirect_jump`) and
+ * coverage only knows about the runs it came from - but the branch around a
+ * check that always fails shows up as untaken, and that's usually the one
+ * worth forcing.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::arity;
+use crate::disasm::{self, Instruction};
+use crate::map::{self, Kind};
+
+// shorter runs of instructions in data are too likely to be chance
+const MIN_BLOCK: usize = 3;
+
+pub enum Finding {
+    Unreachable { start: u16, end: u16 },
+    Untaken { addr: u16, ins: Instruction, never: u16 },
+    DeadStore { addr: u16, ins: Instruction, overwritten: u16 },
+}
+
+impl Finding {
+    pub fn addr(&self) -> u16 {
+        match self {
+            Finding::Unreachable { start, .. } => *start,
+            Finding::Untaken { addr, .. } => *addr,
+            Finding::DeadStore { addr, .. } => *addr,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Unreachable { start, end } => {
+                write!(f, "{:>5} unreachable code to {}", start, end)
+            }
+            Finding::Untaken { addr, ins, never } => {
+                write!(f, "{:>5} {}  never went to {}", addr, ins, never)
+            }
+            Finding::DeadStore { addr, ins, overwritten } => {
+                write!(f, "{:>5} {}  overwritten at {}", addr, ins, overwritten)
+            }
+        }
+    }
+}
+
+// true if the instruction never carries on to the next one
+fn ends_block(ins: &Instruction) -> bool {
+    matches!(ins.opcode, 0 | 6 | 18)
+}
+
+// the register an instruction writes, if it writes one
+fn written(ins: &Instruction) -> Option<u16> {
+    if !arity::writes_first(ins.opcode) {
+        return None;
+    }
+    ins.operands.first().copied().filter(|w| *w >= 32768)
+}
+
+// the registers an instruction reads
+fn read(ins: &Instruction) -> Vec<u16> {
+    let skip = if written(ins).is_some() { 1 } else { 0 };
+    ins.operands.iter().skip(skip).copied().filter(|w| *w >= 32768).collect()
+}
+
+// valid instructions in words nothing reaches, a block at a time
+fn unreachable(words: &[u16], kinds: &[Kind]) -> Vec<Finding> {
+    let mut found = vec![];
+    let mut addr = 0;
+
+    while addr < words.len() {
+        if kinds[addr] != Kind::Data {
+            addr += 1;
+            continue;
+        }
+
+        // follow instructions until one ends the block, or something isn't
+        // an unclaimed instruction
+        let start = addr;
+        let mut n = 0;
+        let mut end = None;
+        let mut at = addr;
+        while let Some(ins) = disasm::decode(words, at as u16) {
+            let next = at + ins.len();
+            if kinds[at..next.min(kinds.len())].iter().any(|k| *k != Kind::Data)
+            {
+                break;
+            }
+            n += 1;
+            at = next;
+            if ends_block(&ins) {
+                end = Some(at - 1);
+                break;
+            }
+        }
+
+        match end {
+            Some(end) if n >= MIN_BLOCK => {
+                found.push(Finding::Unreachable {
+                    start: start as u16,
+                    end: end as u16,
+                });
+                addr = end + 1;
+            }
+            _ => addr += 1,
+        }
+    }
+
+    found
+}
+
+// conditional jumps that ran and only ever went one way
+fn untaken(
+    code: &[Instruction],
+    executed: &dyn Fn(u16) -> bool,
+) -> Vec<Finding> {
+    code.iter()
+        .filter(|ins| matches!(ins.opcode, 7 | 8) && executed(ins.addr))
+        .filter_map(|ins| {
+            let target = ins.operands[1];
+            let next = ins.addr + ins.len() as u16;
+            // a target in a register goes somewhere different each time
+            if target >= 32768 {
+                return None;
+            }
+            let never = match (executed(target), executed(next)) {
+                (true, false) => next,
+                (false, true) => target,
+                _ => return None,
+            };
+            Some(Finding::Untaken { addr: ins.addr, ins: ins.clone(), never })
+        })
+        .collect()
+}
+
+// writes to a register that the next write replaces before any read, within
+// straight-line code.  calls count as reading everything, as their arguments
+// are in registers
+fn dead_stores(code: &[Instruction], targets: &BTreeSet<u16>) -> Vec<Finding> {
+    let mut found = vec![];
+    // the instruction that last wrote each register and hasn't been read
+    let mut pending: [Option<&Instruction>; 8] = [None; 8];
+    let mut last_end = None;
+
+    for ins in code {
+        // a jump target or a gap (data in between) starts over
+        if targets.contains(&ins.addr) || last_end != Some(ins.addr) {
+            pending = [None; 8];
+        }
+        last_end = Some(ins.addr + ins.len() as u16);
+
+        for r in read(ins) {
+            pending[(r - 32768) as usize] = None;
+        }
+        if let Some(r) = written(ins) {
+            let r = (r - 32768) as usize;
+            if let Some(prev) = pending[r] {
+                found.push(Finding::DeadStore {
+                    addr: prev.addr,
+                    ins: prev.clone(),
+                    overwritten: ins.addr,
+                });
+            }
+            pending[r] = Some(ins);
+        }
+        if matches!(ins.opcode, 0 | 6..=8 | 17 | 18) {
+            pending = [None; 8];
+        }
+    }
+
+    found
+}
+
+// everything found in the image, in address order.  executed is the
+// coverage, if there is any
+pub fn analyze(
+    ram: &[u8],
+    executed: Option<&dyn Fn(u16) -> bool>,
+) -> Vec<Finding> {
+    let words = map::words(ram);
+    let kinds = map::classify(ram, executed);
+
+    // the instructions in the code regions
+    let mut code = vec![];
+    for region in map::regions(&kinds).iter().filter(|r| r.kind == Kind::Code) {
+        code.extend(disasm::disassemble(&words, region.start..region.end + 1));
+    }
+    let targets: BTreeSet<u16> = code
+        .iter()
+        .filter_map(|ins| match ins.opcode {
+            6 | 17 => ins.operands.first().copied(),
+            7 | 8 => ins.operands.get(1).copied(),
+            _ => None,
+        })
+        .filter(|t| *t < 32768)
+        .chain(
+            code.iter()
+                .filter_map(|ins| disasm::indirect_jump(&words, ins.addr)),
+        )
+        .collect();
+
+    let mut found = unreachable(&words, &kinds);
+    if let Some(executed) = executed {
+        found.extend(untaken(&code, executed));
+    }
+    found.extend(dead_stores(&code, &targets));
+
+    found.sort_by_key(|f| f.addr());
+    found
+}
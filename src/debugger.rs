@@ -0,0 +1,195 @@
+/*!
+ * Breakpoints for pausing the VM and dropping into the command prompt, and
+ * the events the VM reports to its frontend.
+ *
+ * These are generic over the address type so any `Machine` can use them.
+ *
+ * Breakpoints and watched strings can be put in named groups, to turn a
+ * puzzle's worth of them off and on again without losing their ignore
+ * counts and such:
+ *
+ * ```text
+ * /group create teleporter b1 b2 w2734
+ * /group disable teleporter
+ * ```
+ *
+ * where `b1` is breakpoint 1 and `w2734` the string watched at 2734.
+ */
+
+use std::fmt;
+
+// a routine entered with `call`
+#[derive(Clone, Debug)]
+pub struct Frame<A = u16> {
+    // address the routine was called at
+    pub routine: A,
+    // address it will return to
+    pub return_addr: A,
+    // index of the return address on the stack
+    pub depth: usize,
+    // instruction count when the routine was entered
+    pub entered: u64,
+    // earliest instruction count at which this or any enclosing call goes
+    // over its budget
+    pub deadline: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint<A = u16> {
+    pub id: usize,
+    pub addr: A,
+    // times execution reached the address, including ignored hits
+    pub hits: u64,
+    // remaining hits to skip before stopping
+    pub ignore: u64,
+    // delete after the first stop
+    pub temporary: bool,
+    // a disabled breakpoint doesn't stop or count hits
+    pub enabled: bool,
+}
+
+pub struct Breakpoints<A = u16> {
+    list: Vec<Breakpoint<A>>,
+    next_id: usize,
+}
+
+impl<A> Default for Breakpoints<A> {
+    fn default() -> Self {
+        Self { list: vec![], next_id: 0 }
+    }
+}
+
+impl<A: Copy + PartialEq> Breakpoints<A> {
+    pub fn add(&mut self, addr: A, temporary: bool) -> usize {
+        self.next_id += 1;
+        self.list.push(Breakpoint {
+            id: self.next_id,
+            addr,
+            hits: 0,
+            ignore: 0,
+            temporary,
+            enabled: true,
+        });
+        self.next_id
+    }
+
+    // skip the next n hits of a breakpoint, false if there is no such id
+    pub fn set_ignore(&mut self, id: usize, n: u64) -> bool {
+        match self.list.iter_mut().find(|b| b.id == id) {
+            Some(b) => {
+                b.ignore = n;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // false if there is no such id
+    pub fn remove(&mut self, id: usize) -> bool {
+        let len = self.list.len();
+        self.list.retain(|b| b.id != id);
+        self.list.len() < len
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    // false if there is no such id
+    pub fn set_enabled(&mut self, id: usize, on: bool) -> bool {
+        match self.list.iter_mut().find(|b| b.id == id) {
+            Some(b) => {
+                b.enabled = on;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Breakpoint<A>> {
+        self.list.iter().find(|b| b.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint<A>> {
+        self.list.iter()
+    }
+
+    // record execution reaching addr, returning the breakpoint to stop at
+    // (if any).  temporary breakpoints are removed when they stop
+    pub fn hit(&mut self, addr: A) -> Option<Breakpoint<A>> {
+        let mut stop = None;
+
+        for b in self.list.iter_mut().filter(|b| b.addr == addr && b.enabled) {
+            b.hits += 1;
+            if b.ignore > 0 {
+                b.ignore -= 1;
+            } else if stop.is_none() {
+                stop = Some(b.clone());
+            }
+        }
+
+        if let Some(b) = &stop
+            && b.temporary
+        {
+            self.list.retain(|other| other.id != b.id);
+        }
+
+        stop
+    }
+}
+
+// one of the things in a group
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Member<A = u16> {
+    // a breakpoint, by id
+    Breakpoint(usize),
+    // a watched string, by address
+    String(A),
+}
+
+impl<A: std::str::FromStr> Member<A> {
+    // `b1` or `w2734`, see above
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(id) = s.strip_prefix('b') {
+            return id.parse().ok().map(Member::Breakpoint);
+        }
+        s.strip_prefix('w')?.parse().ok().map(Member::String)
+    }
+}
+
+impl<A: fmt::Display> fmt::Display for Member<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Member::Breakpoint(id) => write!(f, "b{}", id),
+            Member::String(addr) => write!(f, "w{}", addr),
+        }
+    }
+}
+
+// something a frontend should hear about, see `VM::take_events`
+#[derive(Clone, Debug)]
+pub enum Event<A = u16> {
+    // execution reached a breakpoint, the instruction there hasn't run yet
+    Breakpoint(Breakpoint<A>),
+    // a call has run more instructions than its budget
+    OverBudget { frame: Frame<A>, ran: u64, budget: u64 },
+    // a watched string changed, None if it is no longer a valid string
+    StringChanged(A, Option<String>),
+    // a call returned with the stack deeper (extra > 0) or shallower
+    // (extra < 0) than when it was made, not counting the return address
+    StackImbalance { frame: Frame<A>, ret: A, extra: isize },
+    // a line the program wrote to the debug port
+    Debug(String),
+}
+
+impl<A> Event<A> {
+    // true if the VM stopped before running the next instruction so the
+    // user can take a look
+    pub fn is_stop(&self) -> bool {
+        matches!(self, Event::Breakpoint(_) | Event::OverBudget { .. })
+    }
+}
@@ -0,0 +1,136 @@
+/*!
+ * Decode instructions out of memory.
+ *
+ * The output uses the same syntax the assembler reads, so disassembled code
+ * can be pasted into a patch.
+ */
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::vm::{MNEMONICS, OPERANDS};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub addr: u16,
+    pub opcode: u16,
+    // raw operand words - registers are 32768 - 32775
+    pub operands: Vec<u16>,
+}
+
+impl Instruction {
+    // number of words the instruction takes up
+    pub fn len(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        MNEMONICS[self.opcode as usize]
+    }
+}
+
+// an operand as the assembler reads it: a register, a character for `out`
+// or a number
+pub fn operand(opcode: u16, word: u16) -> String {
+    match word {
+        32768..=32775 => format!("r{}", word - 32768),
+        10 if opcode == 19 => "'\\n'".to_string(),
+        32..=126 if opcode == 19 && word != b'\'' as u16 => {
+            format!("'{}'", word as u8 as char)
+        }
+        n => n.to_string(),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for word in &self.operands {
+            write!(f, " {}", operand(self.opcode, *word))?;
+        }
+        Ok(())
+    }
+}
+
+// decode the instruction at addr, or None if the words there aren't a valid
+// instruction
+pub fn decode(words: &[u16], addr: u16) -> Option<Instruction> {
+    let opcode = *words.get(addr as usize)?;
+    let n = *OPERANDS.get(opcode as usize)? as usize;
+
+    let start = addr as usize + 1;
+    let operands = words.get(start..start + n)?;
+    if operands.iter().any(|w| *w > 32775) {
+        return None;
+    }
+
+    Some(Instruction { addr, opcode, operands: operands.to_vec() })
+}
+
+// every instruction in the range, in order.  words that don't decode are
+// skipped one at a time as data, so code after a table is still found
+pub fn disassemble(words: &[u16], range: Range<u16>) -> Vec<Instruction> {
+    let end = range.end.min(words.len() as u16);
+    let mut out = vec![];
+    let mut addr = range.start;
+    while addr < end {
+        match decode(words, addr) {
+            Some(ins) => {
+                addr += ins.len() as u16;
+                out.push(ins);
+            }
+            None => addr += 1,
+        }
+    }
+    out
+}
+
+// the address an indirect jump starting at addr goes to.  the ROM jumps
+// with `push x; ret` (or `set r x; push r; ret`), which otherwise looks
+// like a routine ending and hides where it goes.  x is always the last
+// operand of the instruction at addr
+pub fn indirect_jump(words: &[u16], addr: u16) -> Option<u16> {
+    let first = decode(words, addr)?;
+    let target = *first.operands.last()?;
+    let push = match first.opcode {
+        // pushing the register just set
+        1 => decode(words, addr + first.len() as u16)
+            .filter(|p| p.opcode == 2 && p.operands[0] == first.operands[0])?,
+        2 => first,
+        _ => return None,
+    };
+    let ret = push.addr as usize + push.len();
+    (target < 32768 && words.get(ret) == Some(&18)).then_some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+    use crate::symbols::Symbols;
+
+    // whatever `out` prints, the listing assembles back to the same words
+    #[test]
+    fn out_round_trips() {
+        for byte in 0..=255 {
+            let words = [19, byte];
+            let ins = decode(&words, 0).unwrap();
+            let text = ins.to_string();
+            let back = asm::assemble(&text, 0, &Symbols::default());
+            assert_eq!(back, Ok(words.to_vec()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(operand(19, b' ' as u16), "' '");
+        assert_eq!(operand(19, b'\'' as u16), "39");
+        assert_eq!(operand(19, 10), "'\\n'");
+        assert_eq!(operand(9, 65), "65");
+        assert_eq!(operand(9, 32770), "r2");
+    }
+}
@@ -0,0 +1,110 @@
+/*!
+ * A high-level driver for playing the game from code.
+ *
+ * ```no_run
+ * use synacor_challenge::driver::Game;
+ *
+ * let mut game = Game::load(std::fs::read("challenge.bin").unwrap());
+ * println!("{}", game.command("take tablet"));
+ * println!("{:?}", game.inventory());
+ * game.save("tablet.json").unwrap();
+ * ```
+ */
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+use crate::machine::Machine;
+use crate::parser::{self, Room};
+use crate::vm::VM;
+
+pub struct Game<M: Machine = VM> {
+    vm: M,
+    intro: String,
+    // the instruction that failed, which stops the game
+    fault: Option<M::Error>,
+}
+
+impl Game<VM> {
+    // load a ROM and run it up to the first prompt
+    pub fn load(rom: Vec<u8>) -> Self {
+        Self::from_vm(VM::new(rom))
+    }
+}
+
+impl<M: Machine> Game<M> {
+    // take over an existing VM (ie. one loaded from a save) and run it up to
+    // the next prompt
+    pub fn from_vm(vm: M) -> Self {
+        let mut game = Self { vm, intro: String::new(), fault: None };
+        game.intro = game.run();
+        game
+    }
+
+    // everything the game printed before the first prompt
+    pub fn intro(&self) -> &str {
+        &self.intro
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.vm.is_halted() || self.fault.is_some()
+    }
+
+    pub fn fault(&self) -> Option<&M::Error> {
+        self.fault.as_ref()
+    }
+
+    pub fn vm(&self) -> &M {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut M {
+        &mut self.vm
+    }
+
+    // run until the game asks for input (or halts, or faults) and return
+    // its output
+    pub fn run(&mut self) -> String {
+        if self.fault.is_none()
+            && let Err(e) = self.vm.run_until_input()
+        {
+            self.fault = Some(e);
+        }
+
+        String::from_utf8_lossy(&self.vm.take_output()).into_owned()
+    }
+
+    // send a line of input and return everything printed in response
+    pub fn command(&mut self, cmd: &str) -> String {
+        self.vm.send_input(cmd.trim_end().as_bytes());
+        self.vm.send_input(b"\n");
+        self.run()
+    }
+
+    // look around and parse the room description
+    pub fn look(&mut self) -> Option<Room> {
+        parser::parse_room(&self.command("look"))
+    }
+
+    // the items currently held, as listed by `inv`
+    pub fn inventory(&mut self) -> Vec<String> {
+        let output = self.command("inv");
+
+        output
+            .lines()
+            .skip_while(|line| !line.starts_with("Your inventory:"))
+            .skip(1)
+            .map_while(|line| line.strip_prefix("- "))
+            .map(|item| item.to_string())
+            .collect()
+    }
+}
+
+impl<M: Machine + Serialize> Game<M> {
+    // write the full VM state as JSON, loadable by the main binary
+    pub fn save(&self, file: &str) -> io::Result<()> {
+        let data = serde_json::to_string(&self.vm)?;
+        fs::write(file, data)
+    }
+}
@@ -0,0 +1,185 @@
+/*!
+ * Say what an instruction is about to do, in plain English.
+ *
+ * For `--explain` and `/explain on`, which print a line for each instruction
+ * as it runs, with the values its operands have right then:
+ *
+ * ```text
+ *  1531  set r1 41
+ *        set register 1 to 41
+ *  1534  jt r1 1458
+ *        register 1 (41) isn't 0, so jump to 1458
+ * ```
+ *
+ * It's meant for learning how the machine works from small programs -
+ * explaining the whole game is a lot of output.
+ */
+
+use crate::disasm::Instruction;
+use crate::vm::{OPERANDS, VM};
+
+// the instruction at the pc, if there is a valid one
+fn current(vm: &VM) -> Option<Instruction> {
+    let word = |addr: u16| vm.ram().word(addr);
+    let addr = vm.pc();
+    let opcode = word(addr)?;
+    let n = *OPERANDS.get(opcode as usize)?;
+    let operands: Option<Vec<u16>> = (1..=n).map(|i| word(addr + i)).collect();
+    let operands = operands?;
+    if operands.iter().any(|w| *w > 32775) {
+        return None;
+    }
+    Some(Instruction { addr, opcode, operands })
+}
+
+// what an operand holds, ie. "42" or "register 2 (7)"
+fn describe(vm: &VM, word: u16) -> String {
+    match word {
+        32768..=32775 => {
+            let r = word - 32768;
+            format!("register {} ({})", r, vm.registers()[r as usize])
+        }
+        n => n.to_string(),
+    }
+}
+
+fn value(vm: &VM, word: u16) -> u16 {
+    match word {
+        32768..=32775 => vm.registers()[(word - 32768) as usize],
+        n => n,
+    }
+}
+
+// the register an operand names, as written when it's written to
+fn target(word: u16) -> String {
+    match word {
+        32768..=32775 => format!("register {}", word - 32768),
+        n => format!("{} (not a register!)", n),
+    }
+}
+
+fn character(c: u16) -> String {
+    match c {
+        10 => "a newline".to_string(),
+        32..=126 => format!("'{}'", c as u8 as char),
+        c => format!("character {}", c),
+    }
+}
+
+// the instruction at the pc and what it does, on two lines
+pub fn explain(vm: &VM) -> String {
+    let Some(ins) = current(vm) else {
+        let word = vm.ram().word(vm.pc()).unwrap_or(0);
+        return format!(
+            "{:>5}  {}\n       not an instruction, running it is an error",
+            vm.pc(),
+            word
+        );
+    };
+
+    let ops = &ins.operands;
+    let arg = |i: usize| describe(vm, ops[i]);
+    let val = |i: usize| value(vm, ops[i]);
+    let next = ins.addr + ins.len() as u16;
+    let stack = vm.stack.len();
+
+    let what = match ins.opcode {
+        0 => "stop the machine".to_string(),
+        1 => format!("set {} to {}", target(ops[0]), arg(1)),
+        2 => format!(
+            "push {} onto the stack, making it {} deep",
+            arg(0),
+            stack + 1
+        ),
+        3 => match vm.stack.last() {
+            Some(top) => format!(
+                "pop the top of the stack ({}) into {}",
+                top,
+                target(ops[0])
+            ),
+            None => "pop from the stack, but it's empty - an error".to_string(),
+        },
+        4 | 5 => {
+            let (op, result) = match ins.opcode {
+                4 => ("equals", val(1) == val(2)),
+                _ => ("is greater than", val(1) > val(2)),
+            };
+            format!(
+                "set {} to 1 if {} {} {}, otherwise 0 - it {}, so {}",
+                target(ops[0]),
+                arg(1),
+                op,
+                arg(2),
+                if result { "does" } else { "doesn't" },
+                result as u16
+            )
+        }
+        6 => format!("jump to {}", arg(0)),
+        7 | 8 => {
+            let jumps = (val(0) != 0) == (ins.opcode == 7);
+            format!(
+                "{} {} 0, so {}",
+                arg(0),
+                if val(0) != 0 { "isn't" } else { "is" },
+                if jumps {
+                    format!("jump to {}", arg(1))
+                } else {
+                    format!("carry on to {}", next)
+                }
+            )
+        }
+        9..=13 => {
+            let (op, result) = match ins.opcode {
+                9 => ("+", (val(1) as u32 + val(2) as u32) % 32768),
+                10 => ("*", (val(1) as u32 * val(2) as u32) % 32768),
+                11 if val(2) == 0 => {
+                    return format!(
+                        "{:>5}  {}\n       {} mod 0 - an error",
+                        ins.addr,
+                        ins,
+                        arg(1)
+                    );
+                }
+                11 => ("mod", (val(1) % val(2)) as u32),
+                12 => ("bitwise and", (val(1) & val(2)) as u32),
+                _ => ("bitwise or", (val(1) | val(2)) as u32),
+            };
+            let wrapped = match ins.opcode {
+                9 | 10 => " (wrapping at 32768)",
+                _ => "",
+            };
+            format!(
+                "set {} to {} {} {}{}, which is {}",
+                target(ops[0]),
+                arg(1),
+                op,
+                arg(2),
+                wrapped,
+                result
+            )
+        }
+        14 => format!(
+            "set {} to the 15 bit inverse of {}, which is {}",
+            target(ops[0]),
+            arg(1),
+            !val(1) & 0x7fff
+        ),
+        15 => format!(
+            "set {} to the value at address {}, which is {}",
+            target(ops[0]),
+            arg(1),
+            vm.ram().word(val(1)).unwrap_or(0)
+        ),
+        16 => format!("write {} to address {}", arg(1), arg(0)),
+        17 => format!("call {}, pushing the return address {}", arg(0), next),
+        18 => match vm.stack.last() {
+            Some(top) => format!("return to the address on the stack, {}", top),
+            None => "return with nothing on the stack, which halts".to_string(),
+        },
+        19 => format!("print {}", character(val(0))),
+        20 => format!("read a character of input into {}", target(ops[0])),
+        _ => "do nothing".to_string(),
+    };
+
+    format!("{:>5}  {}\n       {}", ins.addr, ins, what)
+}
@@ -0,0 +1,151 @@
+/*!
+ * Filters applied to the game's output, a line at a time, before it reaches
+ * the terminal.
+ *
+ * Filters are given as specs and run in the order they were added:
+ *
+ * - `strip-ansi` - remove terminal escape sequences
+ * - `upper` - uppercase everything
+ * - `redact-codes` - replace challenge codes with `************`
+ * - `s/regex/replacement/` - regex replace, any delimiter can be used in
+ *   place of `/` and the replacement can refer to groups with `$1`
+ * - `tee:<file>` - append the line, as filtered so far, to a file
+ */
+
+use regex::Regex;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use crate::codes;
+
+pub enum Filter {
+    StripAnsi,
+    Upper,
+    RedactCodes,
+    Replace(Regex, String),
+    Tee(File),
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "strip-ansi" => return Ok(Filter::StripAnsi),
+            "upper" => return Ok(Filter::Upper),
+            "redact-codes" => return Ok(Filter::RedactCodes),
+            _ => (),
+        }
+
+        if let Some(file) = spec.strip_prefix("tee:") {
+            let f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .map_err(|e| format!("failed to open {}: {}", file, e))?;
+            return Ok(Filter::Tee(f));
+        }
+
+        if let Some(rest) = spec.strip_prefix('s')
+            && let Some(delim) = rest.chars().next()
+            && !delim.is_alphanumeric()
+        {
+            let parts: Vec<_> = rest[delim.len_utf8()..].split(delim).collect();
+            let [pattern, replacement, ""] = parts[..] else {
+                return Err(format!(
+                    "expected s{0}regex{0}replacement{0}, found '{1}'",
+                    delim, spec
+                ));
+            };
+            let re = Regex::new(pattern)
+                .map_err(|e| format!("invalid regex '{}': {}", pattern, e))?;
+            return Ok(Filter::Replace(re, replacement.to_string()));
+        }
+
+        Err(format!("unknown filter '{}'", spec))
+    }
+
+    fn apply(&mut self, line: String) -> String {
+        match self {
+            Filter::StripAnsi => strip_ansi(&line),
+            Filter::Upper => line.to_uppercase(),
+            Filter::RedactCodes => {
+                let mut line = line;
+                for code in codes::find_codes(&line) {
+                    line = line.replace(&code, &"*".repeat(codes::CODE_LEN));
+                }
+                line
+            }
+            Filter::Replace(re, replacement) => {
+                re.replace_all(&line, replacement.as_str()).into_owned()
+            }
+            Filter::Tee(f) => {
+                f.write_all(line.as_bytes()).unwrap();
+                line
+            }
+        }
+    }
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        // CSI sequences run until a letter, anything else is a lone escape
+        if chars.peek() == Some(&'[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    filters: Vec<(String, Filter)>,
+    // output of the current line so far
+    line: Vec<u8>,
+}
+
+impl Pipeline {
+    pub fn add(&mut self, spec: &str) -> Result<(), String> {
+        let filter = Filter::parse(spec)?;
+        self.filters.push((spec.to_string(), filter));
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.filters.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn specs(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|(spec, _)| spec.as_str())
+    }
+
+    // add a byte of output, returning the filtered line once it is complete
+    pub fn push(&mut self, c: u8) -> Option<String> {
+        self.line.push(c);
+        if c == b'\n' { Some(self.flush()) } else { None }
+    }
+
+    // filter whatever is buffered, ie. a prompt waiting on input
+    pub fn flush(&mut self) -> String {
+        let mut line = String::from_utf8_lossy(&std::mem::take(&mut self.line))
+            .into_owned();
+        for (_, filter) in self.filters.iter_mut() {
+            line = filter.apply(line);
+        }
+        line
+    }
+}
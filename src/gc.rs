@@ -0,0 +1,204 @@
+/*!
+ * Delete saves that aren't worth keeping.
+ *
+ * `/gc [--dry-run] [--keep N] [dir]` looks at the saved states in a
+ * directory (as `/saves` lists them) and keeps:
+ *
+ * - the N most recently written (KEEP_LAST without --keep)
+ * - the newest save for each code milestone, the `<n>-<code>.json` files
+ *   written to codes/ when a code is found
+ * - branch heads: delta saves nothing else is a delta of, ie. the end of
+ *   each line of play saved with `/export-delta`
+ * - anything a kept delta save needs to load, its base and their bases
+ *
+ * and deletes the rest.  With --dry-run it only says what it would do:
+ *
+ * ```text
+ * keep    codes/3-XdrbODYTcnWP.json    code 3
+ * keep    save-20251221-134502.json    recent
+ * keep    base.json                    base of save-20251221-134502.json
+ * delete  save-20251219-091133.json    412 KB
+ * would free 412 KB, deleting 1 of 4 saves
+ * ```
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::saves;
+
+// saves kept for being recent, when not told otherwise
+pub const KEEP_LAST: usize = 10;
+
+pub struct Policy {
+    pub keep_last: usize,
+    pub milestones: bool,
+    pub heads: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self { keep_last: KEEP_LAST, milestones: true, heads: true }
+    }
+}
+
+pub struct Decision {
+    pub path: PathBuf,
+    pub size: u64,
+    // why it's kept, none if it's to be deleted
+    pub reasons: Vec<String>,
+}
+
+impl Decision {
+    pub fn keep(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+// the code number of a milestone save, from its name
+fn milestone(path: &Path) -> Option<u32> {
+    let stem = path.file_stem()?.to_str()?;
+    let (n, code) = stem.split_once('-')?;
+    let alphanumeric = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric());
+    match alphanumeric(code) && !code.is_empty() {
+        true => n.parse().ok(),
+        false => None,
+    }
+}
+
+// the save a delta was made against, resolved the way loading it does
+fn base_of(path: &Path, base: &str) -> Option<PathBuf> {
+    let base = match path.parent() {
+        Some(dir) if Path::new(base).is_relative() => dir.join(base),
+        _ => PathBuf::from(base),
+    };
+    fs::canonicalize(base).ok()
+}
+
+fn size(n: u64) -> String {
+    match n {
+        0..1024 => format!("{} bytes", n),
+        1024..1048576 => format!("{} KB", n / 1024),
+        _ => format!("{:.1} MB", n as f64 / 1048576.0),
+    }
+}
+
+// what to do with each save in dir, newest first
+pub fn plan(dir: &str, policy: &Policy) -> Result<Vec<Decision>, String> {
+    let mut saves = vec![];
+    for (path, info) in saves::list(dir)? {
+        let meta = fs::metadata(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let canonical = fs::canonicalize(&path).unwrap_or(path.clone());
+        let base = info.base.as_deref().and_then(|b| base_of(&path, b));
+        saves.push((path, canonical, meta.len(), modified, base));
+    }
+    saves.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+
+    let mut decisions: Vec<Decision> = saves
+        .iter()
+        .map(|(path, _, size, _, _)| Decision {
+            path: path.clone(),
+            size: *size,
+            reasons: vec![],
+        })
+        .collect();
+
+    for decision in decisions.iter_mut().take(policy.keep_last) {
+        decision.reasons.push("recent".to_string());
+    }
+
+    if policy.milestones {
+        let mut seen = vec![];
+        for decision in decisions.iter_mut() {
+            if let Some(n) = milestone(&decision.path)
+                && !seen.contains(&n)
+            {
+                seen.push(n);
+                decision.reasons.push(format!("code {}", n));
+            }
+        }
+    }
+
+    let index: HashMap<&PathBuf, usize> =
+        saves.iter().enumerate().map(|(i, s)| (&s.1, i)).collect();
+    let is_base: Vec<bool> = saves
+        .iter()
+        .map(|s| saves.iter().any(|o| o.4.as_ref() == Some(&s.1)))
+        .collect();
+    if policy.heads {
+        for (i, s) in saves.iter().enumerate() {
+            if s.4.is_some() && !is_base[i] {
+                decisions[i].reasons.push("branch head".to_string());
+            }
+        }
+    }
+
+    // a kept delta is no use without its base, and so on down the chain
+    for i in 0..saves.len() {
+        if !decisions[i].keep() {
+            continue;
+        }
+        let (mut child, mut base) = (i, saves[i].4.as_ref());
+        while let Some(&j) = base.and_then(|b| index.get(b)) {
+            let name = saves[child].0.file_name().unwrap_or_default();
+            let reason = format!("base of {}", name.to_string_lossy());
+            if decisions[j].reasons.contains(&reason) {
+                break;
+            }
+            decisions[j].reasons.push(reason);
+            (child, base) = (j, saves[j].4.as_ref());
+        }
+    }
+
+    Ok(decisions)
+}
+
+// a line for each save, and the total
+pub fn report(decisions: &[Decision], dry_run: bool) -> String {
+    let width = decisions
+        .iter()
+        .map(|d| d.path.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![];
+    let (mut freed, mut deleted) = (0, 0);
+    for d in decisions {
+        let (action, why) = match d.keep() {
+            true => ("keep", d.reasons.join(", ")),
+            false => {
+                freed += d.size;
+                deleted += 1;
+                ("delete", size(d.size))
+            }
+        };
+        lines.push(format!(
+            "{:<6}  {:<width$}  {}",
+            action,
+            d.path.display().to_string(),
+            why,
+            width = width
+        ));
+    }
+    lines.push(format!(
+        "{} {}, deleting {} of {} saves",
+        if dry_run { "would free" } else { "freed" },
+        size(freed),
+        deleted,
+        decisions.len()
+    ));
+    lines.join("\n")
+}
+
+// delete what the plan doesn't keep
+pub fn delete(decisions: &[Decision]) -> Result<(), String> {
+    for d in decisions.iter().filter(|d| !d.keep()) {
+        fs::remove_file(&d.path)
+            .map_err(|e| format!("{}: {}", d.path.display(), e))?;
+    }
+    Ok(())
+}
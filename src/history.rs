@@ -0,0 +1,93 @@
+/*!
+ * `/` command history kept across sessions.
+ *
+ * Commands are appended to a per-user file, one per line with the leading
+ * slash, as they are run.  The shell hands them to the line editor so the up
+ * arrow (and ctrl-r) can find commands from earlier sessions, and `/history`
+ * searches them.
+ */
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+// commands to keep, older ones are dropped when the file is loaded
+const HISTORY_MAX: usize = 1000;
+
+pub struct History {
+    file: String,
+    entries: Vec<String>,
+}
+
+impl History {
+    // $SYNACOR_HISTORY, or ~/.synacor_history
+    pub fn default_file() -> Option<String> {
+        if let Ok(file) = env::var("SYNACOR_HISTORY") {
+            return Some(file);
+        }
+        env::var("HOME").ok().map(|home| format!("{}/.synacor_history", home))
+    }
+
+    // a missing file is an empty history
+    pub fn load(file: &str) -> Result<Self, String> {
+        let mut entries: Vec<String> = match fs::read_to_string(file) {
+            Ok(data) => data
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.to_string())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("failed to read {}: {}", file, e)),
+        };
+
+        if entries.len() > HISTORY_MAX {
+            entries.drain(..entries.len() - HISTORY_MAX);
+            let data: String =
+                entries.iter().map(|e| format!("{}\n", e)).collect();
+            fs::write(file, data)
+                .map_err(|e| format!("failed to write {}: {}", file, e))?;
+        }
+
+        Ok(Self { file: file.to_string(), entries })
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    // record a command, unless it repeats the one before it
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().is_some_and(|l| l == line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+
+        // append rather than rewrite so concurrent sessions don't clobber
+        // each other
+        let res = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)
+            .and_then(|mut f| writeln!(f, "{}", line));
+        if let Err(e) = res {
+            println!("failed to write {}: {}", self.file, e);
+        }
+    }
+
+    // entries containing text, numbered from 1 for the oldest
+    pub fn search<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a String)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, e)| e.contains(text))
+            .map(|(i, e)| (i + 1, e))
+    }
+}
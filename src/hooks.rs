@@ -0,0 +1,94 @@
+/*!
+ * Input hooks - type something automatically when execution reaches an
+ * address.
+ *
+ * A hooks file has one `addr input` rule per line, blank lines and lines
+ * starting with `#` are ignored.  The input is everything after the address,
+ * with `\n`, `\t` and `\\` escapes:
+ *
+ * ```text
+ * # answer the question every time it is asked
+ * 2734 yes\n
+ * ```
+ *
+ * A hook fires once each time the game asks for input: having fired, it
+ * waits until the game next reads with nothing left to read, so a hook on
+ * the `in` of a read-line loop types its line once rather than once for
+ * every character read.
+ */
+
+use std::fs;
+
+#[derive(Clone, Debug)]
+pub struct Hook {
+    pub addr: u16,
+    pub input: Vec<u8>,
+    // times the hook has fired
+    pub hits: u64,
+    // set once fired, until the game next waits for input
+    pub fired: bool,
+}
+
+fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            out.extend(c.encode_utf8(&mut buf).bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('\\') => out.push(b'\\'),
+            Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+            None => return Err("trailing '\\'".to_string()),
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn parse(s: &str) -> Result<Vec<Hook>, String> {
+    let mut hooks = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.trim().is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let Some((addr, input)) = trimmed.split_once(char::is_whitespace)
+        else {
+            return Err(format!(
+                "line {}: expected `addr input`, found '{}'",
+                i + 1,
+                line
+            ));
+        };
+        let addr: u16 = match addr.parse() {
+            Ok(addr) if addr < 32768 => addr,
+            _ => {
+                return Err(format!(
+                    "line {}: invalid address '{}'",
+                    i + 1,
+                    addr
+                ));
+            }
+        };
+        let input =
+            unescape(input).map_err(|e| format!("line {}: {}", i + 1, e))?;
+
+        hooks.push(Hook { addr, input, hits: 0, fired: false });
+    }
+
+    Ok(hooks)
+}
+
+pub fn load(file: &str) -> Result<Vec<Hook>, String> {
+    let data = fs::read_to_string(file)
+        .map_err(|e| format!("failed to read {}: {}", file, e))?;
+    parse(&data)
+}
@@ -0,0 +1,93 @@
+/*!
+ * Take back instructions one at a time.
+ *
+ * While single-stepping (`/step`, `/animate`) the VM notes what each
+ * instruction is about to change before running it - the register it
+ * writes, the word a `wmem` writes over, the top of the stack, the
+ * character of input an `in` reads - so `/stepback [n]` can put things back
+ * as they were without a snapshot per instruction.  The last JOURNAL_MAX
+ * instructions are kept.
+ *
+ * Running freely (carrying on from the debug prompt, or typing a line for
+ * the game) forgets the journal, so it only ever goes back over
+ * instructions that were stepped.  Unless the shell was started with
+ * `--rewind N`: then every instruction goes in, the last N of them are
+ * kept, and `/rewind [n]` takes back the last n wherever they came from and
+ * opens the debug prompt there.  That runs the game without superblocks, a
+ * few times slower.
+ *
+ * Either way, output that has been printed stays printed.
+ */
+
+use std::collections::VecDeque;
+
+use crate::debugger::Frame;
+
+// instructions that can be stepped back over
+pub const JOURNAL_MAX: usize = 5000;
+
+// the state one instruction could change, from just before it ran
+pub(crate) struct Entry {
+    pub pc: u16,
+    pub steps: u64,
+    // the register it writes and what was in it
+    pub register: Option<(usize, u16)>,
+    // the address wmem wrote and the word that was there, and how long
+    // memory was, since writing past the end grows it
+    pub memory: Option<(u16, u16)>,
+    pub ram_len: usize,
+    // the stack's length and top, an instruction only pushes or pops one
+    pub stack_len: usize,
+    pub stack_top: Option<u16>,
+    // the input character read by in
+    pub input: Option<u8>,
+    // the routines being run, for call and ret
+    pub frames: Option<Vec<Frame>>,
+    pub running: bool,
+    pub pushed: Option<u16>,
+}
+
+pub struct Journal {
+    entries: VecDeque<Entry>,
+    // instructions kept before the oldest is forgotten
+    max: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new(JOURNAL_MAX)
+    }
+}
+
+impl Journal {
+    pub fn new(max: usize) -> Self {
+        Self { entries: VecDeque::new(), max: max.max(1) }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub(crate) fn push(&mut self, entry: Entry) {
+        if self.entries.len() == self.max {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,142 @@
+/*!
+ * Key bindings for the line editor.
+ *
+ * The prompts are read with a line editor, in emacs mode unless a keys file
+ * says otherwise.  `--keys FILE` (without it $SYNACOR_KEYS, or
+ * ~/.synacor_keys if there is one) picks a preset and binds keys to `/`
+ * commands:
+ *
+ * ```text
+ * # vi editing, F5 to continue from a breakpoint and F10 to step
+ * preset vi
+ * # and a couple more
+ * bind C-s save
+ * bind F9 break 2734
+ * ```
+ *
+ * `preset emacs` binds M-n to step, M-c to continue and M-s to save, and
+ * `preset vi` F10 to step, F5 to continue and F2 to save; bindings after
+ * the preset add to or replace its keys.  A key is `C-x` (control), `M-x`
+ * (alt) or `F1` to `F12`.
+ *
+ * A bound key runs its command straight away, whatever has been typed on
+ * the line, just as if it had been typed with a `/` in front.
+ */
+
+use rustyline::{KeyCode, KeyEvent, Modifiers};
+use std::env;
+use std::fs;
+use std::io;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Keys {
+    pub mode: Mode,
+    // key as written in the file, and the command it runs
+    pub bindings: Vec<(String, String)>,
+}
+
+// the keys file, ie. "C-s" or "F5"
+pub fn parse_key(key: &str) -> Option<KeyEvent> {
+    let char_key = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c.to_ascii_lowercase()),
+            _ => None,
+        }
+    };
+    if let Some(c) = key.strip_prefix("C-").and_then(char_key) {
+        return Some(KeyEvent::ctrl(c));
+    }
+    if let Some(c) = key.strip_prefix("M-").and_then(char_key) {
+        return Some(KeyEvent::alt(c));
+    }
+    match key.strip_prefix('F').and_then(|n| n.parse().ok()) {
+        Some(n @ 1..=12) => Some(KeyEvent(KeyCode::F(n), Modifiers::NONE)),
+        _ => None,
+    }
+}
+
+impl Keys {
+    // $SYNACOR_KEYS, or ~/.synacor_keys
+    pub fn default_file() -> Option<String> {
+        if let Ok(file) = env::var("SYNACOR_KEYS") {
+            return Some(file);
+        }
+        env::var("HOME").ok().map(|home| format!("{}/.synacor_keys", home))
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (mode, keys) = match name {
+            "emacs" => (
+                Mode::Emacs,
+                [("M-n", "step"), ("M-c", "continue"), ("M-s", "save")],
+            ),
+            "vi" => (
+                Mode::Vi,
+                [("F10", "step"), ("F5", "continue"), ("F2", "save")],
+            ),
+            _ => return None,
+        };
+        let bindings =
+            keys.iter().map(|(k, c)| (k.to_string(), c.to_string())).collect();
+        Some(Self { mode, bindings })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys = Keys::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |e: String| format!("line {}: {}", i + 1, e);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["preset", name] => {
+                    keys = Keys::preset(name).ok_or_else(|| {
+                        err(format!("no preset {:?}, try emacs or vi", name))
+                    })?;
+                }
+                ["bind", key, ref cmd @ ..] if !cmd.is_empty() => {
+                    if parse_key(key).is_none() {
+                        return Err(err(format!(
+                            "bad key {:?}, try C-x, M-x or F1 to F12",
+                            key
+                        )));
+                    }
+                    let cmd = cmd.join(" ");
+                    let cmd = cmd.strip_prefix('/').unwrap_or(&cmd);
+                    keys.bindings.retain(|(k, _)| k != key);
+                    keys.bindings.push((key.to_string(), cmd.to_string()));
+                }
+                _ => {
+                    return Err(err(
+                        "expected `preset <name>` or `bind <key> <command>`"
+                            .to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    // a missing file is the defaults
+    pub fn load(file: &str) -> Result<Self, String> {
+        match fs::read_to_string(file) {
+            Ok(text) => {
+                Keys::parse(&text).map_err(|e| format!("{}: {}", file, e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(Keys::default())
+            }
+            Err(e) => Err(format!("{}: {}", file, e)),
+        }
+    }
+}
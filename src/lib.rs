@@ -0,0 +1,64 @@
+/*!
+ * My implementation of the 2012 Synacor VM Challenge.
+ *
+ * Author: Dave Eddy <ysap@daveeddy.com>
+ * Date: December 21, 2025
+ * License: MIT
+ */
+
+pub mod arity;
+pub mod asm;
+#[cfg(feature = "tokio")]
+pub mod asyncvm;
+pub mod audit;
+pub mod autosave;
+pub mod binsave;
+pub mod books;
+pub mod builder;
+pub mod codes;
+pub mod conformance;
+pub mod deadcode;
+pub mod debugger;
+pub mod disasm;
+pub mod driver;
+pub mod explain;
+pub mod filters;
+pub mod gc;
+pub mod history;
+pub mod hooks;
+pub mod journal;
+pub mod keys;
+pub mod listing;
+pub mod logfile;
+pub mod logging;
+pub mod machine;
+pub mod map;
+pub mod memedit;
+pub mod novelty;
+pub mod offsets;
+pub mod parser;
+pub mod plugin;
+pub mod ram;
+pub mod rominfo;
+pub mod roomgraph;
+pub mod sampler;
+pub mod saves;
+pub mod scan;
+pub mod script;
+pub mod shell;
+pub mod shutdown;
+pub mod stats;
+pub mod strings;
+pub mod superblock;
+pub mod symbols;
+pub mod textstate;
+pub mod throttle;
+pub mod timeline;
+pub mod trace;
+pub mod tracefilter;
+pub mod tracesink;
+pub mod validate;
+pub mod valuehistory;
+pub mod vault;
+pub mod vm;
+pub mod watchdog;
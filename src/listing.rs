@@ -0,0 +1,175 @@
+/*!
+ * A disassembly listing meant to be kept in git.
+ *
+ * `dis --stable game.bin` prints every region of the ROM with no addresses
+ * and fixed-width columns, and names jump and call targets after what's
+ * there rather than where it is or the order it was found in:
+ *
+ * ```text
+ * sub_3f09a1:
+ *           push  r0
+ *           set   r0 r1
+ *           call  sub_8c51e0
+ *           ret
+ * # "You see nothing special."
+ * ```
+ *
+ * A label is `sub_` (something calls it) or `loc_` (only jumped to, which
+ * includes the `push x; ret` jumps of `disasm::indirect_jump`) and the
+ * first six hex digits of the md5 of the instructions starting there, with
+ * the targets of any jumps among them left out.  Patching a ROM - moving
+ * code, adding a string - then only changes the lines that really changed,
+ * since nothing else is renamed or renumbered.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+
+use crate::disasm::{self, Instruction};
+use crate::map::{self, Kind};
+use crate::strings;
+
+// instructions hashed for a label, unless the block ends sooner
+const LABEL_INSTRUCTIONS: usize = 8;
+
+// data words per line
+const DATA_WORDS: usize = 8;
+
+// the operand of an instruction that's a jump or call target, including the
+// address an indirect jump pushes
+fn target_operand(words: &[u16], ins: &Instruction) -> Option<usize> {
+    match ins.opcode {
+        6 | 17 => Some(0),
+        7 | 8 => Some(1),
+        _ => disasm::indirect_jump(words, ins.addr)
+            .map(|_| ins.operands.len() - 1),
+    }
+}
+
+// the literal address an instruction jumps or calls to, if it has one
+fn target(words: &[u16], ins: &Instruction) -> Option<u16> {
+    let word = ins.operands[target_operand(words, ins)?];
+    (word < 32768).then_some(word)
+}
+
+// hash of the instructions at addr, up to the end of the block
+fn block_hash(words: &[u16], addr: u16) -> String {
+    let mut data = vec![];
+    let mut addr = addr;
+    for _ in 0..LABEL_INSTRUCTIONS {
+        let Some(ins) = disasm::decode(words, addr) else { break };
+        data.extend(ins.opcode.to_le_bytes());
+        for (i, word) in ins.operands.iter().enumerate() {
+            let word = match target_operand(words, &ins) {
+                Some(t) if t == i && *word < 32768 => 0,
+                _ => *word,
+            };
+            data.extend(word.to_le_bytes());
+        }
+        if matches!(ins.opcode, 0 | 6 | 18) {
+            break;
+        }
+        addr += ins.len() as u16;
+    }
+    format!("{:x}", md5::compute(&data))[..6].to_string()
+}
+
+// names for every jump and call target that lands in code.  identical blocks
+// get _2, _3... in address order
+fn labels(words: &[u16], code: &[Instruction]) -> HashMap<u16, String> {
+    let starts: BTreeSet<u16> = code.iter().map(|ins| ins.addr).collect();
+    let mut targets: BTreeMap<u16, bool> = BTreeMap::new();
+    for ins in code {
+        if let Some(addr) = target(words, ins)
+            && starts.contains(&addr)
+        {
+            *targets.entry(addr).or_default() |= ins.opcode == 17;
+        }
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut labels = HashMap::new();
+    for (addr, called) in targets {
+        let prefix = if called { "sub" } else { "loc" };
+        let mut name = format!("{}_{}", prefix, block_hash(words, addr));
+        let n = seen.entry(name.clone()).or_default();
+        *n += 1;
+        if *n > 1 {
+            write!(name, "_{}", n).unwrap();
+        }
+        labels.insert(addr, name);
+    }
+    labels
+}
+
+fn instruction(
+    words: &[u16],
+    ins: &Instruction,
+    labels: &HashMap<u16, String>,
+) -> String {
+    let mut line = format!("          {:<5}", ins.mnemonic());
+    for (i, word) in ins.operands.iter().enumerate() {
+        let label = match target_operand(words, ins) {
+            Some(t) if t == i => labels.get(word),
+            _ => None,
+        };
+        match label {
+            Some(label) => write!(line, " {}", label).unwrap(),
+            None => {
+                write!(line, " {}", disasm::operand(ins.opcode, *word)).unwrap()
+            }
+        }
+    }
+    line.trim_end().to_string()
+}
+
+// the whole ROM as a stable listing
+pub fn stable(ram: &[u8]) -> String {
+    let words = map::words(ram);
+    let kinds = map::classify(ram, None);
+    let regions = map::regions(&kinds);
+
+    let code: Vec<Instruction> = regions
+        .iter()
+        .filter(|r| r.kind == Kind::Code)
+        .flat_map(|r| disasm::disassemble(&words, r.start..r.end + 1))
+        .collect();
+    let labels = labels(&words, &code);
+
+    let mut out = String::new();
+    let mut code = code.iter().peekable();
+    for region in &regions {
+        match region.kind {
+            Kind::Code => {
+                while let Some(ins) = code.next_if(|ins| ins.addr <= region.end)
+                {
+                    if let Some(label) = labels.get(&ins.addr) {
+                        writeln!(out, "{}:", label).unwrap();
+                    }
+                    writeln!(out, "{}", instruction(&words, ins, &labels))
+                        .unwrap();
+                }
+            }
+            Kind::String => {
+                let mut addr = region.start;
+                while addr <= region.end {
+                    let s = strings::decode(&words, addr).unwrap_or_default();
+                    writeln!(out, "# {:?}", s).unwrap();
+                    addr += words[addr as usize] + 1;
+                }
+            }
+            Kind::Data => {
+                let data = &words[region.start as usize..=region.end as usize];
+                for chunk in data.chunks(DATA_WORDS) {
+                    let chunk: Vec<_> =
+                        chunk.iter().map(|w| format!("{:>5}", w)).collect();
+                    writeln!(out, "# data {}", chunk.join(" ")).unwrap();
+                }
+            }
+            Kind::Free => {
+                writeln!(out, "# free ({} words)", region.size()).unwrap()
+            }
+        }
+    }
+    out
+}
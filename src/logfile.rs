@@ -0,0 +1,80 @@
+/*!
+ * A log file that rotates itself by size.
+ *
+ * Logs normally go to stderr along with the game's text.  With `--log-file`
+ * they go here instead, and once the file would grow past its limit it is
+ * moved aside (`vm.log` becomes `vm.log.1`, `vm.log.1` becomes `vm.log.2` and
+ * so on) and a fresh one started, keeping the last few around.
+ */
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+
+// default size a log file can reach before it is rotated
+pub const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+// old log files to keep around after rotating
+pub const KEEP: usize = 3;
+
+pub struct LogFile {
+    path: String,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    // append to the file, creating it if need be
+    pub fn open(
+        path: &str,
+        max_size: u64,
+        keep: usize,
+    ) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("failed to open {}: {}", path, e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        Ok(Self { path: path.to_string(), max_size, keep, file, size })
+    }
+
+    // shift the old files along, dropping the oldest, and start over
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = format!("{}.{}", self.path, n);
+                if fs::exists(&from)? {
+                    fs::rename(&from, format!("{}.{}", self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        }
+
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a single record bigger than the limit still goes in whole
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,117 @@
+/*!
+ * Logging, through `tracing`.
+ *
+ * Every `call` the VM makes opens a span that lasts until the matching `ret`,
+ * and each step runs inside the innermost one, so each log line shows the
+ * calls it happened in, and other `tracing-subscriber` layers see the same
+ * hierarchy.  The spans belong to the VM rather than the thread, so any
+ * number of VMs can run side by side (see `vm`).
+ *
+ * Logs go to stderr, or a rotating `--log-file`, as text or one JSON object
+ * per line.  RUST_LOG takes the usual filter directives; without it only
+ * errors are logged until `/loglevel` asks for more.
+ */
+
+use std::env;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+use tracing::level_filters::LevelFilter;
+use tracing::{Span, debug_span};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, reload};
+
+use crate::debugger::Frame;
+use crate::logfile::{self, LogFile};
+use crate::symbols::Symbols;
+use crate::vm;
+
+// where /loglevel changes the level
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+// install the global subscriber
+pub fn init(
+    file: Option<&str>,
+    max_size: u64,
+    json: bool,
+) -> Result<(), String> {
+    let rust_log = env::var("RUST_LOG").ok();
+
+    // with RUST_LOG set, it does the filtering and /loglevel can only lower
+    // the level below what it allows
+    let level = if rust_log.is_some() {
+        LevelFilter::TRACE
+    } else {
+        LevelFilter::ERROR
+    };
+    let (level, handle) = reload::Layer::new(level);
+    let env_filter = rust_log.map(EnvFilter::new);
+
+    let layer = fmt::layer().without_time().with_target(false);
+    let layer = match (file, json) {
+        (Some(file), json) => {
+            let log = LogFile::open(file, max_size, logfile::KEEP)?;
+            let layer = layer.with_ansi(false).with_writer(Mutex::new(log));
+            if json { layer.json().boxed() } else { layer.boxed() }
+        }
+        (None, true) => layer.json().with_writer(io::stderr).boxed(),
+        (None, false) => layer.with_writer(io::stderr).boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(level)
+        .with(env_filter)
+        .with(layer)
+        .try_init()
+        .map_err(|e| format!("failed to set up logging: {}", e))?;
+
+    LEVEL.set(handle).ok();
+    Ok(())
+}
+
+// the most verbose level being logged
+pub fn level() -> LevelFilter {
+    LevelFilter::current()
+}
+
+// does nothing if init wasn't called.  the level is the process's, shared
+// by every VM in it
+pub fn set_level(level: LevelFilter) {
+    if let Some(handle) = LEVEL.get() {
+        handle.modify(|l| *l = level).ok();
+    }
+}
+
+// a span per call frame, innermost last.  each is the child of the one
+// before, and only the innermost is entered, for the length of a step - VMs
+// sharing a thread (or moving between threads) never see each other's spans
+#[derive(Default)]
+pub struct CallSpans {
+    spans: Vec<Span>,
+}
+
+impl CallSpans {
+    // open or close spans to match the frames
+    pub fn sync(&mut self, frames: &[Frame], symbols: &Symbols) {
+        self.spans.truncate(frames.len());
+        while self.spans.len() < frames.len() {
+            let frame = &frames[self.spans.len()];
+            let parent = self.spans.last().and_then(|s| s.id());
+            let span = debug_span!(
+                parent: parent,
+                "call",
+                routine = %vm::routine_name(symbols, frame.routine)
+            );
+            self.spans.push(span);
+        }
+    }
+
+    // close every span, ie. before the frames are replaced wholesale
+    pub fn clear(&mut self) {
+        self.spans.clear();
+    }
+
+    // the span to run the next step in
+    pub fn current(&self) -> Span {
+        self.spans.last().cloned().unwrap_or_else(Span::none)
+    }
+}
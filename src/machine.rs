@@ -0,0 +1,45 @@
+/*!
+ * The interface between a virtual machine and everything built on top of it.
+ *
+ * The driver, speedrun mode and other tooling only need to be able to step a
+ * machine, feed it input and collect its output, so they're written against
+ * this trait rather than the Synacor VM directly.  Another architecture can
+ * reuse them by implementing it.
+ */
+
+use std::fmt;
+
+pub trait Machine {
+    // an address in the machine's memory
+    type Addr: Copy + PartialEq + fmt::Display;
+
+    // why an instruction couldn't run
+    type Error: fmt::Display;
+
+    // execute a single instruction
+    fn step(&mut self) -> Result<(), Self::Error>;
+
+    fn is_halted(&self) -> bool;
+
+    // true if the machine is blocked waiting for input it doesn't have
+    fn wants_input(&self) -> bool;
+
+    // address of the next instruction
+    fn pc(&self) -> Self::Addr;
+
+    // number of instructions executed
+    fn steps(&self) -> u64;
+
+    fn send_input(&mut self, input: &[u8]);
+
+    // everything printed since the last call
+    fn take_output(&mut self) -> Vec<u8>;
+
+    // step until the machine halts or needs input, or an instruction fails
+    fn run_until_input(&mut self) -> Result<(), Self::Error> {
+        while !self.is_halted() && !self.wants_input() {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
@@ -1,546 +1,758 @@
 /*!
- * My implementation of the 2012 Synacor VM Challenge.
+ * Run a ROM (or a saved VM state) interactively.
  *
  * Author: Dave Eddy <ysap@daveeddy.com>
  * Date: December 21, 2025
  * License: MIT
  */
 
-use log::{debug, info, trace};
-use serde::{Deserialize, Serialize};
-use std::env;
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use clap_complete::Shell;
+use std::collections::HashSet;
 use std::fs;
-use std::io::{self, Read, Write};
-
-#[derive(Default, Serialize, Deserialize)]
-struct VM {
-    ram: Vec<u8>,
-    registers: [u16; 8],
-    addr: u16, // addr pointer
-    stack: Vec<u16>,
-    running: bool,
-    level: usize,
-    pub input_buffer: Vec<u8>,
+use std::io;
+use std::time::Instant;
+
+use synacor_challenge::autosave::AUTOSAVE_EVERY;
+use synacor_challenge::books;
+use synacor_challenge::builder::VmBuilder;
+use synacor_challenge::codes::{self, Progress};
+use synacor_challenge::conformance;
+use synacor_challenge::deadcode;
+use synacor_challenge::history::History;
+use synacor_challenge::hooks;
+use synacor_challenge::keys::Keys;
+use synacor_challenge::logfile;
+use synacor_challenge::logging;
+use synacor_challenge::machine::Machine;
+use synacor_challenge::map;
+use synacor_challenge::offsets;
+use synacor_challenge::plugin;
+use synacor_challenge::rominfo;
+use synacor_challenge::sampler::{Every, Profile};
+use synacor_challenge::saves;
+use synacor_challenge::shutdown::Shutdown;
+use synacor_challenge::symbols::Symbols;
+use synacor_challenge::tracefilter::TraceFilter;
+use synacor_challenge::vm::VM;
+
+#[derive(Parser)]
+#[command(about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// ROM or saved VM state (.json, .snap or .state) to run
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    file: Option<String>,
+
+    /// File of game input to play before reading from the terminal, with
+    /// optional `@assert-output "text"` and `@assert-reg <r> <n>` checks
+    #[arg(value_hint = ValueHint::FilePath)]
+    script: Option<String>,
+
+    /// File of `addr input` rules - type the input whenever execution
+    /// reaches the address
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    hooks: Option<String>,
+
+    /// Filter the game's output, can be given more than once:
+    /// strip-ansi, upper, redact-codes, s/regex/replacement/, tee:<file>
+    #[arg(long = "filter", value_name = "SPEC")]
+    filters: Vec<String>,
+
+    /// File to record found codes in, defaults to <file>.progress
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    progress: Option<String>,
+
+    /// File to keep `/` command history in, defaults to $SYNACOR_HISTORY or
+    /// ~/.synacor_history
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    history: Option<String>,
+
+    /// File of line editor key bindings (see `keys`), defaults to
+    /// $SYNACOR_KEYS or ~/.synacor_keys
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    keys: Option<String>,
+
+    /// File of `name addr` symbols, defaults to <file>.sym if there is one
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    symbols: Option<String>,
+
+    /// Write logs to this file instead of stderr, where they mix with the
+    /// game's text
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    log_file: Option<String>,
+
+    /// Size in bytes the log file can reach before it is rotated
+    #[arg(long, value_name = "BYTES", default_value_t = logfile::MAX_SIZE)]
+    log_max_size: u64,
+
+    /// Log one JSON object per line instead of text
+    #[arg(long)]
+    log_json: bool,
+
+    /// Only log instructions matching this expression, ie.
+    /// "opcode == wmem && a >= 6000"
+    #[arg(long, value_name = "EXPR")]
+    trace_filter: Option<String>,
+
+    /// Write call/ret events to this file in the Chrome trace event format
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    chrome_trace: Option<String>,
+
+    /// Count the call stack every so often and write the counts to this
+    /// file, a profile without the cost of a full trace
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    sample: Option<String>,
+
+    /// How often --sample looks: a number of instructions, or a time like
+    /// "2ms" [default: 10000]
+    #[arg(long, value_name = "N")]
+    sample_every: Option<String>,
+
+    /// Warn when a call returns with more or fewer values on the stack than
+    /// it was made with
+    #[arg(long)]
+    stack_check: bool,
+
+    /// Run frequently executed straight-line code from pre-decoded
+    /// superblocks (only while no breakpoints, hooks or tracing are active)
+    #[arg(long)]
+    fast: bool,
+
+    /// Check every arithmetic result against the spec, worked out the slow
+    /// way, and panic if they differ
+    #[arg(long)]
+    audit: bool,
+
+    /// Print the characters a ROM writes to this address (with wmem) as
+    /// debug messages, a line at a time, ie. 32767 for a homebrew ROM to
+    /// log to
+    #[arg(long, value_name = "ADDR",
+          value_parser = clap::value_parser!(u16).range(..32768))]
+    debug_port: Option<u16>,
+
+    /// Give the stack room for only N values, for testing how a homebrew
+    /// ROM copes with running out.  A push or call that doesn't fit is a
+    /// fault
+    #[arg(long, value_name = "N")]
+    stack_limit: Option<usize>,
+
+    /// With --stack-limit, skip a push or call that doesn't fit and write 1
+    /// to this address instead, for the ROM to check (and clear)
+    #[arg(long, value_name = "ADDR", requires = "stack_limit",
+          value_parser = clap::value_parser!(u16).range(..32768))]
+    stack_flag: Option<u16>,
+
+    /// Show the number of instructions run so far in the prompt
+    #[arg(long)]
+    show_steps: bool,
+
+    /// Save the game to this file (replacing it) and exit on SIGTERM, SIGHUP
+    /// or ctrl-c, ie. when hosting the game
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    checkpoint: Option<String>,
+
+    /// Save the game to autosave-1.snap, autosave-2.snap... in this
+    /// directory every so often, writing over the oldest once there are
+    /// five, to /load after a bad input
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    autosave: Option<String>,
+
+    /// With --autosave, instructions between autosaves
+    #[arg(long, value_name = "N", requires = "autosave",
+          default_value_t = AUTOSAVE_EVERY,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    autosave_every: u64,
+
+    /// Keep what the last N instructions changed, however they were run, so
+    /// /rewind can take them back.  Runs without --fast's superblocks
+    #[arg(long, value_name = "N",
+          value_parser = clap::value_parser!(u64).range(1..))]
+    rewind: Option<u64>,
+
+    /// Run at most this many instructions a second, ie. when hosting the
+    /// game for several players on one machine
+    #[arg(long, value_name = "N")]
+    max_ips: Option<u64>,
+
+    /// Take at most this many lines of input a minute
+    #[arg(long, value_name = "N")]
+    max_inputs: Option<u64>,
+
+    /// Explain each instruction in plain English as it runs (see also
+    /// /explain and /step at the debug prompt)
+    #[arg(long)]
+    explain: bool,
+
+    /// Record the game input and `/` commands to this file, as a script
+    /// that plays the session back (see /record)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    record: Option<String>,
+
+    /// With --record, replace the file if it already exists
+    #[arg(long, requires = "record")]
+    force_record: bool,
+
+    /// Keep this file up to date with whether the game is thinking, waiting
+    /// for input or halted, as a line of JSON (see /status)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    status_file: Option<String>,
+
+    /// Run this with `sh -c` when the game stops thinking after a long
+    /// computation, with $SYNACOR_STATUS, $SYNACOR_SECONDS and $SYNACOR_STEPS
+    /// set
+    #[arg(long, value_name = "CMD", value_hint = ValueHint::CommandString)]
+    notify: Option<String>,
+
+    /// Turn off the `/` commands that change the VM's memory, registers or
+    /// program counter (including undoing, rewinding and plugins'
+    /// commands), and refuse to write over the file being run
+    #[arg(long)]
+    read_only: bool,
+
+    /// Load a built in plugin by name (see /plugin).  Can be given more than
+    /// once
+    #[arg(long = "plugin", value_name = "NAME",
+          value_parser = plugin_names())]
+    plugins: Vec<String>,
+
+    /// Run (without reading the terminal) until execution reaches this
+    /// address or symbol, or with "return" until the current call returns.
+    /// Can be given more than once
+    #[arg(long, value_name = "ADDR")]
+    run_to: Vec<String>,
+
+    /// A `/` command to run (without the slash) once the --run-to targets
+    /// are reached, ie. "export pre-teleporter.json".  Can be given more
+    /// than once, and exits afterwards instead of going interactive
+    #[arg(long, value_name = "COMMAND")]
+    then: Vec<String>,
+
+    /// Play the script as fast as possible and report splits per code
+    #[arg(long)]
+    speedrun: bool,
 }
 
-enum ValueType {
-    Register(u16),
-    Literal(u16),
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize the memory layout of a ROM or saved VM state
+    Map {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// Print the regions as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Executed addresses (as written by /coverage) to count as code
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        coverage: Option<String>,
+    },
+    /// List code that never runs and register stores that are never read
+    DeadCode {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// Executed addresses (as written by /coverage), to also list
+        /// conditional jumps that only ever went one way
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        coverage: Option<String>,
+    },
+    /// List the saved VM states in a directory with where each was made
+    Saves {
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: String,
+    },
+    /// Challenge code helpers
+    Codes {
+        #[command(subcommand)]
+        command: CodesCommand,
+    },
+    /// Write the long texts in a ROM or saved VM state (the strange book
+    /// and such) to Markdown files
+    Books {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// Where to write them
+        #[arg(long, default_value = "books", value_hint = ValueHint::DirPath)]
+        dir: String,
+    },
+    /// Print a --sample profile as folded stacks, for flamegraph.pl or
+    /// inferno-flamegraph
+    Fold {
+        #[arg(value_hint = ValueHint::FilePath)]
+        profile: String,
+
+        /// The ROM or saved state the profile is of, to name routines from
+        /// its symbols file and the offsets database
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        rom: Option<String>,
+    },
+    /// Check another Synacor VM against this one
+    Conformance {
+        #[command(subcommand)]
+        command: ConformanceCommand,
+    },
+    /// Print shell completions for the options and subcommands, ie.
+    /// `synacor-challenge completions bash > /etc/bash_completion.d/synacor`
+    Completions { shell: Shell },
 }
 
-#[allow(dead_code)]
-impl VM {
-    fn new(rom: Vec<u8>) -> Self {
-        Self { ram: rom, running: true, ..Default::default() }
-    }
+// the built in plugins, to complete --plugin with
+fn plugin_names() -> clap::builder::PossibleValuesParser {
+    clap::builder::PossibleValuesParser::new(
+        plugin::REGISTRY.iter().map(|(name, _)| *name),
+    )
+}
 
-    fn is_halted(&self) -> bool {
-        !self.running
-    }
+#[derive(Subcommand)]
+enum CodesCommand {
+    /// Check a code looks plausible and print its md5
+    Verify { code: String },
+}
 
-    fn push_stack(&mut self, value: u16) {
-        trace!("pushing {} onto the stack", value);
-        self.stack.push(value);
-    }
+#[derive(Subcommand)]
+enum ConformanceCommand {
+    /// Run a test ROM for each instruction on another VM and compare its
+    /// output to this one's
+    Verify {
+        /// Command to run the other VM, given the ROM file as its last
+        /// argument or in place of `{}`
+        #[arg(long)]
+        other: String,
+
+        /// Where to write the test ROMs
+        #[arg(long, default_value = "conformance", value_hint = ValueHint::DirPath)]
+        dir: String,
+
+        /// Read the other VM's stderr instead of its stdout
+        #[arg(long)]
+        stderr: bool,
+    },
+}
 
-    fn pop_stack(&mut self) -> u16 {
-        self.stack.pop().expect("stack was empty")
+fn verify_code(code: &str) {
+    let problems = codes::problems(code);
+    if problems.is_empty() {
+        println!("{} looks plausible", code);
+    } else {
+        for problem in &problems {
+            println!("{} {}", code, problem);
+        }
     }
+    println!("md5 {}", codes::md5_hex(code));
 
-    fn dump_state(&self) {
-        println!("loaded rom of size {}", self.ram.len());
-        for (i, register) in self.registers.iter().enumerate() {
-            println!("register {}: {}", i, register);
-        }
-        for (i, value) in self.stack.iter().enumerate() {
-            println!("stack {}: {}", i, value);
-        }
-        println!("running={}, addr={}", self.running, self.addr);
+    if !problems.is_empty() {
+        std::process::exit(1);
     }
+}
 
-    fn get_ram(&self, addr: u16) -> u16 {
-        let ptr = (addr * 2) as usize;
-        let low = self.ram[ptr] as u16;
-        let high = self.ram[ptr + 1] as u16;
+// the books in a ROM or state, using its symbols file and the offsets
+// database to find any the search for long strings misses
+fn extract_books(file: &str, dir: &str) -> Result<(), String> {
+    let vm = saves::load(file)?;
 
-        let num = (high << 8) + low;
-        trace!(
-            "self.get_ram: ptr={} (low={} high={}) num={}",
-            ptr, low, high, num
-        );
+    let sym = format!("{}.sym", file);
+    let mut symbols = match fs::exists(&sym).unwrap_or(false) {
+        true => Symbols::load(&sym)?,
+        false => Symbols::default(),
+    };
+    let info = rominfo::identify(vm.image());
+    offsets::apply(&offsets::Database::load()?, &info, &mut symbols);
 
-        num
-    }
+    books::extract(&vm.ram().words(), &symbols, file, dir)
+}
 
-    // get the raw number from the rom
-    fn get_ram_value(&self, addr: u16) -> ValueType {
-        let num = self.get_ram(addr);
+// the profile as folded stacks, with names from the ROM's symbols
+fn fold_profile(profile: &str, rom: Option<&str>) -> Result<(), String> {
+    let profile = Profile::load(profile)?;
 
-        if num < 32768 {
-            // it's a literal value
-            ValueType::Literal(num)
-        } else if num < 32776 {
-            // it's a register
-            ValueType::Register(num % 32768)
-        } else {
-            // it's invalid
-            panic!("get_value found invalid number at addr {}: {}", addr, num);
+    let mut symbols = Symbols::default();
+    if let Some(file) = rom {
+        let sym = format!("{}.sym", file);
+        if fs::exists(&sym).unwrap_or(false) {
+            symbols = Symbols::load(&sym)?;
         }
+        let info = rominfo::identify(saves::load(file)?.image());
+        offsets::apply(&offsets::Database::load()?, &info, &mut symbols);
     }
 
-    // get the register at the address - fails if not a register
-    fn get_register(&self, addr: u16) -> u16 {
-        match self.get_ram_value(addr) {
-            ValueType::Register(n) => n,
-            ValueType::Literal(_) => panic!(),
-        }
-    }
+    print!("{}", profile.folded(&symbols));
+    Ok(())
+}
 
-    // get the value at the address - either grabbing the literal value or
-    // traversing into the register itself and using that value
-    fn get_value(&self, addr: u16) -> u16 {
-        match self.get_ram_value(addr) {
-            ValueType::Register(r) => {
-                info!(
-                    "(addr={}) register {} read: {}",
-                    addr, r, self.registers[r as usize]
-                );
-                self.registers[r as usize]
-            }
-            ValueType::Literal(n) => n,
-        }
-    }
+fn load_vm(file: &str) -> VM {
+    saves::load(file).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-    // set a register to a value
-    fn set_register(&mut self, register: u16, value: u16) {
-        //info!("register {} write: {}", register, value);
-        self.registers[register as usize] = value;
-    }
+// addresses from a file written by /coverage
+fn load_coverage(file: &str) -> Result<HashSet<u16>, String> {
+    let data = fs::read_to_string(file)
+        .map_err(|e| format!("failed to read {}: {}", file, e))?;
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.trim().parse().map_err(|_| {
+                format!("{}: line {}: not an address: {}", file, i + 1, line)
+            })
+        })
+        .collect()
+}
 
-    // jump to an ADDRESS
-    fn jump(&mut self, addr: u16) {
-        trace!("self.jump: jumping to addr {}", addr);
-        self.addr = addr;
-    }
+fn print_map(
+    file: &str,
+    json: bool,
+    coverage: Option<&str>,
+) -> Result<(), String> {
+    let vm = load_vm(file);
+
+    let executed: HashSet<u16> = match coverage {
+        Some(f) => load_coverage(f)?,
+        None => (0..32768).filter(|addr| vm.executed(*addr)).collect(),
+    };
+    let is_executed = |addr: u16| executed.contains(&addr);
 
-    fn log_assembly(&self, op: &str) {
-        let w = self.level;
-        debug!("{} {:<w$} {}", " ", self.addr, op);
+    let kinds = map::classify(&vm.ram().to_vec(), Some(&is_executed));
+    let regions = map::regions(&kinds);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&regions).unwrap());
+        return Ok(());
     }
 
-    fn step(&mut self) {
-        assert!(self.running, "tried to step while halted");
+    for region in &regions {
+        println!(
+            "{:>5} - {:>5}  {:<6}  {} words",
+            region.start,
+            region.end,
+            format!("{:?}", region.kind).to_lowercase(),
+            region.size()
+        );
+    }
 
-        // grab the instruction to process
-        let instruction = self.get_value(self.addr);
+    println!();
+    for kind in
+        [map::Kind::Code, map::Kind::String, map::Kind::Data, map::Kind::Free]
+    {
+        let total: usize =
+            regions.iter().filter(|r| r.kind == kind).map(|r| r.size()).sum();
+        println!(
+            "{:<6}  {:>5} words  {:>5.1}%",
+            format!("{:?}", kind).to_lowercase(),
+            total,
+            total as f64 * 100.0 / kinds.len().max(1) as f64
+        );
+    }
+    Ok(())
+}
 
-        match instruction {
-            0 => {
-                // halt
-                // stop execution and terminate the program
-                self.log_assembly("halt");
+fn print_dead_code(file: &str, coverage: Option<&str>) -> Result<(), String> {
+    let vm = load_vm(file);
 
-                self.running = false;
-            }
-            1 => {
-                // set: 1 a b
-                // set register <a> to the value of <b>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
+    let executed = coverage.map(load_coverage).transpose()?;
+    let is_executed = |addr: u16| executed.as_ref().unwrap().contains(&addr);
+    let executed: Option<&dyn Fn(u16) -> bool> =
+        executed.as_ref().map(|_| &is_executed as _);
 
-                self.log_assembly(&format!("set <{}> = {}", a, b));
+    let found = deadcode::analyze(&vm.ram().to_vec(), executed);
+    for finding in &found {
+        println!("{}", finding);
+    }
+    println!("{} found", found.len());
+    Ok(())
+}
 
-                self.set_register(a, b);
+// run the VM against its input buffer as fast as possible, with no terminal
+// input at all, and report how long it took to get to each code
+fn speedrun<M: Machine>(vm: &mut M) {
+    let start = Instant::now();
+    let first_step = vm.steps();
+    let mut line = String::new();
+    let mut splits = vec![];
+    let mut fault = None;
+
+    while !vm.is_halted() && !vm.wants_input() {
+        if let Err(e) = vm.step() {
+            fault = Some(e);
+            break;
+        }
 
-                self.addr += 3;
+        for c in vm.take_output() {
+            eprint!("{}", c as char);
+            line.push(c as char);
+            if c == b'\n' {
+                for code in codes::find_codes(&line) {
+                    splits.push((
+                        code,
+                        start.elapsed(),
+                        vm.steps() - first_step,
+                    ));
+                }
+                line.clear();
             }
-            2 => {
-                // push: 2 a
-                // push <a> onto the stack
-                let a = self.get_value(self.addr + 1);
-                self.log_assembly(&format!("push {}", a));
+        }
+    }
 
-                self.push_stack(a);
+    let elapsed = start.elapsed();
+    let steps = vm.steps() - first_step;
 
-                self.addr += 2;
-            }
-            3 => {
-                // pop: 3 a
-                // remove the top element from the stack and write it into <a>;
-                // empty stack = error
-                let a = self.get_register(self.addr + 1);
-                let elem = self.pop_stack();
+    println!();
+    match &fault {
+        Some(e) => println!("speedrun faulted: {}", e),
+        None if vm.is_halted() => println!("speedrun halted"),
+        None => println!("speedrun done"),
+    }
+    for (i, (code, time, steps)) in splits.iter().enumerate() {
+        println!(
+            "  code {:<2} {}  {:>10.3}s  {:>12} instructions",
+            i + 1,
+            code,
+            time.as_secs_f64(),
+            steps
+        );
+    }
+    println!(
+        "  {:<20}  {:>10.3}s  {:>12} instructions",
+        "total",
+        elapsed.as_secs_f64(),
+        steps
+    );
+}
 
-                self.log_assembly(&format!(
-                    "pop writing {} into <{}>",
-                    elem, a
-                ));
+fn main() {
+    let cli = Cli::parse();
 
-                self.set_register(a, elem);
+    let log_file = cli.log_file.as_deref();
+    if let Err(e) = logging::init(log_file, cli.log_max_size, cli.log_json) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
-                self.addr += 2;
-            }
-            4 => {
-                // eq: 4 a b c
-                // set <a> to 1 if <b> is equal to <c>; set it to 0 otherwise
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("eq ({} == {})", b, c));
-
-                if b == c {
-                    self.set_register(a, 1);
-                } else {
-                    self.set_register(a, 0);
+    if let Some(command) = cli.command {
+        match command {
+            Command::Map { file, json, coverage } => {
+                if let Err(e) = print_map(&file, json, coverage.as_deref()) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
-
-                self.addr += 4;
             }
-            5 => {
-                // gt: 5 a b c
-                // set <a> to 1 if <b> is greater than <c>; set it to 0 otherwise
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("gt ({} > {})", b, c));
-
-                if b > c {
-                    self.set_register(a, 1);
-                } else {
-                    self.set_register(a, 0);
+            Command::DeadCode { file, coverage } => {
+                if let Err(e) = print_dead_code(&file, coverage.as_deref()) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
-
-                self.addr += 4;
             }
-            6 => {
-                // jmp: 6 a
-                // jump to <a>
-                let a = self.get_value(self.addr + 1);
-                self.log_assembly(&format!("jmp <{}>", a));
-
-                self.jump(a);
-            }
-            7 => {
-                // jt: 7 a b
-                // if <a> is nonzero, jump to <b>
-                let a = self.get_value(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-
-                trace!("jt: a={}, b={}", a, b);
-                self.log_assembly(&format!("jt ({} != 0 -> {})", a, b));
-
-                if a != 0 {
-                    trace!("jt jumped to {}", b);
-                    self.jump(b);
-                } else {
-                    trace!("jt didn't jump");
-                    self.addr += 3;
+            Command::Saves { dir } => {
+                if let Err(e) = saves::print_list(&dir) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
             }
-            8 => {
-                // jf: 8 a b
-                // if <a> is zero, jump to <b>
-                let a = self.get_value(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-
-                trace!("jf: a={}, b={}", a, b);
-                self.log_assembly(&format!("jf ({} == 0 -> {})", a, b));
-
-                if a == 0 {
-                    trace!("jf jumped to {}", b);
-                    self.jump(b);
-                } else {
-                    trace!("jf didn't jump");
-                    self.addr += 3;
+            Command::Books { file, dir } => {
+                if let Err(e) = extract_books(&file, &dir) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
             }
-            9 => {
-                // add: 9 a b c
-                // assign into <a> the sum of <b> and <c> (modulo 32768)
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("add <{}> = {} + {}", a, b, c));
-
-                let sum = (b + c) % 32768;
-                self.set_register(a, sum);
-
-                self.addr += 4;
-            }
-            10 => {
-                // mult: 10 a b c
-                // store into <a> the product of <b> and <c> (modulo 32768)
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("mult <{}> = {} * {}", a, b, c));
-
-                let sum = (b as u32 * c as u32) % 32768;
-                self.set_register(a, sum as u16);
-
-                self.addr += 4;
+            Command::Fold { profile, rom } => {
+                if let Err(e) = fold_profile(&profile, rom.as_deref()) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
             }
-            11 => {
-                // mod: 11 a b c
-                // store into <a> the remainder of <b> divided by <c>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("mod <{}> = {} % {}", a, b, c));
-
-                let sum = (b % c) % 32768;
-                self.set_register(a, sum);
-
-                self.addr += 4;
+            Command::Completions { shell } => {
+                let mut cmd = Cli::command();
+                let name = cmd.get_name().to_string();
+                clap_complete::generate(
+                    shell,
+                    &mut cmd,
+                    name,
+                    &mut io::stdout(),
+                );
             }
-            12 => {
-                // and: 12 a b c
-                // stores into <a> the bitwise and of <b> and <c>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("and <{}> = {} & {}", a, b, c));
-
-                let sum = (b & c) % 32768;
-                self.set_register(a, sum);
-
-                self.addr += 4;
+            Command::Codes { command: CodesCommand::Verify { code } } => {
+                verify_code(&code)
             }
-            13 => {
-                // or: 13 a b c
-                // stores into <a> the bitwise or of <b> and <c>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
-                let c = self.get_value(self.addr + 3);
-
-                self.log_assembly(&format!("or <{}> = {} | {}", a, b, c));
+            Command::Conformance {
+                command: ConformanceCommand::Verify { other, dir, stderr },
+            } => match conformance::verify(&other, &dir, stderr) {
+                Ok(true) => (),
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+        }
+        return;
+    }
 
-                let sum = (b | c) % 32768;
-                self.set_register(a, sum);
+    let file = cli.file.clone().unwrap();
+    if let Err(e) = run(&file, &cli) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-                self.addr += 4;
-            }
-            14 => {
-                // not: 14 a b
-                // stores 15-bit bitwise inverse of <b> in <a>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
+fn run(file: &str, cli: &Cli) -> Result<(), String> {
+    let vm = saves::load(file)?;
 
-                self.log_assembly(&format!("not <{}> = ~{}", a, b));
+    let symbols_file = cli.symbols.clone().or_else(|| {
+        let f = format!("{}.sym", file);
+        fs::exists(&f).unwrap_or(false).then_some(f)
+    });
+    let mut symbols = match &symbols_file {
+        Some(f) => Symbols::load(f)?,
+        None => Symbols::default(),
+    };
 
-                let b = !b % 32768;
-                self.set_register(a, b);
+    // say what's being run before anything else
+    let info = rominfo::identify(vm.image());
+    let db = offsets::Database::load()?;
+    if !cli.speedrun {
+        println!("{}", info);
+        println!("{}", rominfo::describe_offsets(&db, &info));
+        println!("{}", rominfo::describe_symbols(&symbols, &info));
+    }
+    offsets::apply(&db, &info, &mut symbols);
 
-                self.addr += 3;
-            }
-            15 => {
-                // rmem: 15 a b
-                // read memory at address <b> and write it to <a>
-                let a = self.get_register(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
+    let mut builder = VmBuilder::new().state(vm).symbols(symbols);
 
-                let num = self.get_ram(b);
+    // command file given as arg2
+    if let Some(f) = &cli.script {
+        let input = fs::read(f).map_err(|e| format!("{}: {}", f, e))?;
+        builder = builder.script(input);
+    }
 
-                self.log_assembly(&format!("rmem <{}> = {}", a, num));
+    if let Some(f) = &cli.hooks {
+        let hooks = hooks::load(f).map_err(|e| format!("{}: {}", f, e))?;
+        builder = builder.hooks(hooks);
+    }
 
-                self.set_register(a, num);
+    if let Some(expr) = &cli.trace_filter {
+        let filter = TraceFilter::parse(expr)
+            .map_err(|e| format!("invalid trace filter: {}", e))?;
+        builder = builder.trace_filter(filter);
+    }
 
-                self.addr += 3;
-            }
-            16 => {
-                // wmem: 16 a b
-                // write the value from <b> into memory at address <a>
-                let a = self.get_value(self.addr + 1);
-                let b = self.get_value(self.addr + 2);
+    if let Some(f) = &cli.chrome_trace {
+        builder = builder.trace(f);
+    }
 
-                // this is how we made a big number
-                // (high << 8) + low
+    if let Some(f) = &cli.sample {
+        let every = match &cli.sample_every {
+            Some(every) => Every::parse(every)?,
+            None => Every::default(),
+        };
+        builder = builder.sample(f, every);
+    }
 
-                let high = b >> 8;
-                let low = b % 256;
+    builder =
+        builder.stack_check(cli.stack_check).fast(cli.fast).audit(cli.audit);
 
-                trace!("setting value {} into ram memory addr {}", b, a);
-                trace!("self.ram[({} * 2)] = {}", a, high);
-                trace!("self.ram[({} * 2) + 1] = {}", a, low);
+    if let Some(addr) = cli.debug_port {
+        builder = builder.debug_port(addr);
+    }
 
-                self.log_assembly(&format!("wmem {} = {}", a, b));
+    if let Some(limit) = cli.stack_limit {
+        builder = builder.stack_limit(limit, cli.stack_flag);
+    }
 
-                self.ram[(a * 2) as usize] = low as u8;
-                self.ram[(a * 2) as usize + 1] = high as u8;
+    if cli.speedrun {
+        let mut vm = builder.build()?;
+        speedrun(&mut vm);
+        return Ok(());
+    }
 
-                self.addr += 3;
-            }
-            17 => {
-                // call: 17 a
-                // write the address of the next instruction to the stack and
-                // jump to <a>
-
-                let a = self.get_value(self.addr + 1);
-
-                self.log_assembly(&format!("call {}", a));
-
-                if a == 6049 {
-                    // LOL - game genie
-                    /*
-                    self.registers[0] = 6;
-                    self.addr += 2;
-                    return;
-                    */
-                }
+    let progress_file = match &cli.progress {
+        Some(f) => f.clone(),
+        None => format!("{}.progress", file),
+    };
+    builder = builder.progress(Progress::load(&progress_file)?);
 
-                self.push_stack(self.addr + 2);
+    for spec in &cli.filters {
+        builder = builder.filter(spec);
+    }
 
-                self.level += 1;
-                self.jump(a);
-            }
-            18 => {
-                // ret: 18
-                // remove the top element from the stack and jump to it; empty
-                // stack = halt
-                let addr = self.pop_stack();
-                self.log_assembly(&format!("ret ({})", addr));
-                self.level -= 1;
-                self.jump(addr);
-            }
-            19 => {
-                // out: 19 a
-                // write the character represented by ascii code <a> to the
-                // terminal
-                self.log_assembly("out");
+    if let Some(f) = cli.history.clone().or_else(History::default_file) {
+        builder = builder.history(History::load(&f)?);
+    }
 
-                let a = self.get_value(self.addr + 1);
-                eprint!("{}", a as u8 as char);
-                trace!("output: {}", a);
+    if let Some(f) = cli.keys.clone().or_else(Keys::default_file) {
+        builder = builder.keys(Keys::load(&f)?);
+    }
 
-                self.addr += 2;
-            }
-            20 => {
-                // in: 20 a
-                // read a character from the terminal and write its ascii
-                // code to <a>; it can be assumed that once input starts, it
-                // will continue until a newline is encountered; this means
-                // that you can safely read whole lines from the keyboard
-                // instead of having to figure out how to read individual
-                // characters
-                self.log_assembly("in");
-
-                let a = self.get_register(self.addr + 1);
-
-                // read a single character - try from input buffer and fallback
-                // to stdin
-                let (c, color) = if !self.input_buffer.is_empty() {
-                    // input buffer
-                    (self.input_buffer.remove(0), 31)
-                } else {
-                    // stdin
-                    let mut buf: [u8; 1] = [0u8];
-                    io::stdin()
-                        .read_exact(&mut buf)
-                        .expect("failed to read 1 char");
-
-                    // allow user to send commands to the VM itself
-                    if buf[0] == b'/' {
-                        let mut cmd = String::new();
-                        io::stdin().read_line(&mut cmd).unwrap();
-                        let cmd = cmd.trim();
-
-                        self.process_internal_command(cmd);
-                        return;
-                    }
-
-                    (buf[0], 32)
-                };
-
-                eprint!("\x1b[{}m{}\x1b[0m", color, c as char);
-
-                self.set_register(a, c as u16);
-
-                self.addr += 2;
-            }
-            21 => {
-                // no-op
-                // no operation
-                self.log_assembly("no-op");
-                self.addr += 1;
-            }
-            n => {
-                // uh oh
-                self.dump_state();
-                panic!("unknown instruction: {}", n);
-            }
+    if cli.read_only {
+        if let Some(f) = &cli.checkpoint
+            && fs::canonicalize(f).ok() == fs::canonicalize(file).ok()
+        {
+            return Err(format!("--checkpoint {} would write over it", f));
         }
+        builder = builder.read_only(file);
     }
 
-    fn process_internal_command(&mut self, s: &str) {
-        trace!("internal command: {}", s);
+    if let Some(f) = &cli.record {
+        builder = builder.record(f, cli.force_record);
+    }
 
-        let cmd: Vec<_> = s.split_whitespace().collect();
+    if let Some(f) = &cli.status_file {
+        builder = builder.status_file(f);
+    }
 
-        match cmd[0] {
-            "dump" => self.dump_state(),
-            "set" => {
-                // set the register
-                let register: u16 = cmd[1].parse().unwrap();
-                let value: u16 = cmd[2].parse().unwrap();
-                println!("updating register {}: {}", register, value);
-                self.set_register(register, value);
-            }
-            "save" => {
-                let file = cmd[1];
-                if fs::exists(file).unwrap() {
-                    println!("file already exists, doing nothing");
-                    return;
-                }
-                fs::write(file, &self.ram).unwrap();
-                println!("file saved to {}", file);
-            }
-            "export" => {
-                let file = cmd[1];
-                if fs::exists(file).unwrap() {
-                    println!("file already exists, doing nothing");
-                    return;
-                }
-                let data = serde_json::to_string(&self).unwrap();
-                fs::write(file, &data).unwrap();
-                println!("file saved to {}", file);
-            }
-            cmd => panic!("unknown internal command: {}", cmd),
-        }
+    if let Some(cmd) = &cli.notify {
+        builder = builder.notify(cmd);
     }
-}
 
-fn main() {
-    env_logger::builder()
-        .format(|buf, record| writeln!(buf, "> {}", record.args()))
-        .init();
+    if let Some(f) = &cli.checkpoint {
+        builder = builder.shutdown(Shutdown::install(f)?);
+    }
 
-    let args: Vec<_> = env::args().skip(1).collect();
+    if let Some(dir) = &cli.autosave {
+        builder = builder.autosave(dir, cli.autosave_every);
+    }
 
-    let file = &args[0];
+    if let Some(n) = cli.rewind {
+        builder = builder.rewind(n as usize);
+    }
 
-    let mut vm = if file.ends_with(".json") {
-        let data = fs::read_to_string(file).unwrap();
-        serde_json::from_str(&data).unwrap()
-    } else {
-        let binary = fs::read(file).unwrap();
-        VM::new(binary)
-    };
+    if cli.max_ips == Some(0) || cli.max_inputs == Some(0) {
+        return Err(
+            "--max-ips and --max-inputs have to be at least 1".to_string()
+        );
+    }
+    if let Some(ips) = cli.max_ips {
+        builder = builder.max_ips(ips);
+    }
+    if let Some(n) = cli.max_inputs {
+        builder = builder.max_inputs(n);
+    }
 
-    // command file given as arg2
-    if let Some(f) = args.get(1) {
-        let input_buffer = fs::read(f).unwrap();
-        vm.input_buffer = input_buffer;
+    for name in &cli.plugins {
+        builder = builder.plugin(plugin::load(name)?);
     }
 
-    while !vm.is_halted() {
-        vm.step();
+    let mut shell =
+        builder.show_steps(cli.show_steps).explain(cli.explain).shell()?;
+    for target in &cli.run_to {
+        shell.run_to(target)?;
     }
+    if !cli.then.is_empty() {
+        for cmd in &cli.then {
+            shell.command(cmd.trim_start_matches('/'));
+        }
+        return Ok(());
+    }
+    shell.run()?;
 
-    println!("VM finished");
+    if shell.vm().is_halted() {
+        println!("VM finished");
+    }
+    Ok(())
 }
@@ -0,0 +1,165 @@
+/*!
+ * Summarize the layout of a ROM's address space.
+ *
+ * Every word is classified as code (reachable by following control flow from
+ * address 0, `push x; ret` jumps included, or seen executing in coverage
+ * data), a length-prefixed string,
+ * free space (long runs of zeros) or, failing all of those, data.
+ */
+
+use serde::Serialize;
+
+use crate::disasm;
+use crate::vm::OPERANDS;
+
+// runs of zero words at least this long are considered free space
+const MIN_FREE: usize = 8;
+
+// length-prefixed strings shorter than this are too likely to be noise
+const MIN_STRING: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Code,
+    String,
+    Data,
+    Free,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Region {
+    pub start: u16,
+    // inclusive
+    pub end: u16,
+    pub kind: Kind,
+}
+
+impl Region {
+    // number of words in the region
+    pub fn size(&self) -> usize {
+        (self.end - self.start) as usize + 1
+    }
+}
+
+// little endian byte pairs to words
+pub fn words(ram: &[u8]) -> Vec<u16> {
+    ram.chunks(2)
+        .map(|c| c[0] as u16 | (*c.get(1).unwrap_or(&0) as u16) << 8)
+        .collect()
+}
+
+// the length of the instruction at addr, if it decodes to something valid
+fn instruction_len(words: &[u16], addr: usize) -> Option<usize> {
+    let op = *words.get(addr)?;
+    let n = *OPERANDS.get(op as usize)? as usize;
+    let operands = words.get(addr + 1..addr + 1 + n)?;
+    if operands.iter().all(|w| *w < 32776) { Some(n + 1) } else { None }
+}
+
+// mark everything reachable from address 0 by following literal jump and
+// call targets
+fn mark_reachable(words: &[u16], kinds: &mut [Option<Kind>]) {
+    let mut todo = vec![0];
+
+    while let Some(addr) = todo.pop() {
+        if kinds.get(addr).is_none_or(|k| k.is_some()) {
+            continue;
+        }
+        let Some(len) = instruction_len(words, addr) else { continue };
+
+        kinds[addr..addr + len].fill(Some(Kind::Code));
+
+        // literal targets only - jumps through registers can't be followed
+        let target = |i: usize| match words[addr + i] {
+            n if n < 32768 => Some(n as usize),
+            _ => None,
+        };
+        let next = addr + len;
+        if let Some(target) = disasm::indirect_jump(words, addr as u16) {
+            todo.push(target as usize);
+        }
+        match words[addr] {
+            0 | 18 => {}
+            6 => todo.extend(target(1)),
+            7 | 8 => todo.extend([Some(next), target(2)].into_iter().flatten()),
+            17 => todo.extend([Some(next), target(1)].into_iter().flatten()),
+            _ => todo.push(next),
+        }
+    }
+}
+
+// classify every word of ram, given an optional predicate for addresses that
+// have been seen executing
+pub fn classify(
+    ram: &[u8],
+    executed: Option<&dyn Fn(u16) -> bool>,
+) -> Vec<Kind> {
+    let words = words(ram);
+    let mut kinds: Vec<Option<Kind>> = vec![None; words.len()];
+
+    mark_reachable(&words, &mut kinds);
+
+    if let Some(executed) = executed {
+        for addr in 0..words.len() {
+            if executed(addr as u16)
+                && let Some(len) = instruction_len(&words, addr)
+            {
+                kinds[addr..addr + len].fill(Some(Kind::Code));
+            }
+        }
+    }
+
+    // length-prefixed strings of printable characters
+    let printable = |w: &u16| (32..127).contains(w) || *w == 10;
+    let mut addr = 0;
+    while addr < words.len() {
+        let n = words[addr] as usize;
+        let end = addr + n;
+        if kinds[addr].is_none()
+            && n >= MIN_STRING
+            && end < words.len()
+            && words[addr + 1..=end].iter().all(printable)
+            && kinds[addr..=end].iter().all(|k| k.is_none())
+        {
+            kinds[addr..=end].fill(Some(Kind::String));
+            addr = end + 1;
+        } else {
+            addr += 1;
+        }
+    }
+
+    // long runs of zeros are free space, anything left is data
+    let mut addr = 0;
+    while addr < words.len() {
+        let run = words[addr..]
+            .iter()
+            .zip(&kinds[addr..])
+            .take_while(|(w, k)| **w == 0 && k.is_none())
+            .count();
+        if run >= MIN_FREE {
+            kinds[addr..addr + run].fill(Some(Kind::Free));
+        }
+        addr += run.max(1);
+    }
+
+    kinds.into_iter().map(|k| k.unwrap_or(Kind::Data)).collect()
+}
+
+// collapse a classification into contiguous regions
+pub fn regions(kinds: &[Kind]) -> Vec<Region> {
+    let mut regions: Vec<Region> = vec![];
+
+    for (addr, kind) in kinds.iter().enumerate() {
+        match regions.last_mut() {
+            Some(region) if region.kind == *kind => region.end = addr as u16,
+            _ => regions.push(Region {
+                start: addr as u16,
+                end: addr as u16,
+                kind: *kind,
+            }),
+        }
+    }
+
+    regions
+}
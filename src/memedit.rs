@@ -0,0 +1,139 @@
+/*!
+ * The `/edit-mem` memory editor.
+ *
+ * Shows a page of memory as words, with the ascii they'd print as and the
+ * symbols that fall on each row, and takes one command per line:
+ *
+ * ```text
+ * mem> 6049            go to an address (or a symbol name)
+ * mem> 6049 = 6 0x15   write words starting at an address
+ * mem> n / p           next / previous page (an empty line is next)
+ * mem> u               undo the last edit
+ * mem> q               back to the shell
+ * ```
+ *
+ * Values are decimal, `0x` hex, `'c'` for a character or `r0`-`r7` for a
+ * register operand.  Words that differ from the loaded image are shown in
+ * reverse video.
+ */
+
+use crate::symbols::Symbols;
+use crate::vm::VM;
+
+// words per row and rows per page
+pub const COLUMNS: u16 = 8;
+pub const ROWS: u16 = 16;
+
+pub enum Edit {
+    Next,
+    Prev,
+    Goto(u16),
+    Write(u16, Vec<u16>),
+    Undo,
+    Quit,
+}
+
+// an address, or the name of a symbol
+pub fn parse_addr(s: &str, symbols: &Symbols) -> Result<u16, String> {
+    if let Some(addr) = symbols.get(s) {
+        return Ok(addr);
+    }
+    match parse_word(s)? {
+        addr if addr < 32768 => Ok(addr),
+        addr => Err(format!("address {} out of range", addr)),
+    }
+}
+
+// a word of memory - a number, 'c' or a register
+pub fn parse_word(s: &str) -> Result<u16, String> {
+    let invalid = || format!("invalid value '{}'", s);
+
+    let n = if let Some(hex) = s.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).map_err(|_| invalid())?
+    } else if let Some(c) =
+        s.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+    {
+        let mut chars = c.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() => c as u16,
+            _ => return Err(invalid()),
+        }
+    } else if let Some(r) = s.strip_prefix('r') {
+        match r.parse::<u16>() {
+            Ok(r) if r < 8 => 32768 + r,
+            _ => return Err(invalid()),
+        }
+    } else {
+        s.parse().map_err(|_| invalid())?
+    };
+
+    if n > 32775 {
+        return Err(format!("value {} out of range", n));
+    }
+    Ok(n)
+}
+
+pub fn parse(line: &str, symbols: &Symbols) -> Result<Edit, String> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+
+    match fields[..] {
+        [] | ["n"] => Ok(Edit::Next),
+        ["p"] => Ok(Edit::Prev),
+        ["u"] => Ok(Edit::Undo),
+        ["q"] => Ok(Edit::Quit),
+        [addr] => Ok(Edit::Goto(parse_addr(addr, symbols)?)),
+        [addr, "=", ref values @ ..] if !values.is_empty() => {
+            let addr = parse_addr(addr, symbols)?;
+            let values = values
+                .iter()
+                .map(|v| parse_word(v))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Edit::Write(addr, values))
+        }
+        _ => {
+            Err("expected <addr>, <addr> = <value>..., n, p, u or q"
+                .to_string())
+        }
+    }
+}
+
+// a page of memory starting at start
+pub fn render(vm: &VM, start: u16) -> String {
+    let mut out = String::new();
+
+    for row in 0..ROWS {
+        let addr = start + row * COLUMNS;
+        if addr >= 32768 {
+            break;
+        }
+        let marker =
+            if (addr..addr + COLUMNS).contains(&vm.pc()) { "=>" } else { "  " };
+
+        let mut words = String::new();
+        let mut ascii = String::new();
+        let mut names = vec![];
+        for a in addr..addr + COLUMNS {
+            let w = vm.ram().word(a).unwrap_or(0);
+            if vm.modified(a) {
+                words += &format!(" \x1b[7m{:04x}\x1b[0m", w);
+            } else {
+                words += &format!(" {:04x}", w);
+            }
+            ascii.push(match w {
+                32..127 => w as u8 as char,
+                _ => '.',
+            });
+            if let Some(name) = vm.symbols.name_of(a) {
+                names.push(format!("{}@{}", name, a));
+            }
+        }
+
+        out += &format!("{} {:>5}:{}  {}", marker, addr, words, ascii);
+        if !names.is_empty() {
+            out += &format!("  <- {}", names.join(", "));
+        }
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,47 @@
+/*!
+ * Spot text the game hasn't printed before.
+ *
+ * Easter eggs and hints are mostly new text somewhere unexpected, so the
+ * explorers and `/novelty on` keep a hash of every line of output seen and
+ * point out the ones that are new.  Lines are compared trimmed, and blank
+ * lines are never new.
+ */
+
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+#[derive(Default)]
+pub struct Novelty {
+    seen: HashSet<u64>,
+}
+
+impl Novelty {
+    // true the first time a line is seen
+    pub fn is_new(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if line.is_empty() {
+            return false;
+        }
+        let mut h = DefaultHasher::new();
+        line.hash(&mut h);
+        self.seen.insert(h.finish())
+    }
+
+    // remember every line of text, true if any was new
+    pub fn add_text(&mut self, text: &str) -> bool {
+        let mut new = false;
+        for line in text.lines() {
+            new |= self.is_new(line);
+        }
+        new
+    }
+
+    // number of different lines seen
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
@@ -0,0 +1,229 @@
+/*!
+ * Known offsets, by ROM hash.
+ *
+ * Each ROM build has its routines and tables at its own addresses.  Once
+ * they've been worked out (and checked) for a build they go in the database
+ * under the md5 of the ROM, and every tool loading that ROM picks them up as
+ * symbols - `solve teleporter` finds `teleporter_check`, `/vault` finds
+ * `vault_grid` and so on, without a symbols file.
+ *
+ * Every download of challenge.bin from the website has its own codes in it,
+ * so its md5 is different for everyone, but the code is laid out the same.
+ * An entry can be for a layout instead, named by where `rominfo` found the
+ * teleporter check, and is used for a ROM without an entry of its own:
+ *
+ * ```text
+ * rom teleporter=6049 challenge.bin from the website
+ * teleporter_check 6049
+ * ```
+ *
+ * Only the teleporter check is built in, as it's the one address checked
+ * against the website build.  The string printer doesn't need an entry
+ * since `rominfo` finds it by its code, and the vault grid and room table
+ * aren't known for any build yet: once found they go in the user's own
+ * file, $SYNACOR_OFFSETS or ~/.synacor_offsets, which `/offsets save` adds
+ * to and which is read along with the entries built in here.
+ */
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::rominfo::RomInfo;
+use crate::symbols::Symbols;
+
+// offsets checked against real builds, in the same format as the user's
+// file.  see above for what isn't here
+const BUILTIN: &str = "\
+rom teleporter=6049 challenge.bin from the website
+teleporter_check 6049
+";
+
+pub struct Known {
+    pub description: String,
+    pub symbols: Symbols,
+}
+
+#[derive(Default)]
+pub struct Database {
+    roms: BTreeMap<String, Known>,
+}
+
+impl Database {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut roms = BTreeMap::new();
+        // the rom being read, its description and its lines so far
+        let mut current: Option<(String, String, String)> = None;
+
+        let mut finish =
+            |current: Option<(String, String, String)>| -> Result<(), String> {
+                if let Some((md5, description, lines)) = current {
+                    let symbols = Symbols::parse(&lines)
+                        .map_err(|e| format!("rom {}: {}", md5, e))?;
+                    roms.insert(md5, Known { description, symbols });
+                }
+                Ok(())
+            };
+
+        for (i, line) in s.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(rest) = trimmed.strip_prefix("rom ") {
+                finish(current.take())?;
+                let (md5, description) =
+                    rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+                current = Some((
+                    md5.to_lowercase(),
+                    description.trim().to_string(),
+                    String::new(),
+                ));
+            } else if let Some((_, _, lines)) = &mut current {
+                lines.push_str(line);
+                lines.push('\n');
+            } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return Err(format!(
+                    "line {}: expected `rom <md5> [description]` first",
+                    i + 1
+                ));
+            }
+        }
+        finish(current)?;
+
+        Ok(Self { roms })
+    }
+
+    // $SYNACOR_OFFSETS, or ~/.synacor_offsets
+    pub fn default_file() -> Option<String> {
+        if let Ok(file) = env::var("SYNACOR_OFFSETS") {
+            return Some(file);
+        }
+        env::var("HOME").ok().map(|home| format!("{}/.synacor_offsets", home))
+    }
+
+    // the built in entries and the user's file, the user's winning for a
+    // ROM in both
+    pub fn load() -> Result<Self, String> {
+        let mut db = Self::parse(BUILTIN).unwrap();
+
+        let Some(file) = Self::default_file() else {
+            return Ok(db);
+        };
+        let data = match fs::read_to_string(&file) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(db),
+            Err(e) => return Err(format!("failed to read {}: {}", file, e)),
+        };
+        let user =
+            Self::parse(&data).map_err(|e| format!("{}: {}", file, e))?;
+        db.roms.extend(user.roms);
+        Ok(db)
+    }
+
+    pub fn get(&self, md5: &str) -> Option<&Known> {
+        self.roms.get(md5)
+    }
+
+    // the entry for the ROM itself, or else for its layout
+    pub fn find(&self, info: &RomInfo) -> Option<&Known> {
+        self.get(&info.md5).or_else(|| {
+            let addr = info.teleporter?;
+            self.get(&format!("teleporter={}", addr))
+        })
+    }
+}
+
+// add the offsets known for the ROM to the symbols, falling back to the
+// routines rominfo found by their code.  symbols already loaded win
+pub fn apply(db: &Database, info: &RomInfo, symbols: &mut Symbols) {
+    if let Some(known) = db.find(info) {
+        for (name, addr) in known.symbols.iter() {
+            symbols.add(name, addr);
+        }
+    }
+    if let Some(addr) = info.teleporter {
+        symbols.add("teleporter_check", addr);
+    }
+    if let Some(addr) = info.string_printer {
+        symbols.add("string_printer", addr);
+    }
+}
+
+// append an entry for the ROM to the user's file
+pub fn record(
+    file: &str,
+    md5: &str,
+    description: &str,
+    symbols: &Symbols,
+) -> Result<(), String> {
+    let mut entry = format!("rom {} {}\n", md5, description);
+    for (name, addr) in symbols.iter() {
+        entry += &format!("{} {}\n", name, addr);
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .and_then(|mut f| f.write_all(entry.as_bytes()))
+        .map_err(|e| format!("failed to write {}: {}", file, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(md5: &str, teleporter: Option<u16>) -> RomInfo {
+        RomInfo {
+            md5: md5.to_string(),
+            words: 30050,
+            version: None,
+            teleporter,
+            string_printer: None,
+        }
+    }
+
+    #[test]
+    fn builtin_website_layout() {
+        let db = Database::parse(BUILTIN).unwrap();
+        let known = db.find(&rom("0123", Some(6049))).unwrap();
+        assert_eq!(known.description, "challenge.bin from the website");
+        assert_eq!(known.symbols.get("teleporter_check"), Some(6049));
+
+        assert!(db.find(&rom("0123", Some(6050))).is_none());
+        assert!(db.find(&rom("0123", None)).is_none());
+    }
+
+    #[test]
+    fn md5_before_layout() {
+        let mut db = Database::parse(BUILTIN).unwrap();
+        let user = Database::parse(
+            "rom ABCDEF my copy\nteleporter_check 6049\nvault_grid 100\n",
+        )
+        .unwrap();
+        db.roms.extend(user.roms);
+
+        let known = db.find(&rom("abcdef", Some(6049))).unwrap();
+        assert_eq!(known.description, "my copy");
+        assert_eq!(known.symbols.get("vault_grid"), Some(100));
+    }
+
+    #[test]
+    fn apply_builtin_and_found() {
+        let db = Database::parse(BUILTIN).unwrap();
+        let mut info = rom("0123", Some(6049));
+        info.string_printer = Some(1458);
+
+        let mut symbols = Symbols::default();
+        apply(&db, &info, &mut symbols);
+        assert_eq!(symbols.get("teleporter_check"), Some(6049));
+        assert_eq!(symbols.get("string_printer"), Some(1458));
+        // not known for the website build
+        assert_eq!(symbols.get("vault_grid"), None);
+        assert_eq!(symbols.len(), 2);
+    }
+
+    #[test]
+    fn entry_before_rom_line() {
+        assert!(Database::parse("teleporter_check 6049\n").is_err());
+    }
+}
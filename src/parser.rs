@@ -0,0 +1,184 @@
+/*!
+ * Pull structured data out of the game's output.
+ *
+ * A room as printed by the game looks like:
+ *
+ * ```text
+ * == Foothills ==
+ * You find yourself standing at the base of an enormous mountain.
+ *
+ * Things of interest here:
+ * - tablet
+ *
+ * There are 2 exits:
+ * - north
+ * - south
+ *
+ * What do I do?
+ * ```
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Room {
+    pub title: String,
+    pub description: String,
+    pub exits: Vec<String>,
+    // the "things of interest" listed in the room - these are the items
+    // that can be looked at or taken
+    pub items: Vec<String>,
+}
+
+// the room title if this line is a `== Title ==` header
+pub fn title(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("== ")
+        .and_then(|s| s.strip_suffix(" =="))
+        .map(|s| s.trim())
+}
+
+// the `- item` entries of a list that starts right after lines[start]
+fn list(lines: &[&str], start: usize) -> Vec<String> {
+    lines[start + 1..]
+        .iter()
+        .map_while(|line| line.strip_prefix("- "))
+        .map(|s| s.trim().to_string())
+        .collect()
+}
+
+// parse the last room printed in the given output, if any
+pub fn parse_room(output: &str) -> Option<Room> {
+    let lines: Vec<_> = output.lines().collect();
+
+    let start = lines.iter().rposition(|line| title(line).is_some())?;
+    let mut room = Room {
+        title: title(lines[start]).unwrap().to_string(),
+        ..Default::default()
+    };
+
+    // the description runs until the first blank line
+    let description: Vec<_> = lines[start + 1..]
+        .iter()
+        .take_while(|line| !line.trim().is_empty())
+        .map(|line| line.trim())
+        .collect();
+    room.description = description.join("\n");
+
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim();
+        if line == "Things of interest here:" {
+            room.items = list(&lines, i);
+        } else if line.starts_with("There ")
+            && (line.ends_with(" exits:") || line.ends_with(" exit:"))
+        {
+            room.exits = list(&lines, i);
+        }
+    }
+
+    Some(room)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // part of a game, one chunk of output per command
+    const TRANSCRIPT: &[&str] = &[
+        "\
+== Foothills ==
+You find yourself standing at the base of an enormous mountain.  At its base to the north, there is a massive doorway.  A sign nearby reads \"Keep out!  Definitely no treasure within!\"
+
+Things of interest here:
+- tablet
+
+There are 2 exits:
+- doorway
+- south
+
+What do I do?
+",
+        "\
+Taken.
+
+What do I do?
+",
+        "\
+Your inventory:
+- tablet
+
+What do I do?
+",
+        "\
+== Dark passage ==
+You are in a dark, narrow passage.
+The walls are damp, and the air smells of something old.
+
+There is 1 exit:
+- back
+
+What do I do?
+",
+        "\
+== Fumbling around in the darkness ==
+You are likely to be eaten by a grue.
+
+What do I do?
+",
+    ];
+
+    #[test]
+    fn room_with_items_and_exits() {
+        let room = parse_room(TRANSCRIPT[0]).unwrap();
+        assert_eq!(room.title, "Foothills");
+        assert!(room.description.starts_with("You find yourself standing"));
+        assert!(room.description.ends_with("Definitely no treasure within!\""));
+        assert_eq!(room.items, ["tablet"]);
+        assert_eq!(room.exits, ["doorway", "south"]);
+    }
+
+    #[test]
+    fn output_without_a_room() {
+        assert_eq!(parse_room(TRANSCRIPT[1]), None);
+        // the inventory is a list too, but not of things in a room
+        assert_eq!(parse_room(TRANSCRIPT[2]), None);
+    }
+
+    #[test]
+    fn last_room_wins() {
+        let room = parse_room(&TRANSCRIPT[..3].concat()).unwrap();
+        assert_eq!(room.title, "Foothills");
+        assert_eq!(room.items, ["tablet"]);
+
+        let room = parse_room(&TRANSCRIPT.concat()).unwrap();
+        assert_eq!(room.title, "Fumbling around in the darkness");
+    }
+
+    #[test]
+    fn multi_line_description() {
+        let room = parse_room(TRANSCRIPT[3]).unwrap();
+        assert_eq!(room.title, "Dark passage");
+        assert_eq!(
+            room.description,
+            "You are in a dark, narrow passage.\n\
+             The walls are damp, and the air smells of something old."
+        );
+        assert!(room.items.is_empty());
+        assert_eq!(room.exits, ["back"]);
+    }
+
+    #[test]
+    fn room_with_no_exits() {
+        let room = parse_room(TRANSCRIPT[4]).unwrap();
+        assert_eq!(room.description, "You are likely to be eaten by a grue.");
+        assert!(room.exits.is_empty());
+        assert!(room.items.is_empty());
+    }
+
+    #[test]
+    fn title_line() {
+        assert_eq!(title("== Foothills =="), Some("Foothills"));
+        assert_eq!(title("  ==  Foothills  ==  "), Some("Foothills"));
+        assert_eq!(title("Foothills"), None);
+    }
+}
@@ -0,0 +1,165 @@
+/*!
+ * Plugins - analysis passes and bots that hook into the shell.
+ *
+ * A plugin sees every instruction before it runs, every character the game
+ * prints and every line of input it's given, and can add `/` commands.  The
+ * shell calls the hooks, so a new pass doesn't need to touch the step loop:
+ * implement `Plugin` and add it to `REGISTRY` to make it loadable by name
+ * with `--plugin` or `/plugin load`.
+ *
+ * ```no_run
+ * use synacor_challenge::plugin::Plugin;
+ * use synacor_challenge::vm::VM;
+ *
+ * #[derive(Default)]
+ * struct Calls(u64);
+ *
+ * impl Plugin for Calls {
+ *     fn name(&self) -> &str {
+ *         "calls"
+ *     }
+ *
+ *     fn step(&mut self, vm: &VM) {
+ *         if vm.ram().word(vm.pc()) == Some(17) {
+ *             self.0 += 1;
+ *         }
+ *     }
+ * }
+ * ```
+ *
+ * Plugins see each instruction, so loading one turns superblocks off.
+ */
+
+use crate::vm::{MNEMONICS, VM};
+
+pub trait Plugin: Send {
+    fn name(&self) -> &str;
+
+    // the instruction at the pc is about to run
+    fn step(&mut self, _vm: &VM) {}
+
+    // a character of game output
+    fn output(&mut self, _c: u8) {}
+
+    // a line of input given to the game, typed or from a script
+    fn input(&mut self, _line: &str) {}
+
+    // a `/` command the shell doesn't know, split on whitespace.  true if
+    // the plugin handled it
+    fn command(&mut self, _vm: &mut VM, _args: &[&str]) -> bool {
+        false
+    }
+}
+
+// makes a fresh plugin
+pub type Constructor = fn() -> Box<dyn Plugin>;
+
+// the plugins built in, by name
+pub static REGISTRY: &[(&str, Constructor)] =
+    &[("opcount", || Box::new(OpCount::default()))];
+
+// a built in plugin by name
+pub fn load(name: &str) -> Result<Box<dyn Plugin>, String> {
+    let Some((_, new)) = REGISTRY.iter().find(|(n, _)| *n == name) else {
+        let names: Vec<_> = REGISTRY.iter().map(|(n, _)| *n).collect();
+        return Err(format!("no plugin {} (have {})", name, names.join(", ")));
+    };
+    Ok(new())
+}
+
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Plugins {
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|p| p.name()).collect()
+    }
+
+    // false if one with the same name is already loaded
+    pub fn add(&mut self, plugin: Box<dyn Plugin>) -> bool {
+        if self.plugins.iter().any(|p| p.name() == plugin.name()) {
+            return false;
+        }
+        self.plugins.push(plugin);
+        true
+    }
+
+    pub fn step(&mut self, vm: &VM) {
+        for plugin in &mut self.plugins {
+            plugin.step(vm);
+        }
+    }
+
+    pub fn output(&mut self, c: u8) {
+        for plugin in &mut self.plugins {
+            plugin.output(c);
+        }
+    }
+
+    pub fn input(&mut self, line: &str) {
+        for plugin in &mut self.plugins {
+            plugin.input(line);
+        }
+    }
+
+    // offer a command to each plugin until one takes it
+    pub fn command(&mut self, vm: &mut VM, args: &[&str]) -> bool {
+        self.plugins.iter_mut().any(|p| p.command(vm, args))
+    }
+}
+
+// counts the instructions run by opcode, shown with `/opcount` and cleared
+// with `/opcount reset`
+#[derive(Default)]
+pub struct OpCount {
+    counts: [u64; MNEMONICS.len()],
+}
+
+impl Plugin for OpCount {
+    fn name(&self) -> &str {
+        "opcount"
+    }
+
+    fn step(&mut self, vm: &VM) {
+        if let Some(op) = vm.ram().word(vm.pc())
+            && let Some(count) = self.counts.get_mut(op as usize)
+        {
+            *count += 1;
+        }
+    }
+
+    fn command(&mut self, _vm: &mut VM, args: &[&str]) -> bool {
+        match args {
+            ["opcount"] => {
+                let total: u64 = self.counts.iter().sum();
+                let mut counts: Vec<_> = MNEMONICS
+                    .iter()
+                    .zip(self.counts)
+                    .filter(|(_, n)| *n > 0)
+                    .collect();
+                counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+                for (name, n) in counts {
+                    println!(
+                        "{:<5} {:>12}  {:>5.1}%",
+                        name,
+                        n,
+                        n as f64 * 100.0 / total as f64
+                    );
+                }
+                println!("{} instructions", total);
+            }
+            ["opcount", "reset"] => {
+                self.counts = Default::default();
+                println!("opcount reset");
+            }
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,130 @@
+/*!
+ * The VM's memory, as copy-on-write pages.
+ *
+ * Memory is split into PAGE_SIZE byte pages behind `Arc`s, so cloning it
+ * (for a snapshot, or a search that forks thousands of VMs) only copies the
+ * page pointers.  A page is copied the first time it is written after a
+ * clone - the cost of a clone is the pages that get dirtied afterwards, not
+ * the whole address space.
+ *
+ * It (de)serializes as a plain list of bytes, the same as the `Vec<u8>` it
+ * replaced, so saved states are unchanged.
+ */
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+// bytes per page, 512 words
+pub const PAGE_SIZE: usize = 1024;
+
+#[derive(Clone, Default)]
+pub struct Ram {
+    pages: Vec<Arc<[u8; PAGE_SIZE]>>,
+    // length in bytes, the last page may be partly used
+    len: usize,
+}
+
+impl Ram {
+    pub fn new(bytes: &[u8]) -> Self {
+        let pages = bytes
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+        Self { pages, len: bytes.len() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, ptr: usize) -> Option<u8> {
+        (ptr < self.len).then(|| self.pages[ptr / PAGE_SIZE][ptr % PAGE_SIZE])
+    }
+
+    // the little endian word at a word address, None past the end
+    pub fn word(&self, addr: u16) -> Option<u16> {
+        let ptr = addr as usize * 2;
+        let low = self.get(ptr)?;
+        let high = self.get(ptr + 1)?;
+        Some(u16::from_le_bytes([low, high]))
+    }
+
+    pub fn set(&mut self, ptr: usize, value: u8) {
+        assert!(ptr < self.len, "write past the end of ram at {}", ptr);
+        let page = Arc::make_mut(&mut self.pages[ptr / PAGE_SIZE]);
+        page[ptr % PAGE_SIZE] = value;
+    }
+
+    pub fn set_word(&mut self, addr: u16, value: u16) {
+        let ptr = addr as usize * 2;
+        let [low, high] = value.to_le_bytes();
+        self.set(ptr, low);
+        self.set(ptr + 1, high);
+    }
+
+    // grow (with zeros) or shrink to len bytes
+    pub fn resize(&mut self, len: usize) {
+        if len < self.len {
+            // clear what's cut off the last page so growing again reads zeros
+            for ptr in len..self.len.min(len.div_ceil(PAGE_SIZE) * PAGE_SIZE) {
+                self.set(ptr, 0);
+            }
+        }
+        self.pages
+            .resize_with(len.div_ceil(PAGE_SIZE), || Arc::new([0; PAGE_SIZE]));
+        self.len = len;
+    }
+
+    // true if the word at addr is the same in both
+    pub fn same_word(&self, other: &Ram, addr: u16) -> bool {
+        let page = addr as usize * 2 / PAGE_SIZE;
+        if let (Some(a), Some(b)) =
+            (self.pages.get(page), other.pages.get(page))
+            && Arc::ptr_eq(a, b)
+            && self.len == other.len
+        {
+            return true;
+        }
+        self.word(addr) == other.word(addr)
+    }
+
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pages.iter().flat_map(|page| page.iter()).take(self.len).copied()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.bytes().collect()
+    }
+
+    // every whole word, see `map::words`
+    pub fn words(&self) -> Vec<u16> {
+        (0..self.len / 2).map(|addr| self.word(addr as u16).unwrap()).collect()
+    }
+
+    // the bytes from start up to (not including) end, clamped to the length
+    pub fn slice(&self, start: usize, end: usize) -> Vec<u8> {
+        let end = end.min(self.len);
+        (start.min(end)..end).map(|ptr| self.get(ptr).unwrap()).collect()
+    }
+}
+
+impl Serialize for Ram {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.to_vec().serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ram {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(d)?;
+        Ok(Self::new(&bytes))
+    }
+}
@@ -0,0 +1,202 @@
+/*!
+ * Work out what ROM is loaded.
+ *
+ * Printed when a ROM starts (and by `/info`) so it's obvious when the binary
+ * isn't the one the symbols, patches or notes were made for:
+ *
+ * ```text
+ * rom: 4a1c...e3 (30050 words)
+ * version: Synacor Challenge (self-test code LDOb7UGhTi)
+ * teleporter check: 6049
+ * string printer: 1458
+ * known rom: challenge.bin from the website (1 offset)
+ * symbols: challenge.bin.sym (23 names)
+ * ```
+ *
+ * The version comes from the welcome message the ROM prints before its
+ * self-test, and routines are found by what their code looks like rather
+ * than where they are, so they're found in builds with things moved around.
+ */
+
+use std::fmt;
+
+use crate::disasm;
+use crate::offsets::Database;
+use crate::ram::Ram;
+use crate::symbols::Symbols;
+
+// registers as operand words
+const R0: u16 = 32768;
+const R1: u16 = 32769;
+
+// instructions to look through for the welcome message
+const BANNER_MAX: usize = 512;
+
+// instructions into a routine to look for what it does
+const ROUTINE_MAX: usize = 64;
+
+pub struct RomInfo {
+    // md5 of the image as loaded, as lowercase hex
+    pub md5: String,
+    pub words: usize,
+    pub version: Option<String>,
+    // routines found, by what they do
+    pub teleporter: Option<u16>,
+    pub string_printer: Option<u16>,
+}
+
+// the text printed by the `out` instructions at the start of the ROM
+fn banner(words: &[u16]) -> String {
+    let mut text = String::new();
+    let mut addr = 0;
+    for _ in 0..BANNER_MAX {
+        let Some(ins) = disasm::decode(words, addr) else {
+            break;
+        };
+        match ins.opcode {
+            19 if ins.operands[0] < 128 => {
+                text.push(ins.operands[0] as u8 as char)
+            }
+            19 | 21 => (),
+            _ => break,
+        }
+        addr += ins.len() as u16;
+    }
+    text
+}
+
+fn version(words: &[u16]) -> Option<String> {
+    let banner = banner(words);
+    if !banner.contains("Synacor Challenge") {
+        return None;
+    }
+    // the code handed out for getting this far identifies the build
+    let code = banner
+        .lines()
+        .find_map(|l| l.split_once("challenge website: "))
+        .map(|(_, code)| code.trim());
+    match code {
+        Some(code) => {
+            Some(format!("Synacor Challenge (self-test code {})", code))
+        }
+        None => Some("Synacor Challenge".to_string()),
+    }
+}
+
+// every address called with a literal operand, in order
+fn call_targets(words: &[u16]) -> Vec<u16> {
+    let mut targets: Vec<u16> = (0..words.len().saturating_sub(1))
+        .filter(|i| words[*i] == 17 && words[*i + 1] < 32768)
+        .map(|i| words[i + 1])
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+}
+
+// the instructions of the routine at addr, up to its first ret
+fn routine(words: &[u16], mut addr: u16) -> Vec<disasm::Instruction> {
+    let mut body = vec![];
+    while body.len() < ROUTINE_MAX {
+        let Some(ins) = disasm::decode(words, addr) else {
+            break;
+        };
+        addr += ins.len() as u16;
+        let ret = ins.opcode == 18;
+        body.push(ins);
+        if ret {
+            break;
+        }
+    }
+    body
+}
+
+// the confirmation routine starts with the base case of the recursion:
+// `jt r0 <x>; add r0 r1 1; ret`
+fn is_teleporter(body: &[disasm::Instruction]) -> bool {
+    matches!(
+        body,
+        [a, b, c, ..]
+            if a.opcode == 7 && a.operands[0] == R0
+            && b.opcode == 9 && b.operands == [R0, R1, 1]
+            && c.opcode == 18
+    )
+}
+
+// the string printer walks a length-prefixed string, reading the length and
+// characters with rmem and handing each to a callback given in a register
+fn is_string_printer(body: &[disasm::Instruction]) -> bool {
+    let reads = body.iter().any(|i| i.opcode == 15);
+    let callback =
+        body.iter().any(|i| i.opcode == 17 && i.operands[0] >= 32768);
+    reads && callback
+}
+
+pub fn identify(image: &Ram) -> RomInfo {
+    let words = &image.words();
+    let mut teleporter = None;
+    let mut string_printer = None;
+    for addr in call_targets(words) {
+        let body = routine(words, addr);
+        if teleporter.is_none() && is_teleporter(&body) {
+            teleporter = Some(addr);
+        }
+        if string_printer.is_none() && is_string_printer(&body) {
+            string_printer = Some(addr);
+        }
+    }
+
+    RomInfo {
+        md5: format!("{:x}", md5::compute(image.to_vec())),
+        words: words.len(),
+        version: version(words),
+        teleporter,
+        string_printer,
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let found = |addr: Option<u16>| match addr {
+            Some(addr) => addr.to_string(),
+            None => "not found".to_string(),
+        };
+
+        writeln!(f, "rom: {} ({} words)", self.md5, self.words)?;
+        writeln!(
+            f,
+            "version: {}",
+            self.version.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(f, "teleporter check: {}", found(self.teleporter))?;
+        write!(f, "string printer: {}", found(self.string_printer))
+    }
+}
+
+// a line about what the offsets database knows of the ROM
+pub fn describe_offsets(db: &Database, info: &RomInfo) -> String {
+    match db.find(info) {
+        Some(known) => format!(
+            "known rom: {} ({} offset{})",
+            known.description,
+            known.symbols.len(),
+            if known.symbols.len() == 1 { "" } else { "s" }
+        ),
+        None => "known rom: no, see /offsets save".to_string(),
+    }
+}
+
+// a line about the symbols in use, warning when they were made for another
+// ROM
+pub fn describe_symbols(symbols: &Symbols, info: &RomInfo) -> String {
+    let Some(file) = symbols.file() else {
+        return "symbols: none".to_string();
+    };
+    let mut line = format!("symbols: {} ({} names)", file, symbols.len());
+    if let Some(rom) = symbols.rom()
+        && rom != info.md5
+    {
+        line += &format!(" - made for rom {}, not this one!", rom);
+    }
+    line
+}
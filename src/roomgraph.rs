@@ -0,0 +1,238 @@
+/*!
+ * The explored rooms as a map in a web page.
+ *
+ * `solve maze --html map.html game.bin` writes every room the search found
+ * and the exits between them to one standalone HTML file.  It opens in any
+ * browser with nothing else needed: drag to pan, scroll to zoom, and hover
+ * over a room to see its description, items and exits.  With `--here SAVE`
+ * the room a save was made in is highlighted (given a directory, the save
+ * in it written last).
+ *
+ * Rooms start out laid out by compass direction from the first one and are
+ * then pushed apart until nothing overlaps, so the map looks roughly like
+ * the world without having to be exact - the game's exits don't always add
+ * up.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::parser::Room;
+use crate::saves;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Graph {
+    pub rooms: Vec<Room>,
+    // from, exit, to, as indexes into rooms
+    pub edges: Vec<(usize, String, usize)>,
+}
+
+impl Graph {
+    // the index of a room, added if it's new.  rooms are told apart by their
+    // title and description, since the maze has many with the same title
+    pub fn room(&mut self, room: &Room) -> usize {
+        let same = |r: &Room| {
+            r.title == room.title && r.description == room.description
+        };
+        match self.rooms.iter().position(same) {
+            Some(i) => i,
+            None => {
+                self.rooms.push(room.clone());
+                self.rooms.len() - 1
+            }
+        }
+    }
+
+    pub fn edge(&mut self, from: usize, exit: &str, to: usize) {
+        let edge = (from, exit.to_string(), to);
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+}
+
+// the room a save was made in.  for a directory, the save in it written
+// last
+pub fn location(file: &str) -> Result<Option<String>, String> {
+    let info = match fs::metadata(file).is_ok_and(|m| m.is_dir()) {
+        true => {
+            let modified = |p: &PathBuf| {
+                fs::metadata(p)
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH)
+            };
+            let saves = saves::list(file)?;
+            let latest = saves.into_iter().max_by_key(|(p, _)| modified(p));
+            match latest {
+                Some((_, info)) => info.thumbnail,
+                None => return Err(format!("no saves in {}", file)),
+            }
+        }
+        false => saves::load(file)?.thumbnail().clone(),
+    };
+    Ok(info.location)
+}
+
+// the page, with the room titled here highlighted
+pub fn html(graph: &Graph, here: Option<&str>) -> String {
+    let data = serde_json::json!({ "graph": graph, "here": here });
+    // the json goes in a <script>, which mustn't see a closing tag
+    let data = data.to_string().replace("</", "<\\/");
+    PAGE.replace("/*DATA*/", &data)
+}
+
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Synacor map</title>
+<style>
+body { margin: 0; font: 13px sans-serif; overflow: hidden; }
+svg { width: 100vw; height: 100vh; cursor: grab; }
+line { stroke: #999; stroke-width: 1.5; }
+.room circle { fill: #9cf; stroke: #357; stroke-width: 1.5; }
+.room.here circle { fill: #f96; stroke: #a30; stroke-width: 3; }
+.room:hover circle { stroke-width: 3; }
+.room text { pointer-events: none; text-anchor: middle; }
+#info { position: fixed; left: 8px; bottom: 8px; max-width: 40em;
+        padding: 6px 10px; background: #fffe; border: 1px solid #ccc;
+        white-space: pre-wrap; display: none; }
+#help { position: fixed; right: 8px; top: 8px; color: #888; }
+</style>
+</head>
+<body>
+<svg id="map"><g id="edges"></g><g id="rooms"></g></svg>
+<div id="info"></div>
+<div id="help">drag to pan, scroll to zoom</div>
+<script>
+const data = /*DATA*/;
+const rooms = data.graph.rooms, edges = data.graph.edges;
+const svg = document.getElementById("map");
+const ns = "http://www.w3.org/2000/svg";
+const SPACING = 90;
+
+// start each room a step from the first room that leads to it, in the
+// direction of the exit
+const dirs = { north: [0, -1], south: [0, 1], east: [1, 0], west: [-1, 0],
+               up: [0.7, -0.7], down: [-0.7, 0.7] };
+const pos = rooms.map(() => null);
+const queue = rooms.length ? [0] : [];
+if (rooms.length) pos[0] = { x: 0, y: 0 };
+while (queue.length) {
+  const from = queue.shift();
+  for (const [a, exit, b] of edges) {
+    if (a !== from || pos[b]) continue;
+    const [dx, dy] = dirs[exit] || [Math.random() - 0.5, Math.random() - 0.5];
+    pos[b] = { x: pos[a].x + dx * SPACING, y: pos[a].y + dy * SPACING };
+    queue.push(b);
+  }
+}
+pos.forEach((p, i) => {
+  if (!p) pos[i] = { x: (i % 10) * SPACING, y: 400 + Math.floor(i / 10) * SPACING };
+});
+
+// then push rooms apart and pull connected ones together
+for (let step = 0; step < 300; step++) {
+  const force = pos.map(() => ({ x: 0, y: 0 }));
+  for (let i = 0; i < pos.length; i++) {
+    for (let j = i + 1; j < pos.length; j++) {
+      const dx = pos[i].x - pos[j].x, dy = pos[i].y - pos[j].y;
+      const d = Math.max(Math.hypot(dx, dy), 1);
+      if (d > SPACING * 1.5) continue;
+      const k = (SPACING * 1.5 - d) / d * 0.5;
+      force[i].x += dx * k; force[i].y += dy * k;
+      force[j].x -= dx * k; force[j].y -= dy * k;
+    }
+  }
+  for (const [a, , b] of edges) {
+    const dx = pos[b].x - pos[a].x, dy = pos[b].y - pos[a].y;
+    const d = Math.max(Math.hypot(dx, dy), 1);
+    const k = (d - SPACING) / d * 0.05;
+    force[a].x += dx * k; force[a].y += dy * k;
+    force[b].x -= dx * k; force[b].y -= dy * k;
+  }
+  const cool = 1 - step / 300;
+  pos.forEach((p, i) => { p.x += force[i].x * cool; p.y += force[i].y * cool; });
+}
+
+function el(name, attrs, parent) {
+  const e = document.createElementNS(ns, name);
+  for (const k in attrs) e.setAttribute(k, attrs[k]);
+  parent.appendChild(e);
+  return e;
+}
+
+const drawn = new Set();
+for (const [a, exit, b] of edges) {
+  const key = Math.min(a, b) + "-" + Math.max(a, b);
+  if (drawn.has(key)) continue;
+  drawn.add(key);
+  const line = el("line", { x1: pos[a].x, y1: pos[a].y, x2: pos[b].x, y2: pos[b].y },
+                  document.getElementById("edges"));
+  el("title", {}, line).textContent = exit;
+}
+
+const info = document.getElementById("info");
+rooms.forEach((room, i) => {
+  const here = room.title === data.here;
+  const g = el("g", { class: here ? "room here" : "room",
+                      transform: `translate(${pos[i].x},${pos[i].y})` },
+               document.getElementById("rooms"));
+  el("circle", { r: 10 }, g);
+  el("text", { y: 24 }, g).textContent = room.title;
+  g.addEventListener("mouseenter", () => {
+    let text = room.title + (here ? " (you are here)" : "") + "\n\n" + room.description;
+    if (room.items.length) text += "\n\nthings of interest: " + room.items.join(", ");
+    text += "\n\nexits: " + room.exits.join(", ");
+    info.textContent = text;
+    info.style.display = "block";
+  });
+  g.addEventListener("mouseleave", () => { info.style.display = "none"; });
+});
+
+// fit everything, then pan and zoom by changing the view box
+const xs = pos.map(p => p.x), ys = pos.map(p => p.y);
+const pad = SPACING;
+let view = { x: Math.min(0, ...xs) - pad, y: Math.min(0, ...ys) - pad };
+view.w = Math.max(0, ...xs) - view.x + pad;
+view.h = Math.max(0, ...ys) - view.y + pad;
+const herePos = pos[rooms.findIndex(r => r.title === data.here)];
+function show() {
+  svg.setAttribute("viewBox", `${view.x} ${view.y} ${view.w} ${view.h}`);
+}
+show();
+
+svg.addEventListener("wheel", e => {
+  e.preventDefault();
+  const scale = e.deltaY > 0 ? 1.2 : 1 / 1.2;
+  const r = svg.getBoundingClientRect();
+  const mx = view.x + (e.clientX - r.left) / r.width * view.w;
+  const my = view.y + (e.clientY - r.top) / r.height * view.h;
+  view = { x: mx - (mx - view.x) * scale, y: my - (my - view.y) * scale,
+           w: view.w * scale, h: view.h * scale };
+  show();
+}, { passive: false });
+
+let drag = null;
+svg.addEventListener("mousedown", e => { drag = { x: e.clientX, y: e.clientY }; });
+window.addEventListener("mouseup", () => { drag = null; });
+window.addEventListener("mousemove", e => {
+  if (!drag) return;
+  const r = svg.getBoundingClientRect();
+  view.x -= (e.clientX - drag.x) / r.width * view.w;
+  view.y -= (e.clientY - drag.y) / r.height * view.h;
+  drag = { x: e.clientX, y: e.clientY };
+  show();
+});
+
+// start zoomed in on the current room, if there is one
+if (herePos) {
+  view = { x: herePos.x - 400, y: herePos.y - 300, w: 800, h: 600 };
+  show();
+}
+</script>
+</body>
+</html>
+"##;
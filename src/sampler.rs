@@ -0,0 +1,310 @@
+/*!
+ * Sample the call stack every so often instead of tracing every call.
+ *
+ * A chrome trace of the teleporter confirmation has an event for each of
+ * billions of calls, far too much to write.  `--sample FILE` looks at the
+ * call stack every 10,000 instructions instead (`--sample-every 500` for
+ * another number, or `--sample-every 2ms` to sample on a timer) and counts
+ * how often each stack is seen.  The counts are kept in memory and written to
+ * FILE as JSON every few seconds and when the game halts:
+ *
+ * ```text
+ * {"every":"10000","samples":3412,"stacks":[{"stack":[6027,6027],"count":3300},...]}
+ * ```
+ *
+ * Stacks are the addresses of the routines called, outermost first.
+ * `/profile` in the shell lists the routines seen most often:
+ *
+ * ```text
+ *    self  total  routine
+ *   96.7%  99.9%  6027
+ *    0.1%   0.2%  1458 (print)
+ * ```
+ *
+ * `synacor-challenge fold FILE` turns the counts into folded stacks, a line
+ * for each stack with the routines' names, for `flamegraph.pl` or
+ * `inferno-flamegraph` to draw where the game spends its time:
+ *
+ * ```text
+ * 6027;6027 3300
+ * 2125 (main_loop);1458 (print) 7
+ * ```
+ *
+ * Sampling by instruction count gives the same profile every run.  With
+ * superblocks on several instructions can run in one step, so a sample that
+ * lands in the middle of one is taken at its end, counted as however many
+ * samples were due.
+ */
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::debugger::Frame;
+use crate::saves;
+use crate::shell::group_digits;
+use crate::symbols::Symbols;
+use crate::vm::routine_name;
+
+// instructions between samples when no --sample-every is given
+pub const DEFAULT_EVERY: u64 = 10_000;
+
+// how often the profile is rewritten while sampling
+const WRITE_EVERY: Duration = Duration::from_secs(5);
+
+// routines listed by /profile
+const REPORT_ROUTINES: usize = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Every {
+    Instructions(u64),
+    Interval(Duration),
+}
+
+impl Every {
+    // "10000" for instructions, or a time like "2ms", "500us" or "1s"
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let bad = || format!("bad sample interval: {}", s);
+        let every = if let Some(n) = s.strip_suffix("us") {
+            Every::Interval(Duration::from_micros(
+                n.parse().map_err(|_| bad())?,
+            ))
+        } else if let Some(n) = s.strip_suffix("ms") {
+            Every::Interval(Duration::from_millis(
+                n.parse().map_err(|_| bad())?,
+            ))
+        } else if let Some(n) = s.strip_suffix('s') {
+            Every::Interval(Duration::from_secs(n.parse().map_err(|_| bad())?))
+        } else {
+            Every::Instructions(s.parse().map_err(|_| bad())?)
+        };
+        match every {
+            Every::Instructions(0) => Err(bad()),
+            Every::Interval(d) if d.is_zero() => Err(bad()),
+            every => Ok(every),
+        }
+    }
+}
+
+impl Default for Every {
+    fn default() -> Self {
+        Every::Instructions(DEFAULT_EVERY)
+    }
+}
+
+impl fmt::Display for Every {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Every::Instructions(n) => write!(f, "{}", n),
+            Every::Interval(d) if d.subsec_micros() % 1000 == 0 => {
+                write!(f, "{}ms", d.as_millis())
+            }
+            Every::Interval(d) => write!(f, "{}us", d.as_micros()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Stack {
+    pub stack: Vec<u16>,
+    pub count: u64,
+}
+
+// what's written to the file
+#[derive(Serialize, Deserialize)]
+pub struct Profile {
+    pub every: String,
+    pub samples: u64,
+    // most common first
+    pub stacks: Vec<Stack>,
+}
+
+impl Profile {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let data =
+            std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+        serde_json::from_slice(&data).map_err(|e| format!("{}: {}", file, e))
+    }
+
+    // a "outer;inner count" line for each stack.  outside any call is
+    // "(top)"
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let mut out = String::new();
+        for s in &self.stacks {
+            let names: Vec<String> = s
+                .stack
+                .iter()
+                // ; separates the routines
+                .map(|addr| routine_name(symbols, *addr).replace(';', ","))
+                .collect();
+            let stack = match names.is_empty() {
+                true => "(top)".to_string(),
+                false => names.join(";"),
+            };
+            writeln!(out, "{} {}", stack, s.count).unwrap();
+        }
+        out
+    }
+
+    // the most sampled routines, by samples in the routine itself and in
+    // it or anything it called.  outside any call is "(top)"
+    pub fn report(&self, symbols: &Symbols) -> String {
+        if self.samples == 0 {
+            return "no samples yet".to_string();
+        }
+
+        let mut own: HashMap<Option<u16>, u64> = HashMap::new();
+        let mut total: HashMap<Option<u16>, u64> = HashMap::new();
+        for s in &self.stacks {
+            *own.entry(s.stack.last().copied()).or_default() += s.count;
+            // recursion only counts once towards the total
+            let mut seen = vec![];
+            for routine in s.stack.iter().map(|r| Some(*r)).chain([None]) {
+                if !seen.contains(&routine) {
+                    seen.push(routine);
+                    *total.entry(routine).or_default() += s.count;
+                }
+            }
+        }
+
+        let mut routines: Vec<_> = total.into_iter().collect();
+        let own_of = |r: &Option<u16>| own.get(r).copied().unwrap_or(0);
+        routines.sort_by_key(|(r, n)| (Reverse(own_of(r)), Reverse(*n), *r));
+
+        let percent = |n: u64| 100.0 * n as f64 / self.samples as f64;
+        let mut lines = vec!["   self  total  routine".to_string()];
+        for (routine, n) in routines.into_iter().take(REPORT_ROUTINES) {
+            let name = match routine {
+                Some(addr) => routine_name(symbols, addr),
+                None => "(top)".to_string(),
+            };
+            lines.push(format!(
+                " {:>5.1}% {:>5.1}%  {}",
+                percent(own_of(&routine)),
+                percent(n),
+                name
+            ));
+        }
+        lines.push(format!(
+            "{} samples, every {}",
+            group_digits(self.samples),
+            self.every
+        ));
+        lines.join("\n")
+    }
+}
+
+pub struct Sampler {
+    every: Every,
+    // step count of the next sample, sampling by instructions
+    next: u64,
+    // set by the timer thread when a sample is due, sampling on a timer
+    due: Arc<AtomicBool>,
+    stacks: HashMap<Vec<u16>, u64>,
+    samples: u64,
+    file: String,
+    written: Instant,
+}
+
+impl Sampler {
+    // start sampling, steps being the VM's step count now.  the file is
+    // written straight away so a bad path is found before the game runs
+    pub fn new(file: &str, every: Every, steps: u64) -> Result<Self, String> {
+        let due = Arc::new(AtomicBool::new(false));
+        if let Every::Interval(d) = every {
+            // stops when the sampler is dropped
+            let due = Arc::downgrade(&due);
+            thread::spawn(move || {
+                loop {
+                    thread::sleep(d);
+                    match due.upgrade() {
+                        Some(due) => due.store(true, Ordering::Relaxed),
+                        None => break,
+                    }
+                }
+            });
+        }
+        let next = match every {
+            Every::Instructions(n) => steps + n,
+            Every::Interval(_) => 0,
+        };
+        let sampler = Self {
+            every,
+            next,
+            due,
+            stacks: HashMap::new(),
+            samples: 0,
+            file: file.to_string(),
+            written: Instant::now(),
+        };
+        saves::write(file, &sampler.json(), true)?;
+        Ok(sampler)
+    }
+
+    // called after every step, so it has to be cheap when no sample is due
+    pub fn tick(&mut self, steps: u64, frames: &[Frame]) {
+        let count = match self.every {
+            Every::Instructions(n) => {
+                if steps < self.next {
+                    return;
+                }
+                let count = (steps - self.next) / n + 1;
+                self.next += count * n;
+                count
+            }
+            Every::Interval(_) => {
+                if !self.due.load(Ordering::Relaxed) {
+                    return;
+                }
+                self.due.store(false, Ordering::Relaxed);
+                1
+            }
+        };
+
+        let stack: Vec<u16> = frames.iter().map(|f| f.routine).collect();
+        *self.stacks.entry(stack).or_default() += count;
+        self.samples += count;
+
+        if self.written.elapsed() >= WRITE_EVERY {
+            self.write();
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        let mut stacks: Vec<Stack> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| Stack { stack: stack.clone(), count: *count })
+            .collect();
+        stacks
+            .sort_by(|a, b| b.count.cmp(&a.count).then(a.stack.cmp(&b.stack)));
+        Profile { every: self.every.to_string(), samples: self.samples, stacks }
+    }
+
+    fn json(&self) -> Vec<u8> {
+        let mut data = serde_json::to_vec(&self.profile()).unwrap();
+        data.push(b'\n');
+        data
+    }
+
+    // (re)write the file with the samples so far
+    pub fn write(&mut self) {
+        self.written = Instant::now();
+        if let Err(e) = saves::write(&self.file, &self.json(), true) {
+            println!("{}", e);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.write();
+    }
+}
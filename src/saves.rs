@@ -0,0 +1,310 @@
+/*!
+ * Saved VM states - loading them, storing them as deltas, and telling them
+ * apart without loading them.
+ *
+ * Every state carries a thumbnail - the room the player was last in and the
+ * tail end of the game's output - which `saves` prints for each save in a
+ * directory.
+ *
+ * A delta save is a state stored relative to a base state (a full save, a
+ * ROM, or another delta): it has everything a full save has except memory,
+ * which is only the words that differ from the base.  Loading it loads the
+ * base and applies the changes.  The base is checked against the hash it had
+ * when the delta was made.
+ *
+ * Saves are written to a temporary file next to the target and renamed into
+ * place, so an interrupted write never leaves half a save behind.
+ */
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::binsave;
+use crate::parser;
+use crate::textstate;
+use crate::validate;
+use crate::vm::VM;
+
+// characters of output kept for the excerpt
+pub const EXCERPT_LEN: usize = 200;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Thumbnail {
+    // title of the last room printed
+    pub location: Option<String>,
+    // the last EXCERPT_LEN characters of output
+    pub excerpt: String,
+    // the line being printed, to spot room titles in
+    #[serde(skip)]
+    line: String,
+}
+
+impl Thumbnail {
+    // add a character of game output
+    pub fn push(&mut self, c: char) {
+        self.excerpt.push(c);
+        if self.excerpt.chars().count() > EXCERPT_LEN {
+            self.excerpt.remove(0);
+        }
+
+        if c != '\n' {
+            self.line.push(c);
+            return;
+        }
+        if let Some(title) = parser::title(&self.line) {
+            self.location = Some(title.to_string());
+        }
+        self.line.clear();
+    }
+}
+
+// the parts of a saved state needed to list it
+#[derive(Deserialize)]
+pub struct SaveInfo {
+    // not needed, but required so other json files aren't taken for saves
+    #[serde(rename = "registers")]
+    _registers: IgnoredAny,
+    #[serde(default)]
+    pub steps: u64,
+    #[serde(default)]
+    pub thumbnail: Thumbnail,
+    // the state a delta save is relative to
+    #[serde(default)]
+    pub base: Option<String>,
+}
+
+// write a save by way of a temporary file and a rename.  an existing file
+// is only replaced with force
+pub fn write(file: &str, data: &[u8], force: bool) -> Result<(), String> {
+    if !force && fs::exists(file).unwrap_or(false) {
+        return Err(format!(
+            "{} already exists, use --force to replace it",
+            file
+        ));
+    }
+
+    let tmp = format!("{}.tmp.{}", file, process::id());
+    let res = File::create(&tmp)
+        .and_then(|mut f| f.write_all(data).and_then(|_| f.sync_all()))
+        .and_then(|_| fs::rename(&tmp, file));
+    if let Err(e) = res {
+        fs::remove_file(&tmp).ok();
+        return Err(format!("failed to write {}: {}", file, e));
+    }
+    Ok(())
+}
+
+// a file name for a save made without one, ie. save-20251221-134502.json,
+// in UTC
+pub fn timestamped_name(ext: &str) -> String {
+    let secs =
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // days since the epoch to a date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "save-{:04}{:02}{:02}-{:02}{:02}{:02}.{}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        ext
+    )
+}
+
+// true if data looks like a JSON save rather than a ROM.  a ROM can start
+// with a `{` too, but won't go on to parse
+fn is_json(file: &str, data: &[u8]) -> bool {
+    if file.ends_with(".json") {
+        return true;
+    }
+    let start = data.iter().find(|b| !b.is_ascii_whitespace());
+    start == Some(&b'{') && serde_json::from_slice::<IgnoredAny>(data).is_ok()
+}
+
+// load a raw ROM, a full save, a delta save, a binary save (see `binsave`)
+// or a text state (see `textstate`).  everything but a text state is told
+// apart by what's in the file rather than its name
+pub fn load(file: &str) -> Result<VM, String> {
+    if file.ends_with(textstate::EXTENSION) {
+        let text = fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {}: {}", file, e))?;
+        return textstate::import(file, &text);
+    }
+
+    let data = fs::read(file)
+        .map_err(|e| format!("failed to read {}: {}", file, e))?;
+    if binsave::is_binary(&data) {
+        return binsave::import(file, &data);
+    }
+    if !is_json(file, &data) {
+        validate::check(file, &data)?;
+        return Ok(VM::new(data));
+    }
+
+    let mut value: Value = serde_json::from_slice(&data)
+        .map_err(|e| format!("{}: {}", file, e))?;
+
+    if let Some(base) = value.get("base").and_then(|b| b.as_str()) {
+        let ram = apply_delta(file, base, &value)?;
+        value["ram"] = ram.into();
+    }
+
+    let mut vm: VM = serde_json::from_value(value)
+        .map_err(|e| format!("{}: {}", file, e))?;
+    vm.mark_loaded();
+    Ok(vm)
+}
+
+// the memory of a delta save - its base's with the changes applied
+fn apply_delta(
+    file: &str,
+    base: &str,
+    delta: &Value,
+) -> Result<Vec<u8>, String> {
+    let invalid = |what| format!("{}: invalid delta, bad {}", file, what);
+
+    // relative bases are relative to the delta
+    let base = match Path::new(file).parent() {
+        Some(dir) if Path::new(base).is_relative() => dir.join(base),
+        _ => PathBuf::from(base),
+    };
+    let base = base.to_string_lossy();
+    let base_vm = load(&base)?;
+
+    let hash = delta["base_hash"].as_str().ok_or(invalid("base_hash"))?;
+    if format!("{:016x}", base_vm.state_hash()) != hash {
+        return Err(format!(
+            "{}: {} has changed since the delta was made",
+            file, base
+        ));
+    }
+
+    let mut ram = base_vm.ram().clone();
+    let len = delta["ram_len"].as_u64().ok_or(invalid("ram_len"))?;
+    ram.resize(len as usize);
+
+    let changes: Vec<(u16, u16)> =
+        serde_json::from_value(delta["ram_delta"].clone())
+            .map_err(|_| invalid("ram_delta"))?;
+    for (addr, value) in changes {
+        if addr as usize * 2 + 1 >= ram.len() {
+            return Err(invalid("ram_delta"));
+        }
+        ram.set_word(addr, value);
+    }
+
+    Ok(ram.to_vec())
+}
+
+// the VM state as a delta save against the base file, which is recorded
+// relative to the directory the delta will be written to
+pub fn export_delta(vm: &VM, base: &str, file: &str) -> Result<String, String> {
+    let base_vm = load(base)?;
+
+    let dir = Path::new(file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let dir = fs::canonicalize(dir).map_err(|e| format!("{}: {}", file, e))?;
+    let base_path =
+        fs::canonicalize(base).map_err(|e| format!("{}: {}", base, e))?;
+    let base_name = match base_path.strip_prefix(&dir) {
+        Ok(p) => p.to_path_buf(),
+        Err(_) => base_path,
+    };
+
+    let ram = vm.ram();
+    let changes: Vec<(u16, u16)> = (0..ram.len() / 2)
+        .map(|addr| addr as u16)
+        .filter(|addr| base_vm.ram().word(*addr) != ram.word(*addr))
+        .map(|addr| (addr, ram.word(addr).unwrap()))
+        .collect();
+
+    let mut value = serde_json::to_value(vm).unwrap();
+    let state = value.as_object_mut().unwrap();
+    state.remove("ram");
+    state.insert("base".into(), base_name.to_string_lossy().into());
+    state.insert(
+        "base_hash".into(),
+        format!("{:016x}", base_vm.state_hash()).into(),
+    );
+    state.insert("ram_len".into(), ram.len().into());
+    state.insert("ram_delta".into(), serde_json::to_value(changes).unwrap());
+
+    Ok(serde_json::to_string(&value).unwrap())
+}
+
+// every save (.json or .snap file) in a directory, sorted by name.  files
+// that aren't VM states are skipped
+pub fn list(dir: &str) -> Result<Vec<(PathBuf, SaveInfo)>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("failed to read {}: {}", dir, e))?;
+
+    let mut saves = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        if ext != Some("json") && ext != Some(&binsave::EXTENSION[1..]) {
+            continue;
+        }
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        if let Some((steps, thumbnail)) = binsave::info(&data) {
+            let info = SaveInfo {
+                _registers: IgnoredAny,
+                steps,
+                thumbnail,
+                base: None,
+            };
+            saves.push((path, info));
+        } else if let Ok(info) = serde_json::from_slice(&data) {
+            saves.push((path, info));
+        }
+    }
+
+    saves.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(saves)
+}
+
+// print a listing of the saves in a directory
+pub fn print_list(dir: &str) -> Result<(), String> {
+    for (path, info) in list(dir)? {
+        let location = info.thumbnail.location.as_deref().unwrap_or("unknown");
+        let base = match &info.base {
+            Some(base) => format!(", delta of {}", base),
+            None => String::new(),
+        };
+        println!(
+            "\x1b[1m{}\x1b[0m  {}  ({} instructions{})",
+            path.display(),
+            location,
+            info.steps,
+            base
+        );
+        for line in info.thumbnail.excerpt.trim().lines() {
+            println!("    {}", line);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+/*!
+ * Memory scanning to find where the game keeps a value.
+ *
+ * Take a snapshot, do something in the game, then filter the candidate
+ * addresses by how their value changed since the last snapshot.  Repeat until
+ * only a handful of addresses remain.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Predicate {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(u16),
+}
+
+impl Predicate {
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        let p = match args {
+            ["changed"] => Self::Changed,
+            ["unchanged"] => Self::Unchanged,
+            ["increased"] => Self::Increased,
+            ["decreased"] => Self::Decreased,
+            ["equals", n] => match n.parse() {
+                Ok(n) => Self::Equals(n),
+                Err(_) => return Err(format!("invalid number '{}'", n)),
+            },
+            _ => return Err(format!("unknown predicate '{}'", args.join(" "))),
+        };
+        Ok(p)
+    }
+
+    pub fn matches(&self, old: u16, new: u16) -> bool {
+        match *self {
+            Self::Changed => old != new,
+            Self::Unchanged => old == new,
+            Self::Increased => new > old,
+            Self::Decreased => new < old,
+            Self::Equals(n) => new == n,
+        }
+    }
+}
+
+pub struct Scan {
+    // memory as of the last filter
+    snapshot: Vec<u16>,
+    // addresses still in the running
+    candidates: Vec<u16>,
+}
+
+impl Scan {
+    // start a scan with every address as a candidate
+    pub fn new(words: Vec<u16>) -> Self {
+        let candidates = (0..words.len() as u16).collect();
+        Self { snapshot: words, candidates }
+    }
+
+    // keep the candidates whose value moved from the snapshot to words as
+    // the predicate says, then snapshot words for the next round
+    pub fn filter(&mut self, words: Vec<u16>, p: Predicate) {
+        let old = &self.snapshot;
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            match (old.get(addr), words.get(addr)) {
+                (Some(o), Some(n)) => p.matches(*o, *n),
+                _ => false,
+            }
+        });
+        self.snapshot = words;
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    // value of addr as of the last snapshot
+    pub fn value(&self, addr: u16) -> u16 {
+        self.snapshot[addr as usize]
+    }
+}
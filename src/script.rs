@@ -0,0 +1,192 @@
+/*!
+ * Replay scripts - game input to play, with checks along the way.
+ *
+ * Every line of a script is typed at the game's prompts in turn, except for
+ * lines starting with `@`, which are directives checked when the game next
+ * asks for input (or halts):
+ *
+ * ```text
+ * take tablet
+ * use tablet
+ * @assert-output "You find yourself writing"
+ * @assert-reg 7 0
+ * ```
+ *
+ * `@assert-output` checks the output since the last line of input contains
+ * the text, `@assert-reg` checks the value of a register.
+ *
+ * `@command <step> <command>` runs a `/` command, as `/record` writes them
+ * with the instruction count they ran at.  Those run at a prompt are run
+ * when the script is played there, and those run at the debug prompt (ie.
+ * after a breakpoint) are given to the debug prompt when it comes up again,
+ * `continue` included - so a recorded session sets up the same breakpoints
+ * and edits at the same points when it's played back.
+ */
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Debug)]
+pub enum Line {
+    // a line to type, with its newline
+    Input(Vec<u8>),
+    AssertOutput(String),
+    AssertReg(u16, u16),
+    // a `/` command and the instruction count it was run at
+    Command(u64, String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    // lines still to go, with their line number in the script
+    lines: VecDeque<(usize, Line)>,
+}
+
+// read a "quoted string" with \" \\ and \n escapes
+fn quoted(s: &str) -> Result<String, String> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(format!("expected a quoted string, found '{}'", s))?;
+
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+            None => return Err("trailing '\\'".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+fn directive(s: &str) -> Result<Line, String> {
+    let (name, args) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+    let args = args.trim();
+
+    match name {
+        "@assert-output" => Ok(Line::AssertOutput(quoted(args)?)),
+        "@assert-reg" => {
+            let fields: Vec<_> = args.split_whitespace().collect();
+            let [reg, value] = fields[..] else {
+                return Err("usage: @assert-reg <register> <value>".to_string());
+            };
+            let reg: u16 = match reg.parse() {
+                Ok(reg) if reg < 8 => reg,
+                _ => return Err(format!("invalid register '{}'", reg)),
+            };
+            let value: u16 = match value.parse() {
+                Ok(value) if value < 32768 => value,
+                _ => return Err(format!("invalid value '{}'", value)),
+            };
+            Ok(Line::AssertReg(reg, value))
+        }
+        "@command" => {
+            let (step, cmd) =
+                args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            match step.parse() {
+                Ok(step) if !cmd.trim().is_empty() => {
+                    Ok(Line::Command(step, cmd.trim().to_string()))
+                }
+                _ => Err("usage: @command <step> <command>".to_string()),
+            }
+        }
+        name => Err(format!("unknown directive '{}'", name)),
+    }
+}
+
+impl Script {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut lines = VecDeque::new();
+
+        for (i, line) in data.split_inclusive(|c| *c == b'\n').enumerate() {
+            if line.starts_with(b"@") {
+                let s = String::from_utf8_lossy(line);
+                let d = directive(s.trim())
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?;
+                lines.push_back((i + 1, d));
+            } else {
+                lines.push_back((i + 1, Line::Input(line.to_vec())));
+            }
+        }
+
+        Ok(Self { lines })
+    }
+
+    // just the game input, with the directives dropped
+    pub fn input(&self) -> Vec<u8> {
+        self.lines
+            .iter()
+            .filter_map(|(_, line)| match line {
+                Line::Input(input) => Some(input.as_slice()),
+                _ => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    // the next line to play and its line number
+    pub fn next_line(&mut self) -> Option<(usize, Line)> {
+        self.lines.pop_front()
+    }
+
+    // the next line if it's a command, and its line number
+    pub fn next_command(&mut self) -> Option<(usize, u64, String)> {
+        match self.lines.front() {
+            Some((_, Line::Command(..))) => match self.lines.pop_front() {
+                Some((n, Line::Command(step, cmd))) => Some((n, step, cmd)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// check a directive against the output since the last input and the
+// registers, returning what went wrong
+pub fn check(
+    line: &Line,
+    output: &str,
+    registers: &[u16; 8],
+) -> Result<(), String> {
+    match line {
+        Line::Input(_) | Line::Command(..) => Ok(()),
+        Line::AssertOutput(text) => {
+            if output.contains(text.as_str()) {
+                return Ok(());
+            }
+            let mut diff = format!(
+                "@assert-output {:?}: output doesn't contain it\n--- expected\n+++ output\n",
+                text
+            );
+            for line in text.lines() {
+                diff += &format!("-{}\n", line);
+            }
+            for line in output.lines() {
+                diff += &format!("+{}\n", line);
+            }
+            Err(diff.trim_end().to_string())
+        }
+        Line::AssertReg(reg, value) => {
+            let actual = registers[*reg as usize];
+            if actual == *value {
+                return Ok(());
+            }
+            Err(format!(
+                "@assert-reg {} {}: register {} is {}\n-{}\n+{}",
+                reg, value, reg, actual, value, actual
+            ))
+        }
+    }
+}
@@ -0,0 +1,2785 @@
+/*!
+ * The interactive frontend to the VM.
+ *
+ * The shell owns a VM and sits between it and the terminal: it reads a line
+ * whenever the game is waiting for input, prints the game's output (through
+ * any output filters), echoes the input the game reads in color, and handles
+ * `/` commands and the debugger prompt.
+ *
+ * When reading a terminal, lines are read with a line editor whose history
+ * is the `/` command history (see `history`).
+ */
+
+use regex::Regex;
+use rustyline::error::ReadlineError;
+use rustyline::{
+    Cmd, ConditionalEventHandler, DefaultEditor, EditMode, EventContext,
+    EventHandler, RepeatCount,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing::trace;
+
+use crate::autosave::Autosave;
+use crate::binsave;
+use crate::codes::{self, Progress};
+use crate::debugger::{Event, Member};
+use crate::disasm;
+use crate::explain;
+use crate::filters::Pipeline;
+use crate::gc::{self, Policy};
+use crate::history::History;
+use crate::journal::JOURNAL_MAX;
+use crate::keys::{self, Keys, Mode};
+use crate::logging;
+use crate::memedit::{self, Edit};
+use crate::novelty::Novelty;
+use crate::offsets;
+use crate::plugin::{self, Plugin, Plugins};
+use crate::rominfo;
+use crate::saves;
+use crate::scan::{Predicate, Scan};
+use crate::script::{self, Line, Script};
+use crate::shutdown::Shutdown;
+use crate::stats::Stats;
+use crate::symbols::Symbols;
+use crate::textstate;
+use crate::throttle::Throttle;
+use crate::timeline::{Kind, Timeline};
+use crate::tracefilter::{self, TraceFilter};
+use crate::valuehistory::Source;
+use crate::vault;
+use crate::vm::{Snapshot, VM, VmError};
+use crate::watchdog::Watchdog;
+
+// longest loop body /fast-forward will look for, in instructions
+const LOOP_BODY_MAX: u64 = 1000;
+
+// instructions /fast-forward runs before giving up on the loop ending
+const FAST_FORWARD_MAX: u64 = 1_000_000_000;
+
+// frames to show in a backtrace
+const BACKTRACE_MAX: usize = 20;
+
+// game commands that can be undone
+const UNDO_MAX: usize = 100;
+
+// commands that change the VM's memory, registers or where it is, turned
+// off with --read-only along with /search goto and plugins' commands
+const EDIT_COMMANDS: [&str; 12] = [
+    "set",
+    "edit-mem",
+    "fill",
+    "copy",
+    "loadmem",
+    "nop",
+    "jump",
+    "return",
+    "load",
+    "undo-command",
+    "stepback",
+    "rewind",
+];
+
+// instructions to run between checks of --max-ips
+const THROTTLE_EVERY: u64 = 10_000;
+
+// instructions to run between checks on whether the game is still thinking
+const WATCH_EVERY: u64 = 100_000;
+
+// lines shown either side of a /search match
+const SEARCH_CONTEXT: usize = 2;
+
+// print the remaining addresses once a scan is narrowed down to this many
+const NARROW_LIST_MAX: usize = 20;
+
+pub struct Shell {
+    vm: VM,
+    // where input is read from and game output written to, stdin and stderr
+    // when not set
+    reader: Option<Box<dyn BufRead + Send>>,
+    writer: Option<Box<dyn Write + Send>>,
+    // filters the game's output goes through on its way to the terminal
+    filters: Pipeline,
+    // codes found so far, and the current line of output to look for more in
+    progress: Option<Progress>,
+    progress_line: Vec<u8>,
+    // characters of typed input the game hasn't read yet - these are echoed
+    // in green, everything else the game reads (scripts, hooks) in red
+    typed: usize,
+    // in progress `/narrow` memory scan
+    scan: Option<Scan>,
+    // `/` commands run in this and earlier sessions
+    history: Option<History>,
+    // named groups of breakpoints and watched strings, and whether each is
+    // enabled, see `debugger`
+    groups: BTreeMap<String, (Vec<Member>, bool)>,
+    // line editor for reading the terminal, created on first use, its key
+    // bindings, and the command of the bound key that ended the last line
+    editor: Option<DefaultEditor>,
+    keys: Keys,
+    pressed: Arc<Mutex<Option<String>>>,
+    // the state at each prompt, the line typed there and how long the
+    // transcript was, most recent last
+    undo: Vec<(Snapshot, String, usize)>,
+    // replay script to play before reading any input
+    script: Script,
+    // game output since the last line of input, for script assertions
+    since_input: String,
+    // every line of input given to the game this session, and the output
+    // that came before each one
+    transcript: Vec<String>,
+    output: Vec<String>,
+    // the output each match of the last /search is in
+    search_hits: Vec<usize>,
+    // show the instruction count in the prompt
+    show_steps: bool,
+    // lines of output seen, with /novelty on
+    novelty: Option<Novelty>,
+    // loaded plugins, see `plugin`
+    plugins: Plugins,
+    // saves the game when the process is told to stop, with --checkpoint
+    shutdown: Option<Arc<Shutdown>>,
+    // saves the game every so often, with --autosave
+    autosave: Option<Autosave>,
+    // keep the journal while running freely too, with --rewind
+    rewind: bool,
+    // set while the debug prompt is open
+    debugging: bool,
+    // --max-ips and the instruction count it was last checked at, and
+    // --max-inputs
+    step_limit: Option<Throttle>,
+    step_checked: u64,
+    input_limit: Option<Throttle>,
+    // with --read-only, the file being run, which is never written over
+    read_only: Option<PathBuf>,
+    // explain each instruction as it runs, and set while /step runs
+    explain: bool,
+    stepping: bool,
+    // with /record, the file input and commands are written to as a script
+    recording: Option<(String, File)>,
+    // whether the game is thinking or waiting, for /status, --status-file and
+    // --notify, and the instruction count it was last checked at
+    watchdog: Watchdog,
+    watch_checked: u64,
+    // saves, restores and play time, for the summary at codes and the end
+    stats: Stats,
+    // inputs, rooms, saves and codes by instruction count, for /timeline
+    timeline: Timeline,
+}
+
+impl Shell {
+    pub fn new(vm: VM) -> Self {
+        let watchdog = Watchdog::new(&vm);
+        Self {
+            vm,
+            reader: None,
+            writer: None,
+            filters: Pipeline::default(),
+            progress: None,
+            progress_line: vec![],
+            typed: 0,
+            scan: None,
+            history: None,
+            groups: BTreeMap::new(),
+            editor: None,
+            keys: Keys::default(),
+            pressed: Arc::default(),
+            undo: vec![],
+            script: Script::default(),
+            since_input: String::new(),
+            transcript: vec![],
+            output: vec![],
+            search_hits: vec![],
+            show_steps: false,
+            novelty: None,
+            plugins: Plugins::default(),
+            shutdown: None,
+            autosave: None,
+            rewind: false,
+            debugging: false,
+            step_limit: None,
+            step_checked: 0,
+            input_limit: None,
+            read_only: None,
+            explain: false,
+            stepping: false,
+            recording: None,
+            watchdog,
+            watch_checked: 0,
+            stats: Stats::default(),
+            timeline: Timeline::default(),
+        }
+    }
+
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    pub fn into_vm(self) -> VM {
+        self.vm
+    }
+
+    pub fn set_reader(&mut self, reader: Box<dyn BufRead + Send>) {
+        self.reader = Some(reader);
+    }
+
+    pub fn set_writer(&mut self, writer: Box<dyn Write + Send>) {
+        self.writer = Some(writer);
+    }
+
+    pub fn add_filter(&mut self, spec: &str) -> Result<(), String> {
+        self.filters.add(spec)
+    }
+
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = Some(progress);
+    }
+
+    pub fn set_script(&mut self, script: Script) {
+        self.script = script;
+    }
+
+    pub fn set_history(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    // see `keys`
+    pub fn set_keys(&mut self, keys: Keys) {
+        self.keys = keys;
+        self.editor = None;
+    }
+
+    pub fn set_show_steps(&mut self, on: bool) {
+        self.show_steps = on;
+    }
+
+    pub fn set_shutdown(&mut self, shutdown: Arc<Shutdown>) {
+        self.shutdown = Some(shutdown);
+    }
+
+    // save to dir every so many instructions, see `autosave`
+    pub fn set_autosave(
+        &mut self,
+        dir: &str,
+        every: u64,
+    ) -> Result<(), String> {
+        self.autosave = Some(Autosave::new(dir, every, &self.vm)?);
+        Ok(())
+    }
+
+    // keep the last max instructions however they were run, for /rewind,
+    // see `journal`
+    pub fn set_rewind(&mut self, max: usize) {
+        self.vm.set_journal_max(max);
+        self.rewind = true;
+    }
+
+    // run at most ips instructions a second
+    pub fn set_step_limit(&mut self, ips: u64) {
+        self.step_limit = Some(Throttle::per_second(ips));
+        self.step_checked = self.vm.steps();
+    }
+
+    // take at most n lines of input a minute
+    pub fn set_input_limit(&mut self, n: u64) {
+        self.input_limit = Some(Throttle::per_minute(n));
+    }
+
+    // explain every instruction run.  like plugins, this needs superblocks
+    // turned off
+    pub fn set_explain(&mut self, on: bool) {
+        self.explain = on;
+        if on {
+            self.vm.set_fast(false);
+        }
+    }
+
+    // turn off the commands that change the VM, and refuse to write over
+    // file
+    pub fn set_read_only(&mut self, file: &str) -> Result<(), String> {
+        let path =
+            fs::canonicalize(file).map_err(|e| format!("{}: {}", file, e))?;
+        self.read_only = Some(path);
+        Ok(())
+    }
+
+    // keep file up to date with what the game is doing, see `watchdog`
+    pub fn set_status_file(&mut self, file: &str) {
+        self.watchdog.set_file(file, &self.vm);
+    }
+
+    // run cmd when the game stops thinking after a long time
+    pub fn set_notify(&mut self, cmd: &str) {
+        self.watchdog.set_notify(cmd);
+    }
+
+    // fails if file is the one --read-only protects
+    fn check_writable(&self, file: &str) -> Result<(), String> {
+        match &self.read_only {
+            Some(path) if fs::canonicalize(file).is_ok_and(|f| f == *path) => {
+                Err(format!("{} is read-only", file))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // write every line of input and command from now on to file, as a
+    // script that plays the session back.  the input so far goes first.  an
+    // existing file is only replaced with force
+    pub fn record(&mut self, file: &str, force: bool) -> Result<(), String> {
+        self.check_writable(file)?;
+        if !force && fs::exists(file).unwrap_or(false) {
+            return Err(format!(
+                "{} already exists, use --force to replace it",
+                file
+            ));
+        }
+        let mut f =
+            File::create(file).map_err(|e| format!("{}: {}", file, e))?;
+        f.write_all(self.transcript.concat().as_bytes())
+            .map_err(|e| format!("{}: {}", file, e))?;
+        self.recording = Some((file.to_string(), f));
+        Ok(())
+    }
+
+    fn record_line(&mut self, line: &str) {
+        let Some((file, f)) = &mut self.recording else {
+            return;
+        };
+        if let Err(e) = f.write_all(line.as_bytes()) {
+            println!("{}: {}, stopped recording", file, e);
+            self.recording = None;
+        }
+    }
+
+    fn record_command(&mut self, cmd: &str) {
+        let line = format!("@command {} {}\n", self.vm.steps(), cmd.trim());
+        self.record_line(&line);
+    }
+
+    // run a command from a script, warning if it's not where it was recorded
+    fn play_command(&mut self, n: usize, step: u64, cmd: &str) {
+        if step != self.vm.steps() {
+            println!(
+                "script line {}: /{} was recorded at step {}, running it at step {}",
+                n,
+                cmd,
+                step,
+                self.vm.steps()
+            );
+        }
+        self.command(cmd);
+    }
+
+    // true once the process has been told to stop, saving the game first
+    fn stopping(&self) -> bool {
+        let Some(shutdown) = &self.shutdown else {
+            return false;
+        };
+        if !shutdown.requested() {
+            return false;
+        }
+        shutdown.save(&self.vm);
+        true
+    }
+
+    // plugins see every instruction, so superblocks are turned off
+    pub fn add_plugin(
+        &mut self,
+        plugin: Box<dyn Plugin>,
+    ) -> Result<(), String> {
+        let name = plugin.name().to_string();
+        if !self.plugins.add(plugin) {
+            return Err(format!("plugin {} is already loaded", name));
+        }
+        self.vm.set_fast(false);
+        Ok(())
+    }
+
+    // run the next instruction, letting the plugins see it first
+    fn step(&mut self) -> Result<(), VmError> {
+        if !self.plugins.is_empty() {
+            self.plugins.step(&self.vm);
+        }
+        let explained = self.explain.then(|| explain::explain(&self.vm));
+        let steps = self.vm.steps();
+        self.vm.step()?;
+        // a breakpoint stops before the instruction runs
+        if let Some(text) = explained
+            && self.vm.steps() > steps
+        {
+            println!("{}", text);
+        }
+
+        if let Some(limit) = &mut self.step_limit {
+            // the count can go backwards with /undo and friends
+            let ran = self.vm.steps().saturating_sub(self.step_checked);
+            if ran >= THROTTLE_EVERY || self.vm.steps() < self.step_checked {
+                self.step_checked = self.vm.steps();
+                thread::sleep(limit.take(ran));
+            }
+        }
+
+        let steps = self.vm.steps();
+        if steps >= self.watch_checked + WATCH_EVERY
+            || steps < self.watch_checked
+        {
+            self.watch_checked = steps;
+            self.watchdog.update(&self.vm);
+        }
+
+        if let Some(autosave) = &mut self.autosave {
+            autosave.check(&self.vm);
+        }
+        Ok(())
+    }
+
+    // say why the instruction at the pc couldn't run and where it was
+    // called from, then give the debug prompt a chance to fix things.  false
+    // if there's no one to ask
+    fn fault(&mut self, e: &VmError) -> bool {
+        self.drain();
+        self.flush_filters();
+        println!("\x1b[31mfault: {}\x1b[0m", e);
+        self.watchdog.fault(&self.vm, e);
+        self.print_pc();
+        self.print_backtrace();
+        self.debug_prompt()
+    }
+
+    // what to show before reading a line, with the instruction count if
+    // asked for
+    fn prompt(&self, prompt: &str) -> String {
+        if !self.show_steps {
+            return prompt.to_string();
+        }
+        format!("[step {}] {}", group_digits(self.vm.steps()), prompt)
+    }
+
+    // run until the game halts or the input runs out.  fails if a script
+    // assertion isn't met
+    pub fn run(&mut self) -> Result<(), String> {
+        while !self.vm.is_halted() {
+            if self.stopping() {
+                break;
+            }
+
+            if self.vm.wants_input() {
+                // show the prompt before blocking on input
+                self.flush_filters();
+
+                if self.play_script()? {
+                    continue;
+                }
+
+                let prompt = self.prompt("");
+                self.watchdog.update(&self.vm);
+                if let Some(shutdown) = &self.shutdown {
+                    shutdown.at_prompt(&self.vm);
+                }
+                let line = self.read_line(&prompt);
+                if let Some(shutdown) = &self.shutdown {
+                    shutdown.resumed();
+                }
+                let Some(line) = line else {
+                    break;
+                };
+
+                // allow user to send commands to the shell itself
+                match line.strip_prefix('/') {
+                    Some(cmd) => {
+                        self.remember(cmd);
+                        self.command(cmd.trim());
+                    }
+                    None => self.input(&line, true),
+                }
+                continue;
+            }
+
+            // only stepped instructions can be stepped back over, unless
+            // everything can with --rewind
+            self.vm.set_journal(self.rewind);
+            if let Err(e) = self.step() {
+                // the same instruction runs again if the prompt carries on
+                if !self.fault(&e) {
+                    return Err(e.to_string());
+                }
+                continue;
+            }
+            self.drain();
+        }
+
+        self.flush_filters();
+        self.watchdog.update(&self.vm);
+
+        // check whatever the script expects of the end of the game
+        if self.vm.is_halted() {
+            self.note_room();
+            println!("{}", self.stats_summary());
+            self.play_script()?;
+        }
+        Ok(())
+    }
+
+    // run without reading the terminal until execution reaches an address
+    // (or symbol), or with "return" until the routine being run returns.
+    // input can only come from the script
+    pub fn run_to(&mut self, target: &str) -> Result<(), String> {
+        let reached: Box<dyn Fn(&VM) -> bool> = if target == "return" {
+            let depth = self.vm.frames.len();
+            if depth == 0 {
+                return Err("not in a call to return from".to_string());
+            }
+            Box::new(move |vm| vm.frames.len() < depth)
+        } else {
+            let addr = memedit::parse_addr(target, &self.vm.symbols)?;
+            Box::new(move |vm| vm.pc() == addr)
+        };
+
+        // superblocks run past addresses without stopping at them
+        let fast = self.vm.fast();
+        self.vm.set_fast(false);
+
+        let res = loop {
+            if reached(&self.vm) {
+                break Ok(());
+            }
+            if self.vm.is_halted() {
+                break Err(format!("halted before reaching {}", target));
+            }
+            if self.stopping() {
+                break Err(format!("stopped before reaching {}", target));
+            }
+            if self.vm.wants_input() {
+                self.flush_filters();
+                match self.play_script() {
+                    Ok(true) => continue,
+                    Ok(false) => {
+                        break Err(format!(
+                            "the game wants input before reaching {}, give it \
+                             in a script",
+                            target
+                        ));
+                    }
+                    Err(e) => break Err(e),
+                }
+            }
+            self.vm.set_journal(self.rewind);
+            if let Err(e) = self.step() {
+                break Err(e.to_string());
+            }
+            self.drain();
+        };
+
+        self.flush_filters();
+        self.vm.set_fast(fast);
+        if res.is_ok() {
+            println!(
+                "reached {} at {}, step {}",
+                target,
+                self.vm.pc(),
+                group_digits(self.vm.steps())
+            );
+        }
+        res
+    }
+
+    // run n instructions, from the debug prompt.  stops early if the game
+    // wants input or halts
+    fn step_by(&mut self, n: u64) {
+        // superblocks would run past the end
+        let fast = self.vm.fast();
+        self.vm.set_fast(false);
+        self.vm.set_journal(true);
+        self.stepping = true;
+
+        let end = self.vm.steps() + n;
+        while self.vm.steps() < end {
+            if self.vm.is_halted() {
+                println!("halted");
+                break;
+            }
+            if self.vm.wants_input() {
+                println!("the game is waiting for input");
+                break;
+            }
+            if let Err(e) = self.step() {
+                self.drain();
+                println!("\x1b[31mfault: {}\x1b[0m", e);
+                self.watchdog.fault(&self.vm, &e);
+                break;
+            }
+            self.drain();
+        }
+
+        self.stepping = false;
+        self.vm.set_fast(fast);
+        self.flush_filters();
+
+        // without the explanations, say where it stopped
+        if !self.explain {
+            self.print_pc();
+        }
+    }
+
+    // the instruction about to run
+    fn print_pc(&self) {
+        let pc = self.vm.pc();
+        if let Some(ins) = self.vm.disassemble(pc..pc + 4).first()
+            && ins.addr == pc
+        {
+            println!("{:>5}  {}", ins.addr, ins);
+        }
+    }
+
+    // take back up to n stepped instructions, see `journal`.  false if
+    // there were none
+    fn step_back(&mut self, n: usize) -> bool {
+        let mut undone = 0;
+        while undone < n && self.vm.step_back() {
+            undone += 1;
+        }
+        if undone == 0 {
+            match self.rewind {
+                true => println!("nothing to step back over"),
+                false => println!(
+                    "nothing to step back over, only instructions run with \
+                     /step or /animate can be (up to {}) without --rewind",
+                    JOURNAL_MAX
+                ),
+            }
+            return false;
+        }
+        if undone < n {
+            println!("stepped back {}, as far as the journal goes", undone);
+        }
+        self.print_pc();
+        true
+    }
+
+    // run up to n instructions at hz a second, showing each one with the
+    // registers it changed and what it did to the stack.  stops early like
+    // /step does
+    fn animate(&mut self, hz: f64, n: u64) {
+        let delay = Duration::from_secs_f64(1.0 / hz);
+        let fast = self.vm.fast();
+        self.vm.set_fast(false);
+        self.vm.set_journal(true);
+        self.stepping = true;
+
+        let end = self.vm.steps().saturating_add(n);
+        while self.vm.steps() < end {
+            if self.vm.is_halted() {
+                println!("halted");
+                break;
+            }
+            if self.vm.wants_input() {
+                println!("the game is waiting for input");
+                break;
+            }
+
+            let pc = self.vm.pc();
+            let ins = self.vm.disassemble(pc..pc + 4).into_iter().next();
+            let registers = self.vm.registers();
+            let old_stack = self.vm.stack.clone();
+            let steps = self.vm.steps();
+            if let Err(e) = self.step() {
+                self.drain();
+                println!("\x1b[31mfault: {}\x1b[0m", e);
+                self.watchdog.fault(&self.vm, &e);
+                break;
+            }
+            if self.vm.steps() == steps {
+                // stopped at a breakpoint, the next step runs it
+                self.drain();
+                continue;
+            }
+
+            // changed registers in yellow, pushes in green, pops in red
+            let mut changes = String::new();
+            for (r, (old, new)) in
+                registers.iter().zip(self.vm.registers()).enumerate()
+            {
+                if *old != new {
+                    changes += &format!(
+                        "  \x1b[33mr{}={}\x1b[0m (was {})",
+                        r, new, old
+                    );
+                }
+            }
+            let stack = &self.vm.stack;
+            for value in stack.iter().skip(old_stack.len()) {
+                changes += &format!("  \x1b[32mpush {}\x1b[0m", value);
+            }
+            for value in old_stack.iter().skip(stack.len()).rev() {
+                changes += &format!("  \x1b[31mpop {}\x1b[0m", value);
+            }
+
+            // with /explain on the instruction has already been shown
+            if !self.explain {
+                let ins = ins.filter(|i| i.addr == pc).map(|i| i.to_string());
+                let ins = ins.unwrap_or_default();
+                let line = format!("{:>5}  {:<20}{}", pc, ins, changes);
+                println!("{}", line.trim_end());
+            } else if !changes.is_empty() {
+                println!("     {}", changes);
+            }
+
+            self.drain();
+            thread::sleep(delay);
+        }
+
+        self.stepping = false;
+        self.vm.set_fast(fast);
+        self.flush_filters();
+    }
+
+    // check the script's directives up to its next line of input and type
+    // that.  false once the script is done
+    fn play_script(&mut self) -> Result<bool, String> {
+        while let Some((n, line)) = self.script.next_line() {
+            match line {
+                Line::Input(input) => {
+                    let input = String::from_utf8_lossy(&input).into_owned();
+                    self.input(&input, false);
+                    return Ok(true);
+                }
+                Line::Command(step, cmd) => self.play_command(n, step, &cmd),
+                line => {
+                    script::check(&line, &self.since_input, &self.vm.registers)
+                        .map_err(|e| {
+                            format!(
+                                "script line {}: assertion failed: {}",
+                                n, e
+                            )
+                        })?;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    // remember the state as it is now, to go back to with /undo-command
+    fn save_undo(&mut self, label: String) {
+        if self.undo.len() == UNDO_MAX {
+            self.undo.remove(0);
+        }
+        self.undo.push((self.vm.snapshot(), label, self.transcript.len()));
+    }
+
+    // give the game a line of input, remembering where it was typed to undo
+    // it later
+    fn input(&mut self, line: &str, typed: bool) {
+        if let Some(limit) = &mut self.input_limit {
+            let wait = limit.take(1);
+            if !wait.is_zero() {
+                println!(
+                    "input limit reached, waiting {:.1}s",
+                    wait.as_secs_f64()
+                );
+                thread::sleep(wait);
+            }
+        }
+        self.save_undo(line.trim_end().to_string());
+        self.note_room();
+        self.timeline.push(self.vm.steps(), Kind::Input, line.trim_end());
+        self.transcript.push(line.to_string());
+        self.output.push(self.since_input.clone());
+        self.record_line(line);
+        self.plugins.input(line);
+
+        if typed {
+            self.typed += line.len();
+        }
+        self.since_input.clear();
+        self.vm.queue_input(line.as_bytes());
+        self.watchdog.update(&self.vm);
+    }
+
+    // pass along whatever the last step produced
+    fn drain(&mut self) {
+        let output = self.vm.take_output();
+        for c in output {
+            self.since_input.push(c as char);
+            self.plugins.output(c);
+            if self.progress.is_some() {
+                self.check_progress(c);
+            }
+            if self.filters.is_empty() {
+                self.write_output(&(c as char).to_string());
+            } else if let Some(line) = self.filters.push(c) {
+                self.write_output(&line);
+            }
+            if c == b'\n' && self.novelty.is_some() {
+                self.check_novelty();
+            }
+        }
+
+        for c in self.vm.take_echo() {
+            let color = if self.typed > 0 {
+                self.typed -= 1;
+                32
+            } else {
+                31
+            };
+            self.write_output(&format!("\x1b[{}m{}\x1b[0m", color, c as char));
+        }
+
+        let events = self.vm.take_events();
+        if events.is_empty() {
+            return;
+        }
+
+        let mut over_budget = false;
+        for event in &events {
+            match event {
+                Event::Breakpoint(b) => {
+                    println!(
+                        "breakpoint {} hit at {} ({} hits)",
+                        b.id, b.addr, b.hits
+                    );
+                }
+                Event::OverBudget { frame, ran, budget } => {
+                    println!(
+                        "budget exceeded: call to {} from {} has run {} instructions (budget {})",
+                        frame.routine, frame.return_addr, ran, budget
+                    );
+                    over_budget = true;
+                }
+                Event::StringChanged(addr, value) => {
+                    print_watched_string(*addr, value);
+                }
+                Event::Debug(line) => println!("\x1b[2mdebug: {}\x1b[0m", line),
+                Event::StackImbalance { frame, ret, extra } => {
+                    let name = self.vm.routine_name(frame.routine);
+                    if *extra > 0 {
+                        println!(
+                            "stack imbalance: call to {} from {} returned at {} leaving {} extra values",
+                            name, frame.return_addr, ret, extra
+                        );
+                    } else {
+                        println!(
+                            "stack imbalance: call to {} from {} returned at {} having popped {} of its caller's values",
+                            name, frame.return_addr, ret, -extra
+                        );
+                    }
+                }
+            }
+        }
+
+        if over_budget {
+            self.print_backtrace();
+        }
+        // /step carries on stepping from the prompt it was run at
+        if events.iter().any(|e| e.is_stop()) && !self.stepping {
+            self.debug_prompt();
+        }
+    }
+
+    // a save was written to file
+    fn saved(&mut self, file: &str) {
+        self.stats.saved();
+        self.timeline.push(self.vm.steps(), Kind::Save, file);
+    }
+
+    // put the room the game is in on the timeline, if it's a new one
+    fn note_room(&mut self) {
+        let Some(room) = &self.vm.thumbnail().location else {
+            return;
+        };
+        if self.timeline.room() != Some(room) {
+            let room = room.clone();
+            self.timeline.push(self.vm.steps(), Kind::Room, &room);
+        }
+    }
+
+    // see `stats`
+    fn stats_summary(&self) -> String {
+        let codes =
+            self.progress.as_ref().map(|p| (p.found().len(), codes::TOTAL));
+        self.stats.summary(self.vm.steps(), self.transcript.len(), codes)
+    }
+
+    // look for new codes in each line of output
+    fn check_progress(&mut self, c: u8) {
+        if c != b'\n' {
+            self.progress_line.push(c);
+            return;
+        }
+
+        let line = String::from_utf8_lossy(&self.progress_line).into_owned();
+        self.progress_line.clear();
+
+        for code in codes::find_codes(&line) {
+            let progress = self.progress.as_mut().unwrap();
+            if progress.add(&code) {
+                println!(
+                    "found code {}/{}, saved to {}",
+                    progress.found().len(),
+                    codes::TOTAL,
+                    progress.file()
+                );
+                self.save_milestone(&code);
+                self.timeline.push(self.vm.steps(), Kind::Code, &code);
+                println!("{}", self.stats_summary());
+            }
+        }
+    }
+
+    // point out the line just finished if it's the first time it's been
+    // printed
+    fn check_novelty(&mut self) {
+        let text = self.since_input.trim_end_matches('\n');
+        let line = text.rsplit('\n').next().unwrap_or(text);
+        if self.novelty.as_mut().unwrap().is_new(line) {
+            println!("\x1b[1m^ new text\x1b[0m");
+        }
+    }
+
+    // keep the state a code was found in, and the input that got there, in
+    // a codes/ directory next to the progress file.  the input can be
+    // replayed as a script, and the state loaded to carry on from there
+    fn save_milestone(&self, code: &str) {
+        let progress = self.progress.as_ref().unwrap();
+        let dir = Path::new(progress.file())
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("codes");
+        if let Err(e) = fs::create_dir_all(&dir) {
+            println!("failed to create {}: {}", dir.display(), e);
+            return;
+        }
+
+        let name = format!("{}-{}", progress.found().len(), code);
+        let state = dir.join(format!("{}.json", name));
+        let script = dir.join(format!("{}.txt", name));
+        let state = state.to_string_lossy();
+        let script = script.to_string_lossy();
+
+        let data = serde_json::to_string(&self.vm).unwrap();
+        let res = saves::write(&state, data.as_bytes(), true).and_then(|_| {
+            saves::write(&script, self.transcript.concat().as_bytes(), true)
+        });
+        match res {
+            Ok(()) => println!("state saved to {}, input to {}", state, script),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    // print any partial line held back by the output filters
+    fn flush_filters(&mut self) {
+        if !self.filters.is_empty() {
+            let s = self.filters.flush();
+            self.write_output(&s);
+        }
+    }
+
+    // read a line of input after showing the prompt, None at end of input
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        if self.reader.is_none() && io::stdin().is_terminal() {
+            return self.edit_line(prompt);
+        }
+
+        self.write_output(prompt);
+
+        let mut line = String::new();
+        let n = match &mut self.reader {
+            Some(r) => r.read_line(&mut line).unwrap(),
+            None => io::stdin().read_line(&mut line).unwrap(),
+        };
+        (n > 0).then_some(line)
+    }
+
+    // read a line from the terminal with the line editor
+    fn edit_line(&mut self, prompt: &str) -> Option<String> {
+        if self.editor.is_none() {
+            let mode = match self.keys.mode {
+                Mode::Emacs => EditMode::Emacs,
+                Mode::Vi => EditMode::Vi,
+            };
+            let config = rustyline::Config::builder().edit_mode(mode).build();
+            let mut editor = DefaultEditor::with_config(config).unwrap();
+            for (key, cmd) in &self.keys.bindings {
+                let handler = BoundKey {
+                    cmd: cmd.clone(),
+                    pressed: self.pressed.clone(),
+                };
+                editor.bind_sequence(
+                    keys::parse_key(key).unwrap(),
+                    EventHandler::Conditional(Box::new(handler)),
+                );
+            }
+            if let Some(history) = &self.history {
+                for line in history.entries() {
+                    editor.add_history_entry(line.as_str()).unwrap();
+                }
+            }
+            self.editor = Some(editor);
+        }
+
+        let line = self.editor.as_mut().unwrap().readline(prompt);
+        // a bound key ends the line with its command instead
+        if let Some(cmd) = self.pressed.lock().unwrap().take() {
+            return Some(format!("/{}\n", cmd));
+        }
+        match line {
+            Ok(line) => Some(line + "\n"),
+            // ctrl-c abandons the line
+            Err(ReadlineError::Interrupted) => Some("\n".to_string()),
+            Err(ReadlineError::Eof) => None,
+            Err(e) => panic!("failed to read line: {}", e),
+        }
+    }
+
+    // add a command (without its slash) to the history
+    fn remember(&mut self, cmd: &str) {
+        let line = format!("/{}", cmd.trim());
+        if line == "/" {
+            return;
+        }
+        if let Some(history) = &mut self.history {
+            history.add(&line);
+        }
+        if let Some(editor) = &mut self.editor {
+            editor.add_history_entry(line).unwrap();
+        }
+    }
+
+    // write game output to the terminal (or wherever it was sent)
+    fn write_output(&mut self, s: &str) {
+        match &mut self.writer {
+            Some(w) => {
+                w.write_all(s.as_bytes()).unwrap();
+                w.flush().unwrap();
+            }
+            None => eprint!("{}", s),
+        }
+    }
+
+    // read commands (with or without the leading slash) until told to
+    // continue.  false if the input closed first
+    fn debug_prompt(&mut self) -> bool {
+        let debugging = std::mem::replace(&mut self.debugging, true);
+        let res = self.debug_loop();
+        self.debugging = debugging;
+        res
+    }
+
+    fn debug_loop(&mut self) -> bool {
+        loop {
+            // a recorded session carries on as it did when recorded
+            if let Some((n, step, cmd)) = self.script.next_command() {
+                if cmd == "continue" {
+                    self.record_command(&cmd);
+                    return true;
+                }
+                self.play_command(n, step, &cmd);
+                continue;
+            }
+
+            let prompt = self.prompt("(debug) ");
+            let Some(line) = self.read_line(&prompt) else {
+                // input closed, nothing more to ask
+                return false;
+            };
+
+            let line = line.trim();
+            let line = line.strip_prefix('/').unwrap_or(line);
+            match line {
+                "" => continue,
+                "continue" | "c" => {
+                    self.record_command("continue");
+                    return true;
+                }
+                cmd => {
+                    self.remember(cmd);
+                    self.command(cmd);
+                }
+            }
+        }
+    }
+
+    // read the vault grid out of memory and write it in the format the orb
+    // solver reads, see `vault` for the layout it's assumed to have
+    fn export_vault(&self, file: &str, force: bool) -> Result<(), String> {
+        let Some(base) = self.vm.symbols.get("vault_grid") else {
+            return Err("no vault_grid offset for this ROM: find the grid, \
+                 then add it with /symbols or /offsets save"
+                .to_string());
+        };
+        let grid = vault::grid(self.vm.ram(), base)
+            .map_err(|e| format!("vault_grid {}: {}", base, e))?;
+        self.check_writable(file)?;
+        saves::write(file, grid.as_bytes(), force)
+    }
+
+    // print the stack from the top down, split into the frames of the
+    // routines that own each part of it.  return addresses pushed by `call`
+    // are highlighted to tell them apart from data
+    fn print_stack(&self) {
+        let vm = &self.vm;
+        let mut end = vm.stack.len();
+
+        for (i, frame) in vm.frames.iter().enumerate().rev() {
+            println!(
+                "\x1b[1mframe {}: {}\x1b[0m",
+                i + 1,
+                vm.routine_name(frame.routine)
+            );
+            for depth in (frame.depth..end).rev() {
+                if depth == frame.depth {
+                    println!(
+                        "  [{:>4}] \x1b[33m{:<5}\x1b[0m return address",
+                        depth, vm.stack[depth]
+                    );
+                } else {
+                    println!("  [{:>4}] {}", depth, vm.stack[depth]);
+                }
+            }
+            end = frame.depth.min(end);
+        }
+
+        println!("\x1b[1mtop level\x1b[0m");
+        for depth in (0..end).rev() {
+            println!("  [{:>4}] {}", depth, vm.stack[depth]);
+        }
+    }
+
+    // run a tight loop starting at the current address to completion as fast
+    // as possible - no logging, hooks, breakpoints or budgets.  only loops
+    // that stay away from i/o, memory writes and calls qualify.  a loop that
+    // comes back around with the state unchanged never ends, so that is
+    // reported instead of run
+    fn fast_forward(&mut self, max: u64) {
+        let vm = &mut self.vm;
+        let start = vm.addr;
+        let words = vm.ram.words();
+        let hash = vm.state_hash();
+
+        let hooks = std::mem::take(&mut vm.hooks);
+        let breakpoints = std::mem::take(&mut vm.breakpoints);
+        let frames = std::mem::take(&mut vm.frames);
+        let level = logging::level();
+        logging::set_level(LevelFilter::OFF);
+
+        // go around once to find the loop body
+        let mut body = BTreeSet::new();
+        let mut problem = None;
+        let begin = vm.steps;
+        while vm.steps - begin < LOOP_BODY_MAX {
+            match disasm::decode(&words, vm.addr) {
+                Some(ins) if ![0, 16, 17, 18, 19, 20].contains(&ins.opcode) => {
+                }
+                Some(ins) => {
+                    problem = Some(format!("{} at {}", ins, vm.addr));
+                    break;
+                }
+                None => {
+                    problem = Some(format!("bad instruction at {}", vm.addr));
+                    break;
+                }
+            }
+            body.insert(vm.addr);
+            if let Err(e) = vm.step() {
+                problem = Some(e.to_string());
+                break;
+            }
+            if vm.addr == start {
+                break;
+            }
+        }
+
+        if problem.is_none() && vm.addr != start {
+            problem = Some(format!(
+                "didn't get back to {} within {} instructions",
+                start, LOOP_BODY_MAX
+            ));
+        }
+
+        let spins = problem.is_none() && vm.state_hash() == hash;
+
+        if problem.is_none() && !spins {
+            while body.contains(&vm.addr) && vm.steps - begin < max {
+                if vm.step().is_err() {
+                    break;
+                }
+            }
+        }
+
+        logging::set_level(level);
+        vm.hooks = hooks;
+        vm.breakpoints = breakpoints;
+        vm.frames = frames;
+
+        if let Some(problem) = problem {
+            println!("not a tight loop: {}", problem);
+        } else if spins {
+            println!(
+                "loop at {} ({} instructions) changes nothing, it will spin forever",
+                start,
+                vm.steps - begin
+            );
+        } else if body.contains(&vm.addr) {
+            println!(
+                "still looping after {} instructions, now at {}",
+                vm.steps - begin,
+                vm.addr
+            );
+        } else {
+            println!(
+                "fast-forwarded {} instructions, left the loop at {}",
+                vm.steps - begin,
+                vm.addr
+            );
+        }
+    }
+
+    // /group [create <name> <member>... | enable|disable|delete <name>]
+    fn group_command(&mut self, args: &[&str]) {
+        match args {
+            [] => {
+                if self.groups.is_empty() {
+                    println!("no groups, see /group create");
+                }
+                for (name, (members, enabled)) in &self.groups {
+                    let members: Vec<String> =
+                        members.iter().map(|m| m.to_string()).collect();
+                    println!(
+                        "{}: {}{}",
+                        name,
+                        members.join(" "),
+                        if *enabled { "" } else { " (disabled)" }
+                    );
+                }
+            }
+            ["create", name, members @ ..] if !members.is_empty() => {
+                let mut group = vec![];
+                for m in members {
+                    match Member::parse(m) {
+                        Some(Member::Breakpoint(id))
+                            if self.vm.breakpoints.get(id).is_none() =>
+                        {
+                            println!("no breakpoint {}", id);
+                            return;
+                        }
+                        Some(member) => group.push(member),
+                        None => {
+                            println!(
+                                "{}: expected b<id> for a breakpoint or \
+                                 w<addr> for a watched string",
+                                m
+                            );
+                            return;
+                        }
+                    }
+                }
+                self.groups.insert(name.to_string(), (group, true));
+                println!("created group {}", name);
+            }
+            [action @ ("enable" | "disable" | "delete"), name] => {
+                let Some((members, enabled)) = self.groups.get_mut(*name)
+                else {
+                    println!("no group {}", name);
+                    return;
+                };
+                if *action == "delete" {
+                    self.groups.remove(*name);
+                    println!("deleted group {}", name);
+                    return;
+                }
+
+                let on = *action == "enable";
+                *enabled = on;
+                for member in members.iter() {
+                    match *member {
+                        Member::Breakpoint(id) => {
+                            if !self.vm.breakpoints.set_enabled(id, on) {
+                                println!("breakpoint {} has been deleted", id);
+                            }
+                        }
+                        // disabling a string stops watching it, enabling it
+                        // starts again from its value now
+                        Member::String(addr) => {
+                            let watched = &mut self.vm.watched_strings;
+                            watched.retain(|(a, _)| *a != addr);
+                            if on {
+                                let value = self.vm.decode_string(addr);
+                                self.vm.watched_strings.push((addr, value));
+                            }
+                        }
+                    }
+                }
+                println!("{}d group {}", action, name);
+            }
+            _ => println!(
+                "usage: /group [create <name> <b<id>|w<addr>>... | \
+                 enable|disable|delete <name>]"
+            ),
+        }
+    }
+
+    // the active calls, innermost first
+    fn print_backtrace(&self) {
+        let vm = &self.vm;
+        let frames = vm.frames.iter().enumerate().rev();
+        for (i, frame) in frames.clone().take(BACKTRACE_MAX) {
+            // the arguments it was called with, once /arity knows them
+            let args = vm.arity().and_then(|arity| {
+                let inferred = arity.routines().get(&frame.routine)?;
+                Some(format!(" ({})", inferred.describe_args(arity.entry(i)?)))
+            });
+            println!(
+                "  frame {:<4} {}{} returns to {} ({} instructions)",
+                i + 1,
+                vm.routine_name(frame.routine),
+                args.unwrap_or_default(),
+                frame.return_addr,
+                vm.steps - frame.entered
+            );
+        }
+        if vm.frames.len() > BACKTRACE_MAX {
+            println!("  ... {} more frames", vm.frames.len() - BACKTRACE_MAX);
+        }
+    }
+
+    // disassemble count instructions from the current ram starting at addr.
+    // instructions containing words modified since load are highlighted
+    fn print_disassembly(&self, addr: u16, count: usize) {
+        let words = self.vm.ram.words();
+        let mut addr = addr;
+
+        for _ in 0..count {
+            if addr as usize >= words.len() {
+                break;
+            }
+
+            let (text, len) = match disasm::decode(&words, addr) {
+                Some(ins) => (ins.to_string(), ins.len() as u16),
+                None => (format!(".word {}", words[addr as usize]), 1),
+            };
+
+            let modified = (addr..addr + len).any(|a| self.vm.modified(a));
+            let marker = if addr == self.vm.addr { "=>" } else { "  " };
+            if modified {
+                println!("{} {:>5} \x1b[7m{}\x1b[0m *", marker, addr, text);
+            } else {
+                println!("{} {:>5} {}", marker, addr, text);
+            }
+
+            addr += len;
+        }
+    }
+
+    // `start..end` or `start..=end`, of addresses or symbols
+    fn parse_range(&self, s: &str) -> Result<Range<u16>, String> {
+        let symbols = &self.vm.symbols;
+        let (start, end, inclusive) = match s.split_once("..") {
+            Some((start, end)) => match end.strip_prefix('=') {
+                Some(end) => (start, end, true),
+                None => (start, end, false),
+            },
+            None => return Err(format!("invalid range '{}'", s)),
+        };
+        let start = memedit::parse_addr(start, symbols)?;
+        let end = memedit::parse_addr(end, symbols)? + inclusive as u16;
+        if end < start {
+            return Err(format!("invalid range '{}'", s));
+        }
+        Ok(start..end)
+    }
+
+    // /find-code <expr> lists the instructions in memory matching a trace
+    // filter expression, see `tracefilter`.  `addr` limits the range
+    fn find_code(&self, args: &[&str]) {
+        let filter = match TraceFilter::parse(&args.join(" ")) {
+            Ok(filter) => filter,
+            Err(e) => {
+                println!("usage: /find-code <expr>: {}", e);
+                return;
+            }
+        };
+
+        let found: Vec<_> = self
+            .vm
+            .disassemble(0..32768)
+            .into_iter()
+            .filter(|ins| {
+                filter.matches(&tracefilter::Step::of_instruction(ins))
+            })
+            .collect();
+        for ins in &found {
+            match self.vm.symbols.name_of(ins.addr) {
+                Some(name) => println!("{:>5} {}  <- {}", ins.addr, ins, name),
+                None => println!("{:>5} {}", ins.addr, ins),
+            }
+        }
+        println!("{} found", found.len());
+    }
+
+    // /offsets shows what the database has for this ROM, /offsets save
+    // [description] records the current symbols as its offsets
+    fn offsets(&self, args: &[&str]) {
+        let info = rominfo::identify(self.vm.image());
+        match args {
+            [] => {
+                let db = match offsets::Database::load() {
+                    Ok(db) => db,
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
+                    }
+                };
+                println!("{}", rominfo::describe_offsets(&db, &info));
+                if let Some(known) = db.find(&info) {
+                    for (name, addr) in known.symbols.iter() {
+                        println!("  {} {}", name, addr);
+                    }
+                }
+            }
+            ["save", description @ ..] => {
+                let Some(file) = offsets::Database::default_file() else {
+                    println!("no offsets file, set $SYNACOR_OFFSETS");
+                    return;
+                };
+                if self.vm.symbols.is_empty() {
+                    println!("no symbols to save, see /symbols");
+                    return;
+                }
+                let description = description.join(" ");
+                match offsets::record(
+                    &file,
+                    &info.md5,
+                    &description,
+                    &self.vm.symbols,
+                ) {
+                    Ok(()) => println!(
+                        "saved {} offsets for rom {} to {}",
+                        self.vm.symbols.len(),
+                        info.md5,
+                        file
+                    ),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            _ => println!("usage: /offsets [save [description]]"),
+        }
+    }
+
+    // page through memory and patch it, see `memedit`.  every edit can be
+    // undone with /undo-command like a game command
+    fn edit_memory(&mut self, start: u16) {
+        let mut start = start - start % memedit::COLUMNS;
+        let page = memedit::COLUMNS * memedit::ROWS;
+        print!("{}", memedit::render(&self.vm, start));
+
+        loop {
+            let Some(line) = self.read_line("mem> ") else {
+                return;
+            };
+            match memedit::parse(&line, &self.vm.symbols) {
+                Ok(Edit::Next) => start = (start + page).min(32768 - page),
+                Ok(Edit::Prev) => start = start.saturating_sub(page),
+                Ok(Edit::Goto(addr)) => start = addr - addr % memedit::COLUMNS,
+                Ok(Edit::Write(addr, values)) => {
+                    if addr as usize + values.len() > 32768 {
+                        println!("write past the end of memory");
+                        continue;
+                    }
+                    self.save_undo(format!("/edit-mem {}", line.trim()));
+                    if let Err(e) = self.vm.write_words(addr, &values) {
+                        println!("{}", e);
+                        self.undo.pop();
+                    }
+                }
+                Ok(Edit::Undo) => {
+                    if !self
+                        .undo
+                        .last()
+                        .is_some_and(|(_, l, _)| l.starts_with("/edit-mem"))
+                    {
+                        println!("no edit to undo");
+                        continue;
+                    }
+                    self.undo_command(1);
+                }
+                Ok(Edit::Quit) => return,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            }
+            print!("{}", memedit::render(&self.vm, start));
+        }
+    }
+
+    // carry on from a save (or a ROM) instead, keeping the breakpoints and
+    // such.  it can be undone with /undo-command like a game command
+    fn load(&mut self, file: &str) {
+        let vm = match saves::load(file) {
+            Ok(vm) => vm,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        self.save_undo(format!("/load {}", file));
+        self.stats.restored(0);
+        self.vm.restore(vm.snapshot());
+        self.typed = 0;
+
+        match &self.vm.thumbnail().location {
+            Some(location) => println!("loaded {}, at {}", file, location),
+            None => println!(
+                "loaded {}, at step {}",
+                file,
+                group_digits(self.vm.steps())
+            ),
+        }
+    }
+
+    // roll the game back to the prompt n game commands ago
+    fn undo_command(&mut self, n: usize) {
+        if n == 0 || n > self.undo.len() {
+            println!("can undo up to {} commands", self.undo.len());
+            return;
+        }
+
+        let undone = self.undo.split_off(self.undo.len() - n);
+        for (_, line, _) in undone.iter().rev() {
+            println!("undid \"{}\"", line.escape_debug());
+        }
+
+        self.stats.restored(n);
+        let (snapshot, _, typed) = undone.into_iter().next().unwrap();
+        self.vm.restore(snapshot);
+        self.timeline.rewind(self.vm.steps());
+        self.transcript.truncate(typed);
+        self.since_input = self.output.get(typed).cloned().unwrap_or_default();
+        self.output.truncate(typed);
+        self.typed = 0;
+
+        match &self.vm.thumbnail().location {
+            Some(location) => println!("back at {}", location),
+            None => println!("back at step {}", self.vm.steps()),
+        }
+    }
+
+    // /search [-C n] <regex> lists the lines the game has printed this
+    // session that match, with n lines either side.  /search goto <n> rolls
+    // back to the prompt just after match n was printed, if it's still in
+    // the undo history
+    fn search(&mut self, args: &str) {
+        if let Some(n) = args.strip_prefix("goto ") {
+            if self.read_only.is_some() {
+                println!("/search goto is turned off by --read-only");
+                return;
+            }
+            match n.trim().parse::<usize>() {
+                Ok(n) => self.search_goto(n),
+                Err(_) => println!("usage: /search goto <n>"),
+            }
+            return;
+        }
+
+        let (context, pattern) = match args.strip_prefix("-C ") {
+            Some(rest) => {
+                let (n, pattern) =
+                    rest.trim().split_once(' ').unwrap_or((rest, ""));
+                match n.parse() {
+                    Ok(n) => (n, pattern.trim()),
+                    Err(_) => {
+                        println!("usage: /search [-C n] <regex>");
+                        return;
+                    }
+                }
+            }
+            None => (SEARCH_CONTEXT, args),
+        };
+        if pattern.is_empty() {
+            println!("usage: /search [-C n] <regex> | goto <n>");
+            return;
+        }
+        let re = match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+
+        // the output before each input, then what's been printed since
+        let chunks = self.output.iter().chain([&self.since_input]);
+        let mut hits = vec![];
+        for (i, chunk) in chunks.enumerate() {
+            let lines: Vec<&str> = chunk.lines().collect();
+            for (j, line) in lines.iter().enumerate() {
+                if !re.is_match(line) {
+                    continue;
+                }
+                hits.push(i);
+                let after = match i {
+                    0 => "at the start".to_string(),
+                    i => format!(
+                        "after \"{}\" (input {})",
+                        self.transcript[i - 1].trim_end(),
+                        i
+                    ),
+                };
+                println!("[{}] {}", hits.len(), after);
+                let start = j.saturating_sub(context);
+                let end = (j + context + 1).min(lines.len());
+                for (k, line) in lines[start..end].iter().enumerate() {
+                    let mark = if start + k == j { '>' } else { ' ' };
+                    println!("  {} {}", mark, line);
+                }
+            }
+        }
+
+        match hits.len() {
+            0 => println!("no matches"),
+            n => println!("{} matches, /search goto <n> to go back to one", n),
+        }
+        self.search_hits = hits;
+    }
+
+    fn search_goto(&mut self, n: usize) {
+        let Some(&chunk) =
+            n.checked_sub(1).and_then(|i| self.search_hits.get(i))
+        else {
+            println!("no match {}, /search first", n);
+            return;
+        };
+        if chunk == self.transcript.len() {
+            println!("match {} was printed since the last input", n);
+            return;
+        }
+        if chunk > self.transcript.len() {
+            println!("match {} was printed after what's been undone", n);
+            return;
+        }
+        // the earliest state at that prompt, before any edits made there
+        let Some(pos) = self.undo.iter().position(|(_, _, len)| *len == chunk)
+        else {
+            println!(
+                "no saved state from then, only the last {} prompts are kept",
+                UNDO_MAX
+            );
+            return;
+        };
+        self.undo_command(self.undo.len() - pos);
+    }
+
+    // /gc [--dry-run] [--keep N] [dir] deletes the saves in dir that aren't
+    // recent, milestones or branch heads, see `gc`
+    fn gc(&mut self, args: &[&str]) {
+        let usage = "usage: /gc [--dry-run] [--keep N] [dir]";
+        let mut policy = Policy::default();
+        let (mut dry_run, mut dir) = (false, ".");
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "--dry-run" => dry_run = true,
+                "--keep" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => policy.keep_last = n,
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                },
+                arg if arg.starts_with('-') => {
+                    println!("{}", usage);
+                    return;
+                }
+                arg => dir = arg,
+            }
+        }
+
+        let mut decisions = match gc::plan(dir, &policy) {
+            Ok(decisions) => decisions,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        for d in decisions.iter_mut() {
+            if self.check_writable(&d.path.to_string_lossy()).is_err() {
+                d.reasons.push("read-only".to_string());
+            }
+        }
+        if !dry_run && let Err(e) = gc::delete(&decisions) {
+            println!("{}", e);
+            return;
+        }
+        println!("{}", gc::report(&decisions, dry_run));
+    }
+
+    // /narrow start | list | stop | <predicate>
+    fn narrow(&mut self, args: &[&str]) {
+        match args {
+            ["start"] => {
+                let scan = Scan::new(self.vm.ram.words());
+                println!(
+                    "snapshot taken, {} candidates",
+                    scan.candidates().len()
+                );
+                self.scan = Some(scan);
+                return;
+            }
+            ["stop"] => {
+                self.scan = None;
+                println!("scan stopped");
+                return;
+            }
+            _ => (),
+        }
+
+        let Some(scan) = self.scan.as_mut() else {
+            println!("no scan in progress, use `/narrow start`");
+            return;
+        };
+
+        if args != ["list"] {
+            match Predicate::parse(args) {
+                Ok(p) => scan.filter(self.vm.ram.words(), p),
+                Err(e) => {
+                    println!("{}", e);
+                    println!(
+                        "usage: /narrow start | list | stop | changed | \
+                         unchanged | increased | decreased | equals <n>"
+                    );
+                    return;
+                }
+            }
+        }
+
+        let candidates = scan.candidates();
+        println!("{} candidates", candidates.len());
+        if candidates.len() <= NARROW_LIST_MAX || args == ["list"] {
+            for addr in candidates {
+                println!("  {:>5}: {}", addr, scan.value(*addr));
+            }
+        }
+    }
+
+    // run a command, as typed after the `/` or at the debug prompt
+    pub fn command(&mut self, s: &str) {
+        trace!("internal command: {}", s);
+
+        let cmd: Vec<_> = s.split_whitespace().collect();
+        if cmd.is_empty() {
+            return;
+        }
+        if cmd[0] != "record" {
+            self.record_command(s);
+        }
+
+        if self.read_only.is_some() && EDIT_COMMANDS.contains(&cmd[0]) {
+            println!("/{} is turned off by --read-only", cmd[0]);
+            return;
+        }
+
+        match cmd[0] {
+            "dump" => self.vm.dump_state(),
+            "state-hash" => println!("{:016x}", self.vm.state_hash()),
+            "set" => {
+                // set the register
+                let register: u16 = cmd[1].parse().unwrap();
+                let value: u16 = cmd[2].parse().unwrap();
+                println!("updating register {}: {}", register, value);
+                self.vm.set_register(register, value);
+            }
+            "save" => {
+                // /save [--force] [file]
+                let (force, args) = save_args(&cmd[1..]);
+                let file = match args[..] {
+                    [] => saves::timestamped_name("bin"),
+                    [file] => file.to_string(),
+                    _ => {
+                        println!("usage: /save [--force] [file]");
+                        return;
+                    }
+                };
+                let ram = self.vm.ram().to_vec();
+                let res = self
+                    .check_writable(&file)
+                    .and_then(|_| saves::write(&file, &ram, force));
+                match res {
+                    Ok(()) => {
+                        self.saved(&file);
+                        println!("file saved to {}", file)
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "export" => {
+                // /export [--force] [file]
+                let (force, args) = save_args(&cmd[1..]);
+                let file = match args[..] {
+                    [] => saves::timestamped_name("json"),
+                    [file] => file.to_string(),
+                    _ => {
+                        println!("usage: /export [--force] [file]");
+                        return;
+                    }
+                };
+                // other VMs' format for .state files, and the compact one
+                // for .snap
+                let data = if file.ends_with(textstate::EXTENSION) {
+                    textstate::export(&self.vm).into_bytes()
+                } else if file.ends_with(binsave::EXTENSION) {
+                    binsave::export(&self.vm)
+                } else {
+                    serde_json::to_vec(&self.vm).unwrap()
+                };
+                let res = self
+                    .check_writable(&file)
+                    .and_then(|_| saves::write(&file, &data, force));
+                match res {
+                    Ok(()) => {
+                        self.saved(&file);
+                        println!(
+                            "step {} saved to {}",
+                            group_digits(self.vm.steps()),
+                            file
+                        )
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "load" => match cmd[1..] {
+                [file] => self.load(file),
+                _ => println!("usage: /load <file>"),
+            },
+            "export-delta" => {
+                // /export-delta [--force] <base> [file]
+                let (force, args) = save_args(&cmd[1..]);
+                let (base, file) = match args[..] {
+                    [base] => (base, saves::timestamped_name("json")),
+                    [base, file] => (base, file.to_string()),
+                    _ => {
+                        println!(
+                            "usage: /export-delta [--force] <base> [file]"
+                        );
+                        return;
+                    }
+                };
+                let res = self
+                    .check_writable(&file)
+                    .and_then(|_| saves::export_delta(&self.vm, base, &file))
+                    .and_then(|data| {
+                        saves::write(&file, data.as_bytes(), force)
+                    });
+                match res {
+                    Ok(()) => {
+                        self.saved(&file);
+                        println!("delta of {} saved to {}", base, file)
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "symbols" => {
+                let [file] = cmd[1..] else {
+                    println!("usage: /symbols <file>");
+                    return;
+                };
+                match Symbols::load(file) {
+                    Ok(symbols) => {
+                        println!(
+                            "loaded {} symbols from {}",
+                            symbols.len(),
+                            file
+                        );
+                        self.vm.set_symbols(symbols);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "vault" => {
+                // /vault [--force] <file>
+                let (force, args) = save_args(&cmd[1..]);
+                let [file] = args[..] else {
+                    println!("usage: /vault [--force] <file>");
+                    println!(
+                        "writes the {0}x{0} grid at the vault_grid symbol, \
+                         numbers and operator characters row by row",
+                        vault::SIZE
+                    );
+                    return;
+                };
+                match self.export_vault(file, force) {
+                    Ok(()) => println!("vault grid saved to {}", file),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "watch-string" => match cmd[1..] {
+                [addr] => {
+                    let symbols = &self.vm.symbols;
+                    let addr = match memedit::parse_addr(addr, symbols) {
+                        Ok(addr) => addr,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
+                    let value = self.vm.decode_string(addr);
+                    print_watched_string(addr, &value);
+                    let watched = &mut self.vm.watched_strings;
+                    watched.retain(|(a, _)| *a != addr);
+                    watched.push((addr, value));
+                }
+                [] => {
+                    for (addr, value) in &self.vm.watched_strings {
+                        print_watched_string(*addr, value);
+                    }
+                }
+                _ => println!("usage: /watch-string [addr]"),
+            },
+            "unwatch-string" => {
+                let [addr] = cmd[1..] else {
+                    println!("usage: /unwatch-string <addr>");
+                    return;
+                };
+                match memedit::parse_addr(addr, &self.vm.symbols) {
+                    Ok(addr) => {
+                        self.vm.watched_strings.retain(|(a, _)| *a != addr);
+                        println!("no longer watching string at {}", addr);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "coverage" => {
+                // /coverage [--force] <file> writes every executed address,
+                // one per line, for `map`
+                let (force, args) = save_args(&cmd[1..]);
+                let [file] = args[..] else {
+                    println!("usage: /coverage [--force] <file>");
+                    return;
+                };
+                let data: String = (0..32768)
+                    .filter(|addr| self.vm.executed(*addr))
+                    .map(|addr| format!("{}\n", addr))
+                    .collect();
+                let res = self
+                    .check_writable(file)
+                    .and_then(|_| saves::write(file, data.as_bytes(), force));
+                match res {
+                    Ok(()) => println!("coverage saved to {}", file),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "break" | "tbreak" => {
+                let Some(addr) = cmd.get(1) else {
+                    println!("usage: /{} <addr|symbol>", cmd[0]);
+                    return;
+                };
+                match memedit::parse_addr(addr, &self.vm.symbols) {
+                    Ok(addr) => {
+                        let temporary = cmd[0] == "tbreak";
+                        let id = self.vm.breakpoints.add(addr, temporary);
+                        println!("breakpoint {} at {}", id, addr);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "delete" => match cmd[1..] {
+                [] => {
+                    self.vm.breakpoints.clear();
+                    println!("deleted all breakpoints");
+                }
+                ref ids => {
+                    for id in ids {
+                        match id.parse() {
+                            Ok(n) if self.vm.breakpoints.remove(n) => {
+                                println!("deleted breakpoint {}", n);
+                            }
+                            _ => println!("no breakpoint {}", id),
+                        }
+                    }
+                }
+            },
+            // run until the routine being run returns, see `run_to`
+            "finish" => {
+                if let Err(e) = self.run_to("return") {
+                    println!("{}", e);
+                }
+            }
+            "group" => self.group_command(&cmd[1..]),
+            "ignore" => {
+                // /ignore <breakpoint> <hits>
+                let args = match cmd[1..] {
+                    [id, n] => {
+                        id.parse::<usize>().ok().zip(n.parse::<u64>().ok())
+                    }
+                    _ => None,
+                };
+                let Some((id, n)) = args else {
+                    println!("usage: /ignore <breakpoint> <hits>");
+                    return;
+                };
+                if self.vm.breakpoints.set_ignore(id, n) {
+                    println!(
+                        "will ignore next {} hits of breakpoint {}",
+                        n, id
+                    );
+                } else {
+                    println!("no breakpoint {}", id);
+                }
+            }
+            "stack" => self.print_stack(),
+            "stack-check" => {
+                match cmd.get(1) {
+                    Some(&"on") => self.vm.set_stack_check(true),
+                    Some(&"off") => self.vm.set_stack_check(false),
+                    Some(_) => {
+                        println!("usage: /stack-check [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                let on = self.vm.stack_check();
+                println!("stack check {}", if on { "on" } else { "off" });
+            }
+            "loglevel" => {
+                if let Some(level) = cmd.get(1) {
+                    let Ok(level) = level.parse::<LevelFilter>() else {
+                        println!(
+                            "usage: /loglevel [off|error|warn|info|debug|trace]"
+                        );
+                        return;
+                    };
+                    logging::set_level(level);
+                }
+                println!("log level {}", logging::level());
+            }
+            "fast" => {
+                match cmd.get(1) {
+                    Some(&"on") => self.vm.set_fast(true),
+                    Some(&"off") => self.vm.set_fast(false),
+                    Some(_) => {
+                        println!("usage: /fast [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                if self.vm.fast() {
+                    println!("fast on, {} superblocks", self.vm.superblocks());
+                } else {
+                    println!("fast off");
+                }
+            }
+            "steps" => {
+                match cmd.get(1) {
+                    Some(&"on") => self.show_steps = true,
+                    Some(&"off") => self.show_steps = false,
+                    Some(_) => {
+                        println!("usage: /steps [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                println!(
+                    "step {}, shown in the prompt {}",
+                    group_digits(self.vm.steps()),
+                    if self.show_steps { "on" } else { "off" }
+                );
+            }
+            "explain" => {
+                match cmd.get(1) {
+                    Some(&"on") => self.set_explain(true),
+                    Some(&"off") => self.set_explain(false),
+                    Some(_) => {
+                        println!("usage: /explain [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                println!(
+                    "explaining instructions {}",
+                    if self.explain { "on" } else { "off" }
+                );
+            }
+            "step" => match cmd.get(1).map_or(Ok(1), |n| n.parse()) {
+                Ok(n) => self.step_by(n),
+                Err(_) => println!("usage: /step [n]"),
+            },
+            "stepback" => match cmd.get(1).map_or(Ok(1), |n| n.parse()) {
+                Ok(n) => {
+                    self.step_back(n);
+                }
+                Err(_) => println!("usage: /stepback [n]"),
+            },
+            "rewind" => match cmd.get(1).map_or(Ok(1), |n| n.parse()) {
+                // from the game's prompt, stop where it went back to rather
+                // than running straight back to the prompt
+                Ok(n) => {
+                    if self.step_back(n) && !self.debugging {
+                        self.debug_prompt();
+                    }
+                }
+                Err(_) => println!("usage: /rewind [n]"),
+            },
+            "animate" => {
+                let hz = cmd.get(1).and_then(|n| n.parse::<f64>().ok());
+                let max = match cmd.get(2).map(|n| n.parse()) {
+                    None => Ok(u64::MAX),
+                    Some(n) => n,
+                };
+                match (hz, max) {
+                    (Some(hz), Ok(max)) if hz > 0.0 => self.animate(hz, max),
+                    _ => println!("usage: /animate <hz> [n]"),
+                }
+            }
+            "status" => {
+                self.watchdog.update(&self.vm);
+                println!("{}", self.watchdog.status(&self.vm));
+            }
+            "stats" => println!("{}", self.stats_summary()),
+            "timeline" => {
+                // /timeline [--force] [file.json|file.svg]
+                let (force, args) = save_args(&cmd[1..]);
+                let file = match args[..] {
+                    [] => {
+                        println!(
+                            "{} events on the timeline",
+                            self.timeline.entries.len()
+                        );
+                        return;
+                    }
+                    [file] => file,
+                    _ => {
+                        println!("usage: /timeline [--force] [file]");
+                        return;
+                    }
+                };
+                self.note_room();
+                let data = match file.ends_with(".svg") {
+                    true => self.timeline.svg(),
+                    false => self.timeline.json(),
+                };
+                let res = self
+                    .check_writable(file)
+                    .and_then(|_| saves::write(file, data.as_bytes(), force));
+                match res {
+                    Ok(()) => println!("timeline written to {}", file),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "profile" => match self.vm.sampler() {
+                Some(sampler) => {
+                    println!("{}", sampler.profile().report(&self.vm.symbols))
+                }
+                None => println!("not sampling, start with --sample FILE"),
+            },
+            "record" => {
+                // /record [[--force] <file>|off]
+                let (force, args) = save_args(&cmd[1..]);
+                match (force, &args[..]) {
+                    (false, []) => match &self.recording {
+                        Some((file, _)) => println!("recording to {}", file),
+                        None => println!("not recording"),
+                    },
+                    (false, ["off"]) => match self.recording.take() {
+                        Some((file, _)) => {
+                            println!("session saved to {}", file)
+                        }
+                        None => println!("not recording"),
+                    },
+                    (force, [file]) => match self.record(file, force) {
+                        Ok(()) => println!(
+                            "recording to {}, {} lines of input so far",
+                            file,
+                            self.transcript.len()
+                        ),
+                        Err(e) => println!("{}", e),
+                    },
+                    _ => println!("usage: /record [[--force] <file>|off]"),
+                }
+            }
+            "fast-forward" | "ff" => {
+                let max = match cmd[1..] {
+                    [] => FAST_FORWARD_MAX,
+                    [n] => match n.parse() {
+                        Ok(n) => n,
+                        Err(_) => {
+                            println!("invalid count {}", n);
+                            return;
+                        }
+                    },
+                    _ => {
+                        println!("usage: /{} [max instructions]", cmd[0]);
+                        return;
+                    }
+                };
+                self.fast_forward(max);
+            }
+            "novelty" => {
+                match cmd.get(1) {
+                    // everything already printed counts as seen
+                    Some(&"on") if self.novelty.is_none() => {
+                        let mut novelty = Novelty::default();
+                        novelty.add_text(&self.since_input);
+                        self.novelty = Some(novelty);
+                    }
+                    Some(&"on") => (),
+                    Some(&"off") => self.novelty = None,
+                    Some(_) => {
+                        println!("usage: /novelty [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                match &self.novelty {
+                    Some(novelty) => {
+                        println!("novelty on, {} lines seen", novelty.len())
+                    }
+                    None => println!("novelty off"),
+                }
+            }
+            "arity" => {
+                match cmd.get(1) {
+                    Some(&"on") => self.vm.set_arity(true),
+                    Some(&"off") => self.vm.set_arity(false),
+                    Some(_) => {
+                        println!("usage: /arity [on|off]");
+                        return;
+                    }
+                    None => (),
+                }
+                let Some(arity) = self.vm.arity() else {
+                    println!("arity off, /arity on to start working it out");
+                    return;
+                };
+                for (routine, inferred) in arity.routines() {
+                    println!(
+                        "{}: {}",
+                        self.vm.routine_name(*routine),
+                        inferred
+                    );
+                }
+                println!("{} routines returned from", arity.routines().len());
+            }
+            "backtrace" | "bt" => self.print_backtrace(),
+            "budget" => match cmd[1..] {
+                [] => {
+                    for (addr, n) in &self.vm.budgets {
+                        println!("{:<5} {}", addr, n);
+                    }
+                }
+                [addr, n] => {
+                    let symbols = &self.vm.symbols;
+                    let addr = match memedit::parse_addr(addr, symbols) {
+                        Ok(addr) => addr,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
+                    if n == "off" {
+                        self.vm.budgets.remove(&addr);
+                        println!("removed budget for {}", addr);
+                        return;
+                    }
+                    let Ok(n) = n.parse::<u64>() else {
+                        println!("invalid budget {}", n);
+                        return;
+                    };
+                    // applies to calls made from now on
+                    self.vm.budgets.insert(addr, n);
+                    println!(
+                        "budget for {}: {} instructions per call",
+                        self.vm.routine_name(addr),
+                        n
+                    );
+                }
+                _ => println!("usage: /budget [<addr> <n|off>]"),
+            },
+            "disas" => {
+                // /disas [addr [count]]
+                let symbols = &self.vm.symbols;
+                let args = match cmd[1..] {
+                    [] => Ok((self.vm.pc(), 10)),
+                    [addr] => {
+                        memedit::parse_addr(addr, symbols).map(|a| (a, 10))
+                    }
+                    [addr, n] => {
+                        memedit::parse_addr(addr, symbols).and_then(|a| {
+                            let n = n
+                                .parse()
+                                .map_err(|_| format!("invalid count {}", n))?;
+                            Ok((a, n))
+                        })
+                    }
+                    _ => Err("usage: /disas [addr [count]]".to_string()),
+                };
+                match args {
+                    Ok((addr, count)) => self.print_disassembly(addr, count),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "find-code" => self.find_code(&cmd[1..]),
+            "filter" => match cmd.get(1) {
+                Some(&"clear") => {
+                    self.flush_filters();
+                    self.filters.clear();
+                    println!("output filters cleared");
+                }
+                Some(_) => {
+                    // specs may contain spaces, ie. s/foo bar/baz/
+                    let spec = s.trim_start()["filter".len()..].trim();
+                    match self.filters.add(spec) {
+                        Ok(()) => println!("added output filter {}", spec),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+                None => {
+                    for (i, spec) in self.filters.specs().enumerate() {
+                        println!("{:<3} {}", i + 1, spec);
+                    }
+                }
+            },
+            "progress" => match &self.progress {
+                Some(progress) => {
+                    println!(
+                        "{}/{} codes found",
+                        progress.found().len(),
+                        codes::TOTAL
+                    );
+                    for (i, code) in progress.found().iter().enumerate() {
+                        println!(
+                            "  {}. {}  {}",
+                            i + 1,
+                            code,
+                            codes::md5_hex(code)
+                        );
+                    }
+                }
+                None => println!("not tracking progress"),
+            },
+            "trace-filter" => {
+                let expr = s.trim_start()["trace-filter".len()..].trim();
+                match expr {
+                    "" => match &self.vm.trace_filter {
+                        Some(f) => println!("tracing {}", f.as_str()),
+                        None => println!("tracing everything"),
+                    },
+                    "off" => {
+                        self.vm.set_trace_filter(None);
+                        println!("tracing everything");
+                    }
+                    expr => match TraceFilter::parse(expr) {
+                        Ok(f) => {
+                            println!("tracing {}", f.as_str());
+                            self.vm.set_trace_filter(Some(f));
+                        }
+                        Err(e) => println!("invalid trace filter: {}", e),
+                    },
+                }
+            }
+            "saves" => {
+                if let Err(e) = saves::print_list(cmd.get(1).unwrap_or(&".")) {
+                    println!("{}", e);
+                }
+            }
+            "gc" => self.gc(&cmd[1..]),
+            "hooks" => {
+                for hook in &self.vm.hooks {
+                    println!(
+                        "{:<5} hits={} \"{}\"",
+                        hook.addr,
+                        hook.hits,
+                        String::from_utf8_lossy(&hook.input).escape_debug()
+                    );
+                }
+            }
+            "narrow" => self.narrow(&cmd[1..]),
+            "info" => {
+                let info = rominfo::identify(self.vm.image());
+                println!("{}", info);
+                match offsets::Database::load() {
+                    Ok(db) => {
+                        println!("{}", rominfo::describe_offsets(&db, &info))
+                    }
+                    Err(e) => println!("{}", e),
+                }
+                println!(
+                    "{}",
+                    rominfo::describe_symbols(&self.vm.symbols, &info)
+                );
+            }
+            "offsets" => self.offsets(&cmd[1..]),
+            "edit-mem" => {
+                let start = match cmd.get(1) {
+                    Some(addr) => {
+                        match memedit::parse_addr(addr, &self.vm.symbols) {
+                            Ok(addr) => addr,
+                            Err(e) => {
+                                println!("{}", e);
+                                return;
+                            }
+                        }
+                    }
+                    None => self.vm.pc(),
+                };
+                self.edit_memory(start);
+            }
+            "fill" => {
+                // /fill <addr> <len> <value>
+                let args = match cmd[1..] {
+                    [addr, len, value] => (|| {
+                        Ok::<_, String>((
+                            memedit::parse_addr(addr, &self.vm.symbols)?,
+                            len.parse().map_err(|_| "invalid length")?,
+                            memedit::parse_word(value)?,
+                        ))
+                    })(),
+                    _ => Err("usage: /fill <addr> <len> <value>".to_string()),
+                };
+                let res = args.and_then(|(addr, len, value)| {
+                    self.save_undo(format!("/{}", s));
+                    let res = self.vm.fill(addr, len, value);
+                    if res.is_err() {
+                        // nothing changed, so nothing to undo
+                        self.undo.pop();
+                    }
+                    res
+                });
+                match res {
+                    Ok(()) => println!("filled"),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "copy" => {
+                // /copy <src> <dst> <len>
+                let args = match cmd[1..] {
+                    [src, dst, len] => (|| {
+                        Ok::<_, String>((
+                            memedit::parse_addr(src, &self.vm.symbols)?,
+                            memedit::parse_addr(dst, &self.vm.symbols)?,
+                            len.parse().map_err(|_| "invalid length")?,
+                        ))
+                    })(),
+                    _ => Err("usage: /copy <src> <dst> <len>".to_string()),
+                };
+                let res = args.and_then(|(src, dst, len)| {
+                    self.save_undo(format!("/{}", s));
+                    let res = self.vm.copy(src, dst, len);
+                    if res.is_err() {
+                        // nothing changed, so nothing to undo
+                        self.undo.pop();
+                    }
+                    res
+                });
+                match res {
+                    Ok(()) => println!("copied"),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "dumpmem" => {
+                // /dumpmem [--force] <addr> <len> <file>, as little endian
+                // words like a rom
+                let (force, args) = save_args(&cmd[1..]);
+                let res = match args[..] {
+                    [addr, len, file] => (|| {
+                        let addr = memedit::parse_addr(addr, &self.vm.symbols)?;
+                        let len = len.parse().map_err(|_| "invalid length")?;
+                        let words = self.vm.read_words(addr, len)?;
+                        let data: Vec<u8> = words
+                            .iter()
+                            .flat_map(|w| w.to_le_bytes())
+                            .collect();
+                        self.check_writable(file)?;
+                        saves::write(file, &data, force)?;
+                        Ok::<_, String>(format!(
+                            "{} words from {} saved to {}",
+                            len, addr, file
+                        ))
+                    })(),
+                    _ => Err("usage: /dumpmem [--force] <addr> <len> <file>"
+                        .to_string()),
+                };
+                match res {
+                    Ok(msg) => println!("{}", msg),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "loadmem" => {
+                // /loadmem <addr> <file>, a file written by /dumpmem
+                let args = match cmd[1..] {
+                    [addr, file] => (|| {
+                        let addr = memedit::parse_addr(addr, &self.vm.symbols)?;
+                        let data = fs::read(file)
+                            .map_err(|e| format!("{}: {}", file, e))?;
+                        if data.len() % 2 != 0 {
+                            return Err(format!("{} isn't whole words", file));
+                        }
+                        let words: Vec<u16> = data
+                            .chunks(2)
+                            .map(|w| u16::from_le_bytes([w[0], w[1]]))
+                            .collect();
+                        Ok::<_, String>((addr, words))
+                    })(),
+                    _ => Err("usage: /loadmem <addr> <file>".to_string()),
+                };
+                let res = args.and_then(|(addr, words)| {
+                    self.save_undo(format!("/{}", s));
+                    let res = self.vm.write_words(addr, &words);
+                    if res.is_err() {
+                        // nothing changed, so nothing to undo
+                        self.undo.pop();
+                    }
+                    res.map(|_| (addr, words.len()))
+                });
+                match res {
+                    Ok((addr, len)) => {
+                        println!("loaded {} words at {}", len, addr)
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "nop" => {
+                // /nop <addr>..<addr>, the end is exclusive unless ..=
+                let range = match cmd[1..] {
+                    [range] => self.parse_range(range),
+                    _ => Err("usage: /nop <addr>..<addr>".to_string()),
+                };
+                let res = range.and_then(|range| {
+                    self.save_undo(format!("/{}", s));
+                    let n = range.len();
+                    let res = self.vm.nop(range);
+                    if res.is_err() {
+                        self.undo.pop();
+                    }
+                    res.map(|_| n)
+                });
+                match res {
+                    Ok(n) => println!("{} words replaced with noop", n),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "jump" => {
+                // /jump <addr>
+                let addr = match cmd[1..] {
+                    [addr] => memedit::parse_addr(addr, &self.vm.symbols),
+                    _ => Err("usage: /jump <addr>".to_string()),
+                };
+                match addr {
+                    Ok(addr) => {
+                        self.save_undo(format!("/{}", s));
+                        self.vm.set_pc(addr);
+                        println!("jumped to {}", addr);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "return" => {
+                // /return [value], the value goes in r0
+                let value = match cmd[1..] {
+                    [] => Ok(None),
+                    [value] => memedit::parse_word(value).map(Some),
+                    _ => Err("usage: /return [value]".to_string()),
+                };
+                let res = value.and_then(|value| {
+                    self.save_undo(format!("/{}", s));
+                    let res = self.vm.force_return(value);
+                    if res.is_err() {
+                        self.undo.pop();
+                    }
+                    res
+                });
+                match res {
+                    Ok(addr) => println!("returned to {}", addr),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "search" => {
+                let args = s.trim_start()["search".len()..].trim();
+                self.search(args);
+            }
+            "undo-command" => {
+                let n = match cmd[1..] {
+                    [] => Ok(1),
+                    [n] => {
+                        n.parse().map_err(|_| format!("invalid count {}", n))
+                    }
+                    _ => Err("usage: /undo-command [n]".to_string()),
+                };
+                match n {
+                    Ok(n) => self.undo_command(n),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "track" => match cmd[1..] {
+                [] => {
+                    for tracked in self.vm.value_history.iter() {
+                        let (_, value) = tracked.changes.back().unwrap();
+                        println!(
+                            "{}: {} ({} changes)",
+                            tracked.source,
+                            value,
+                            tracked.changes.len()
+                        );
+                    }
+                }
+                ref sources => {
+                    for s in sources {
+                        match Source::parse(s, &self.vm.symbols) {
+                            Ok(source) => {
+                                let vm = &mut self.vm;
+                                if vm.value_history.add(
+                                    source,
+                                    vm.steps,
+                                    &vm.registers,
+                                    &vm.ram,
+                                ) {
+                                    println!("tracking {}", source);
+                                } else {
+                                    println!("already tracking {}", source);
+                                }
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                }
+            },
+            "untrack" => {
+                for s in &cmd[1..] {
+                    match Source::parse(s, &self.vm.symbols) {
+                        Ok(source) if self.vm.value_history.remove(source) => {
+                            println!("stopped tracking {}", source)
+                        }
+                        Ok(source) => println!("not tracking {}", source),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+            // /history <tracked value> shows its changes, anything else
+            // searches the command history
+            "history"
+                if cmd.len() == 2
+                    && let Ok(source) =
+                        Source::parse(cmd[1], &self.vm.symbols)
+                    && let Some(tracked) =
+                        self.vm.value_history.get(source) =>
+            {
+                println!(
+                    "{}: {} changes, {}",
+                    tracked.source,
+                    tracked.changes.len(),
+                    tracked.sparkline()
+                );
+                for (step, value) in &tracked.changes {
+                    println!("{:>12}  {}", step, value);
+                }
+            }
+            "history" => match &self.history {
+                Some(history) => {
+                    let text = s.trim_start()["history".len()..].trim();
+                    for (i, line) in history.search(text) {
+                        println!("{:>5}  {}", i, line);
+                    }
+                }
+                None => println!("no history file"),
+            },
+            "breakpoints" => {
+                for b in self.vm.breakpoints.iter() {
+                    println!(
+                        "{:<3} {:<5} hits={} ignore={}{}{}",
+                        b.id,
+                        b.addr,
+                        b.hits,
+                        b.ignore,
+                        if b.temporary { " (temporary)" } else { "" },
+                        if b.enabled { "" } else { " (disabled)" }
+                    );
+                }
+            }
+            "plugin" => match cmd[1..] {
+                [] => {
+                    let loaded = self.plugins.names();
+                    for (name, _) in plugin::REGISTRY {
+                        let on = loaded.contains(name);
+                        println!(
+                            "{}{}",
+                            name,
+                            if on { " (loaded)" } else { "" }
+                        );
+                    }
+                }
+                ["load", name] => {
+                    match plugin::load(name).and_then(|p| self.add_plugin(p)) {
+                        Ok(()) => println!("loaded plugin {}", name),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+                _ => println!("usage: /plugin [load <name>]"),
+            },
+            // plugins get the VM to change as they like
+            _ if self.read_only.is_some() && !self.plugins.is_empty() => {
+                println!(
+                    "unknown internal command: {} (plugins' commands are turned off by --read-only)",
+                    cmd[0]
+                )
+            }
+            _ if self.plugins.command(&mut self.vm, &cmd) => (),
+            cmd => println!("unknown internal command: {}", cmd),
+        }
+    }
+}
+
+// ends the line being edited when its key is pressed, leaving the command
+// for edit_line to return
+struct BoundKey {
+    cmd: String,
+    pressed: Arc<Mutex<Option<String>>>,
+}
+
+impl ConditionalEventHandler for BoundKey {
+    fn handle(
+        &self,
+        _: &rustyline::Event,
+        _: RepeatCount,
+        _: bool,
+        _: &EventContext,
+    ) -> Option<Cmd> {
+        *self.pressed.lock().unwrap() = Some(self.cmd.clone());
+        Some(Cmd::AcceptLine)
+    }
+}
+
+// split --force out of the arguments to a save command
+fn save_args<'a>(args: &[&'a str]) -> (bool, Vec<&'a str>) {
+    let force = args.contains(&"--force");
+    (force, args.iter().copied().filter(|a| *a != "--force").collect())
+}
+
+// 1234567 as 1,234,567
+pub(crate) fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn print_watched_string(addr: u16, value: &Option<String>) {
+    match value {
+        Some(s) => println!("string {}: \"{}\"", addr, s.escape_debug()),
+        None => println!("string {}: <not a valid string>", addr),
+    }
+}
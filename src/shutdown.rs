@@ -0,0 +1,168 @@
+/*!
+ * Save the game and exit when the process is told to stop.
+ *
+ * With `--checkpoint FILE`, SIGTERM, SIGHUP and ctrl-c save the game to FILE
+ * (replacing it) and exit, so a game hosted behind something like `socat` or
+ * systemd keeps the player's progress across restarts.  The player is told
+ * where it went, and running the save picks up from there.
+ *
+ * When the shell is waiting for input the state at the prompt is saved
+ * straight away.  Otherwise the shell saves before its next instruction,
+ * and if it hasn't within DEADLINE (ie. it's stuck at the debug prompt) the
+ * state at the last prompt is saved instead.
+ *
+ * The line editor can't be woken from another thread, so in those two cases
+ * the signal thread saves and exits itself.  It puts the terminal back in
+ * the mode it was in at install first, as the editor leaves it raw while
+ * it's waiting.
+ */
+
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use nix::sys::termios;
+
+use crate::saves;
+use crate::vm::{Snapshot, VM};
+
+// how long the shell gets to save before the last prompt is saved instead
+const DEADLINE: Duration = Duration::from_secs(5);
+
+// how often to check on the shell while waiting for it
+const POLL: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+struct State {
+    // the state at the last prompt, and if the shell is still waiting there
+    prompt: Option<Snapshot>,
+    waiting: bool,
+    // set once the game is saved, so it only happens once
+    saved: bool,
+}
+
+pub struct Shutdown {
+    file: String,
+    requested: AtomicBool,
+    state: Mutex<State>,
+    terminal: Terminal,
+}
+
+// the terminal's mode before the line editor changes it, if stdin is one
+#[cfg(unix)]
+struct Terminal(Mutex<Option<termios::Termios>>);
+
+#[cfg(unix)]
+impl Terminal {
+    fn save() -> Self {
+        Self(Mutex::new(termios::tcgetattr(io::stdin()).ok()))
+    }
+
+    fn restore(&self) {
+        let Some(mode) = &*self.0.lock().unwrap() else {
+            return;
+        };
+        let _ = termios::tcsetattr(io::stdin(), termios::SetArg::TCSANOW, mode);
+        // the editor turns on bracketed paste too
+        eprint!("\x1b[?2004l");
+    }
+}
+
+#[cfg(not(unix))]
+struct Terminal;
+
+#[cfg(not(unix))]
+impl Terminal {
+    fn save() -> Self {
+        Self
+    }
+
+    fn restore(&self) {}
+}
+
+impl Shutdown {
+    // handle the signals, saving to file
+    pub fn install(file: &str) -> Result<Arc<Self>, String> {
+        let shutdown = Arc::new(Self {
+            file: file.to_string(),
+            requested: AtomicBool::new(false),
+            state: Mutex::default(),
+            terminal: Terminal::save(),
+        });
+        let handler = shutdown.clone();
+        ctrlc::set_handler(move || handler.handle())
+            .map_err(|e| format!("failed to handle signals: {}", e))?;
+        Ok(shutdown)
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    // the shell is about to wait for input with the VM like this
+    pub fn at_prompt(&self, vm: &VM) {
+        let mut state = self.state.lock().unwrap();
+        state.prompt = Some(vm.snapshot());
+        state.waiting = true;
+    }
+
+    // the shell has its input and is carrying on
+    pub fn resumed(&self) {
+        self.state.lock().unwrap().waiting = false;
+    }
+
+    // save the VM as it is now, from the shell, unless it already was
+    pub fn save(&self, vm: &VM) {
+        let mut state = self.state.lock().unwrap();
+        if !state.saved {
+            state.saved = true;
+            self.write(vm);
+        }
+    }
+
+    fn write(&self, vm: &VM) {
+        let data = serde_json::to_string(vm).unwrap();
+        match saves::write(&self.file, data.as_bytes(), true) {
+            Ok(()) => println!(
+                "shutting down, step {} saved to {}",
+                vm.steps(),
+                self.file
+            ),
+            Err(e) => println!("shutting down, {}", e),
+        }
+    }
+
+    // runs on the signal handling thread.  exits unless the shell saves,
+    // leaving the terminal as it found it
+    fn handle(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+
+        let start = Instant::now();
+        loop {
+            let mut state = self.state.lock().unwrap();
+            if state.saved {
+                return;
+            }
+            let deadline = start.elapsed() >= DEADLINE;
+            if state.waiting || deadline {
+                state.saved = true;
+                match state.prompt.take() {
+                    Some(snapshot) => {
+                        let mut vm = VM::new(vec![]);
+                        vm.restore(snapshot);
+                        self.write(&vm);
+                    }
+                    None => println!("shutting down, nothing to save yet"),
+                }
+                self.terminal.restore();
+                process::exit(0);
+            }
+            drop(state);
+            thread::sleep(POLL);
+        }
+    }
+}
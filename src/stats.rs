@@ -0,0 +1,79 @@
+/*!
+ * Totals for the session, printed when a code is found and when the game
+ * halts (and by `/stats`):
+ *
+ * ```text
+ * session so far:
+ *   played        14m12s
+ *   instructions  12,533,908
+ *   inputs        87
+ *   codes         4 of 8
+ *   saves         2
+ *   restores      3 (5 commands undone)
+ * ```
+ *
+ * Instructions count from the start of the game, and inputs are the ones
+ * that led to where the game is now, so playing back a recording of a
+ * session should end with the same numbers - a quick check that the replay
+ * went the same way.  Codes are all those in the progress file.  Play time,
+ * saves and restores (`/undo-command`, `/search goto`) are only for this
+ * session.  Dying halts the game, so the summary at a halt is the one after
+ * a death.
+ */
+
+use std::time::Instant;
+
+use crate::shell::group_digits;
+use crate::watchdog::duration;
+
+pub struct Stats {
+    started: Instant,
+    saves: u64,
+    restores: u64,
+    undone: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self { started: Instant::now(), saves: 0, restores: 0, undone: 0 }
+    }
+}
+
+impl Stats {
+    pub fn saved(&mut self) {
+        self.saves += 1;
+    }
+
+    // rolled back over n game commands
+    pub fn restored(&mut self, n: usize) {
+        self.restores += 1;
+        self.undone += n as u64;
+    }
+
+    // codes is how many have been found and how many there are, if they're
+    // being tracked
+    pub fn summary(
+        &self,
+        steps: u64,
+        inputs: usize,
+        codes: Option<(usize, usize)>,
+    ) -> String {
+        let mut lines = vec![
+            "session so far:".to_string(),
+            format!("  played        {}", duration(self.started.elapsed())),
+            format!("  instructions  {}", group_digits(steps)),
+            format!("  inputs        {}", inputs),
+        ];
+        if let Some((found, total)) = codes {
+            lines.push(format!("  codes         {} of {}", found, total));
+        }
+        lines.push(format!("  saves         {}", self.saves));
+        let mut restores = format!("  restores      {}", self.restores);
+        if self.restores > 0 {
+            let s = if self.undone == 1 { "" } else { "s" };
+            restores += &format!(" ({} command{} undone)", self.undone, s);
+        }
+        lines.push(restores);
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,21 @@
+/*!
+ * Decode the game's strings out of memory.
+ *
+ * Strings are packed as a length word followed by one character per word.
+ */
+
+// don't treat a huge length word as a string
+pub const MAX_LEN: u16 = 1024;
+
+// decode the length-prefixed string at addr, or None if it runs past the end
+// of ram or has an unreasonable length
+pub fn decode(words: &[u16], addr: u16) -> Option<String> {
+    let addr = addr as usize;
+    let len = *words.get(addr)?;
+    if len > MAX_LEN {
+        return None;
+    }
+
+    let chars = words.get(addr + 1..addr + 1 + len as usize)?;
+    Some(chars.iter().map(|w| (*w as u8) as char).collect())
+}
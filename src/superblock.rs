@@ -0,0 +1,166 @@
+/*!
+ * Superblocks for the fast engine.
+ *
+ * The interpreter fetches and decodes every instruction each time it runs
+ * it.  With the fast engine on, the VM counts how often execution arrives at
+ * each address after a jump, and once an address gets hot it decodes the
+ * straight-line run of instructions starting there (everything up to the
+ * next jump, call, ret, in or halt) into a superblock.  From then on the
+ * whole run executes from the pre-decoded ops without fetching or decoding
+ * anything, and the interpreter takes over again for the instruction that
+ * ends it.
+ *
+ * A `wmem` into a superblock's range throws it away, so self-modifying code
+ * still runs what's in memory.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::ram::Ram;
+use crate::vm::OPERANDS;
+
+// arrivals at an address before a superblock is made for it
+const HOT: u32 = 32;
+
+// most instructions in one superblock
+const MAX_OPS: usize = 64;
+
+// longest superblock in words, for finding the ones covering an address
+const MAX_WORDS: u16 = MAX_OPS as u16 * 4;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operand {
+    Reg(u8),
+    Lit(u16),
+}
+
+impl Operand {
+    // None for words that are neither
+    pub fn decode(word: u16) -> Option<Self> {
+        match word {
+            0..32768 => Some(Operand::Lit(word)),
+            32768..32776 => Some(Operand::Reg((word - 32768) as u8)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Op {
+    pub addr: u16,
+    pub opcode: u16,
+    pub args: [Operand; 3],
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub start: u16,
+    // address of the instruction after the last op
+    pub end: u16,
+    pub ops: Vec<Op>,
+}
+
+// instructions that never change the flow of control and are safe to run
+// without the interpreter's bookkeeping
+fn is_simple(opcode: u16) -> bool {
+    matches!(opcode, 1..=5 | 9..=16 | 19 | 21)
+}
+
+// opcodes whose first operand is a register written to
+fn writes_register(opcode: u16) -> bool {
+    matches!(opcode, 1 | 3 | 4 | 5 | 9..=15)
+}
+
+// decode the run of simple instructions starting at addr, None if there
+// isn't one
+pub fn decode(ram: &Ram, start: u16) -> Option<Block> {
+    let mut ops = vec![];
+    let mut addr = start;
+
+    while ops.len() < MAX_OPS {
+        let Some(opcode) = ram.word(addr) else {
+            break;
+        };
+        if !is_simple(opcode) {
+            break;
+        }
+
+        let n = OPERANDS[opcode as usize];
+        let mut args = [Operand::Lit(0); 3];
+        let mut valid = true;
+        for (i, arg) in args.iter_mut().enumerate().take(n as usize) {
+            match ram.word(addr + 1 + i as u16).and_then(Operand::decode) {
+                Some(operand) => *arg = operand,
+                None => valid = false,
+            }
+        }
+        // leave anything the interpreter would complain about to it
+        if !valid
+            || (writes_register(opcode) && !matches!(args[0], Operand::Reg(_)))
+        {
+            break;
+        }
+
+        ops.push(Op { addr, opcode, args });
+        addr += 1 + n;
+    }
+
+    (!ops.is_empty()).then_some(Block { start, end: addr, ops })
+}
+
+#[derive(Default)]
+pub struct Superblocks {
+    blocks: BTreeMap<u16, Arc<Block>>,
+    heat: HashMap<u16, u32>,
+}
+
+impl Superblocks {
+    // the superblock starting at addr, making one if the address just got
+    // hot
+    pub fn get(&mut self, ram: &Ram, addr: u16) -> Option<Arc<Block>> {
+        if let Some(block) = self.blocks.get(&addr) {
+            return Some(block.clone());
+        }
+
+        let heat = self.heat.entry(addr).or_insert(0);
+        *heat += 1;
+        if *heat < HOT {
+            return None;
+        }
+        self.heat.remove(&addr);
+
+        let block = Arc::new(decode(ram, addr)?);
+        self.blocks.insert(addr, block.clone());
+        Some(block)
+    }
+
+    // drop every superblock covering addr
+    pub fn invalidate(&mut self, addr: u16) {
+        if self.blocks.is_empty() {
+            return;
+        }
+        let stale: Vec<_> = self
+            .blocks
+            .range(addr.saturating_sub(MAX_WORDS)..=addr)
+            .filter(|(_, b)| b.end > addr)
+            .map(|(start, _)| *start)
+            .collect();
+        for start in stale {
+            self.blocks.remove(&start);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.heat.clear();
+    }
+}
@@ -0,0 +1,114 @@
+/*!
+ * Named addresses for the loaded ROM.
+ *
+ * A symbols file has one `name addr` pair per line, blank lines and lines
+ * starting with `#` are ignored:
+ *
+ * ```text
+ * # the teleporter confirmation routine
+ * teleporter_check 6049
+ * ```
+ *
+ * A `# rom <md5>` comment records the ROM the symbols were made for, so
+ * they can be flagged when loaded with a different one (see `rominfo`).
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Default, Clone, Debug)]
+pub struct Symbols {
+    by_name: BTreeMap<String, u16>,
+    // md5 of the ROM these are for, from a `# rom` comment
+    rom: Option<String>,
+    // where they were loaded from
+    file: Option<String>,
+}
+
+impl Symbols {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut by_name = BTreeMap::new();
+        let mut rom = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if let Some(md5) = line.strip_prefix("# rom ") {
+                rom = Some(md5.trim().to_string());
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let [name, addr] = fields[..] else {
+                return Err(format!(
+                    "line {}: expected `name addr`, found '{}'",
+                    i + 1,
+                    line
+                ));
+            };
+            let addr: u16 = match addr.parse() {
+                Ok(addr) if addr < 32768 => addr,
+                _ => {
+                    return Err(format!(
+                        "line {}: invalid address '{}' for {}",
+                        i + 1,
+                        addr,
+                        name
+                    ));
+                }
+            };
+
+            by_name.insert(name.to_string(), addr);
+        }
+
+        Ok(Self { by_name, rom, file: None })
+    }
+
+    pub fn load(file: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {}: {}", file, e))?;
+        let mut symbols = Self::parse(&data)?;
+        symbols.file = Some(file.to_string());
+        Ok(symbols)
+    }
+
+    pub fn rom(&self) -> Option<&str> {
+        self.rom.as_deref()
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    // the name of the symbol at exactly this address
+    pub fn name_of(&self, addr: u16) -> Option<&str> {
+        self.by_name
+            .iter()
+            .find(|(_, a)| **a == addr)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // add a symbol unless the name is already taken
+    pub fn add(&mut self, name: &str, addr: u16) {
+        self.by_name.entry(name.to_string()).or_insert(addr);
+    }
+
+    // every symbol, by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.by_name.iter().map(|(name, addr)| (name.as_str(), *addr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
@@ -0,0 +1,139 @@
+/*!
+ * Saves as plain decimal text, for trading states with other VMs.
+ *
+ * Plenty of Synacor VMs dump their state the same simple way - the program
+ * counter, the registers, the stack and then memory, as decimal numbers -
+ * and `/export game.state` (any file ending in .state) writes one:
+ *
+ * ```text
+ * pc: 2734
+ * registers: 25975 25974 26006 0 101 0 0 0
+ * stack: 6080 16 6124 1 2952 25978 3568 3599 2708 5445 3
+ * memory:
+ * 21 21 19 87 19 101 19 108 19 99 19 111 19 109 19 101
+ * ...
+ * ```
+ *
+ * The stack is bottom first.  Loading a .state file reads the same thing,
+ * with the labels optional - without them the first line is the pc, the
+ * second the registers, the third the stack (blank if it's empty) and the
+ * rest memory.  `#` starts a comment.
+ *
+ * There's nothing in the format but the machine itself, so a state loaded
+ * from one starts counting instructions from 0 and only tracks calls made
+ * after it.
+ */
+
+use std::fmt::Write as _;
+
+use crate::vm::VM;
+
+pub const EXTENSION: &str = ".state";
+
+// memory words per line
+const WORDS_PER_LINE: usize = 16;
+
+const SECTIONS: [&str; 4] = ["pc", "registers", "stack", "memory"];
+
+fn join(words: &[u16]) -> String {
+    let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+    words.join(" ")
+}
+
+pub fn export(vm: &VM) -> String {
+    let mut out = String::new();
+    writeln!(out, "pc: {}", vm.pc()).unwrap();
+    writeln!(out, "registers: {}", join(&vm.registers())).unwrap();
+    writeln!(out, "stack: {}", join(&vm.stack)).unwrap();
+    writeln!(out, "memory:").unwrap();
+    for line in vm.ram().words().chunks(WORDS_PER_LINE) {
+        writeln!(out, "{}", join(line)).unwrap();
+    }
+    out
+}
+
+fn numbers(file: &str, n: usize, line: &str) -> Result<Vec<u16>, String> {
+    line.split_whitespace()
+        .map(|w| {
+            w.parse()
+                .map_err(|_| format!("{} line {}: bad number {:?}", file, n, w))
+        })
+        .collect()
+}
+
+// a VM from the text of a .state file, named file in errors
+pub fn import(file: &str, text: &str) -> Result<VM, String> {
+    let mut sections: [Vec<u16>; 4] = Default::default();
+    let mut seen = [false; 4];
+    let mut section = 0;
+    let mut labeled = false;
+
+    let lines = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .enumerate()
+        .skip_while(|(_, line)| line.is_empty());
+    for (i, line) in lines {
+        let n = i + 1;
+        let (label, rest) = match line.split_once(':') {
+            Some((label, rest)) => (Some(label.trim()), rest),
+            None => (None, line),
+        };
+        match label {
+            Some(label) => {
+                section =
+                    SECTIONS.iter().position(|s| *s == label).ok_or_else(
+                        || format!("{} line {}: unknown {:?}", file, n, label),
+                    )?;
+                labeled = true;
+            }
+            // memory goes on over any number of lines, and without labels an
+            // empty stack is a blank line
+            None if section == 3 || seen[section] => {
+                let next = (section + 1).min(3);
+                if rest.is_empty() && (next != 2 || labeled) {
+                    continue;
+                }
+                section = next;
+            }
+            None => {}
+        }
+        if section < 3 && seen[section] {
+            return Err(format!(
+                "{} line {}: {} given twice",
+                file, n, SECTIONS[section]
+            ));
+        }
+        seen[section] = true;
+        sections[section].extend(numbers(file, n, rest)?);
+    }
+
+    let [pc, registers, stack, memory] = sections;
+    if memory.is_empty() {
+        return Err(format!("{}: no memory", file));
+    }
+    if memory.len() > 32768 {
+        return Err(format!(
+            "{}: {} words of memory, more than the 32768 there are",
+            file,
+            memory.len()
+        ));
+    }
+    let pc = match pc[..] {
+        [pc] if pc < 32768 => pc,
+        _ => return Err(format!("{}: the pc should be one address", file)),
+    };
+    let registers: [u16; 8] = registers
+        .try_into()
+        .map_err(|_| format!("{}: there should be 8 registers", file))?;
+    if let Some(r) = registers.iter().find(|r| **r > 32767) {
+        return Err(format!("{}: register value {} is over 32767", file, r));
+    }
+
+    let rom: Vec<u8> = memory.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut vm = VM::new(rom);
+    vm.registers = registers;
+    vm.stack = stack;
+    vm.set_pc(pc);
+    Ok(vm)
+}
@@ -0,0 +1,50 @@
+/*!
+ * Limit how fast a shell runs instructions and takes input.
+ *
+ * When the game is hosted for several players on one machine, `--max-ips`
+ * caps the instructions a session runs per second and `--max-inputs` the
+ * lines of input it takes per minute, so a player brute forcing something
+ * in game can't starve everyone else of CPU.  Both are token buckets: a
+ * second's worth of instructions (or a minute's worth of input) can go at
+ * once, after which the shell sleeps to keep to the rate.
+ */
+
+use std::time::{Duration, Instant};
+
+pub struct Throttle {
+    // tokens added per second, and the most there can be
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Throttle {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self { rate, burst, tokens: burst, last: Instant::now() }
+    }
+
+    // n a second, up to a second's worth at once
+    pub fn per_second(n: u64) -> Self {
+        Self::new(n as f64, n as f64)
+    }
+
+    // n a minute, up to a minute's worth at once
+    pub fn per_minute(n: u64) -> Self {
+        Self::new(n as f64 / 60.0, n as f64)
+    }
+
+    // use up n tokens, returning how long to wait before they'd have been
+    // there
+    pub fn take(&mut self, n: u64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.tokens -= n as f64;
+        if self.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-self.tokens / self.rate)
+    }
+}
@@ -0,0 +1,224 @@
+/*!
+ * What happened when, over a playthrough.
+ *
+ * The shell notes each line of input, each new room, each save and each
+ * code found, with the instruction count at the time.  `/timeline FILE`
+ * writes them out - as JSON for a file ending in .json:
+ *
+ * ```text
+ * {"entries":[{"steps":250973,"seconds":0.4,"kind":"room","text":"Foothills"},
+ *             {"steps":250973,"seconds":3.1,"kind":"input","text":"take tablet"},...]}
+ * ```
+ *
+ * or drawn as an SVG for .svg, with a lane each for inputs, rooms, saves and
+ * codes.  Going across is one column per line of input rather than to scale
+ * with instructions, since one teleporter check runs more instructions than
+ * the rest of the game put together; the axis marks the instruction count
+ * every so often instead.
+ *
+ * Undoing commands takes their entries off the timeline too, so it shows
+ * the way the game actually got where it is.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::time::Instant;
+
+use crate::shell::group_digits;
+
+// width of a column (one line of input) in the SVG
+const COLUMN: usize = 14;
+
+// height of a lane, and the space for the labels on the left
+const LANE: usize = 60;
+const MARGIN: usize = 70;
+
+// columns between instruction counts on the axis
+const AXIS_EVERY: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Input,
+    Room,
+    Save,
+    Code,
+}
+
+const LANES: [(Kind, &str, &str); 4] = [
+    (Kind::Input, "inputs", "#69c"),
+    (Kind::Room, "rooms", "#6a6"),
+    (Kind::Save, "saves", "#c93"),
+    (Kind::Code, "codes", "#c36"),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub steps: u64,
+    // since the session started
+    pub seconds: f64,
+    pub kind: Kind,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Timeline {
+    pub entries: Vec<Entry>,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self { entries: vec![], started: Instant::now() }
+    }
+}
+
+// text for inside an svg element
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Timeline {
+    pub fn push(&mut self, steps: u64, kind: Kind, text: &str) {
+        let seconds = self.started.elapsed().as_secs_f64();
+        self.entries.push(Entry {
+            steps,
+            seconds: (seconds * 10.0).round() / 10.0,
+            kind,
+            text: text.to_string(),
+        });
+    }
+
+    // the last room entered
+    pub fn room(&self) -> Option<&str> {
+        let room = self.entries.iter().rfind(|e| e.kind == Kind::Room);
+        room.map(|e| e.text.as_str())
+    }
+
+    // forget what happened after the game went back to the prompt at steps,
+    // the input given there included, except saves, which are still on disk
+    pub fn rewind(&mut self, steps: u64) {
+        let undone = |e: &Entry| {
+            e.steps > steps || (e.steps == steps && e.kind == Kind::Input)
+        };
+        let Some(i) = self.entries.iter().position(undone) else {
+            return;
+        };
+        let later = self.entries.split_off(i);
+        self.entries.extend(later.into_iter().filter(|e| e.kind == Kind::Save));
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn svg(&self) -> String {
+        // each entry goes in the column of the input it came after
+        let mut column = 0;
+        let columns: Vec<usize> = self
+            .entries
+            .iter()
+            .map(|e| {
+                if e.kind == Kind::Input {
+                    column += 1;
+                }
+                column
+            })
+            .collect();
+
+        let width = MARGIN + (column + 2) * COLUMN;
+        let height = LANES.len() * LANE + 40;
+        let x = |column: usize| MARGIN + column * COLUMN + COLUMN / 2;
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+             height=\"{}\" font-family=\"sans-serif\" font-size=\"11\">",
+            width, height
+        )
+        .unwrap();
+        writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>")
+            .unwrap();
+
+        for (i, (kind, name, color)) in LANES.iter().enumerate() {
+            let y = i * LANE + LANE / 2;
+            writeln!(
+                out,
+                "<text x=\"4\" y=\"{}\" font-weight=\"bold\">{}</text>\n\
+                 <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#ddd\"/>",
+                y + 4,
+                name,
+                MARGIN,
+                y,
+                width,
+                y
+            )
+            .unwrap();
+
+            let entries = self.entries.iter().zip(&columns);
+            for (e, column) in entries.filter(|(e, _)| e.kind == *kind) {
+                let title = format!(
+                    "{} at step {}, {}s",
+                    e.text,
+                    group_digits(e.steps),
+                    e.seconds
+                );
+                writeln!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\">\
+                     <title>{}</title></circle>",
+                    x(*column),
+                    y,
+                    color,
+                    escape(&title)
+                )
+                .unwrap();
+                // inputs are too many to label, the rest get their text
+                if *kind != Kind::Input {
+                    writeln!(
+                        out,
+                        "<text x=\"{}\" y=\"{}\" transform=\"rotate(-30 {} {})\">{}</text>",
+                        x(*column) + 6,
+                        y - 6,
+                        x(*column) + 6,
+                        y - 6,
+                        escape(&e.text)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        // the instruction count under every few columns
+        let axis = LANES.len() * LANE + 20;
+        let mut steps = vec![None; column + 1];
+        for (e, column) in self.entries.iter().zip(&columns) {
+            steps[*column].get_or_insert(e.steps);
+        }
+        for (column, steps) in steps.iter().enumerate() {
+            if let Some(steps) = steps
+                && column % AXIS_EVERY == 0
+            {
+                writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{}\" fill=\"#888\">{}</text>",
+                    x(column),
+                    axis,
+                    group_digits(*steps)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(
+            out,
+            "<text x=\"4\" y=\"{}\" fill=\"#888\">instructions</text>",
+            axis
+        )
+        .unwrap();
+
+        out.push_str("</svg>\n");
+        out
+    }
+}
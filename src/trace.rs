@@ -0,0 +1,58 @@
+/*!
+ * Write call/ret events in the Chrome trace event format.
+ *
+ * The file can be opened in Perfetto (https://ui.perfetto.dev) or
+ * chrome://tracing to see the game's routines as a flame chart.  Timestamps
+ * are instruction counts rather than wall time, so a "microsecond" in the
+ * viewer is one instruction.
+ *
+ * Events are written as they happen, and the format allows the closing `]` to
+ * be missing, so a trace from a VM that crashed still loads.
+ */
+
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::tracesink::TraceSink;
+
+pub struct ChromeTrace {
+    out: BufWriter<File>,
+    events: u64,
+}
+
+impl ChromeTrace {
+    pub fn create(file: &str) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(file)?);
+        writeln!(out, "[")?;
+        Ok(Self { out, events: 0 })
+    }
+
+    fn event(&mut self, ph: &str, name: &str, ts: u64) {
+        let event = json!({
+            "name": name,
+            "ph": ph,
+            "ts": ts,
+            "pid": 1,
+            "tid": 1,
+        });
+        let sep = if self.events == 0 { "" } else { ",\n" };
+        write!(self.out, "{}{}", sep, event).unwrap();
+        self.events += 1;
+    }
+}
+
+impl TraceSink for ChromeTrace {
+    fn call(&mut self, _: u16, name: &str, steps: u64) {
+        self.event("B", name, steps);
+    }
+
+    fn ret(&mut self, _: u16, name: &str, steps: u64) {
+        self.event("E", name, steps);
+    }
+
+    fn finish(&mut self, _: u64) {
+        writeln!(self.out, "\n]").unwrap();
+        self.out.flush().unwrap();
+    }
+}
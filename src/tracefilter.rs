@@ -0,0 +1,221 @@
+/*!
+ * A small expression language for picking which instructions get traced.
+ *
+ * ```text
+ * opcode == wmem && a >= 6000 && a <= 7000
+ * addr == 6049 || (depth > 3 && !(r7 == 0))
+ * ```
+ *
+ * Fields:
+ *
+ * - `addr` - address of the instruction
+ * - `opcode` - the opcode, compare against mnemonics like `wmem` or numbers
+ * - `a`, `b`, `c` - operands, with registers replaced by their values
+ * - `r0` - `r7` - registers
+ * - `steps` - instructions executed so far
+ * - `depth` - number of routines being executed
+ *
+ * Comparisons are `== != < <= > >=`, combined with `&& || !` and parens.
+ *
+ * The same expressions search code that isn't running (`/find-code`).  There
+ * the operands are the words as written and each register stands for its own
+ * operand word, so `opcode == jt && a == r7` finds every `jt r7 ...`.
+ * The expression is compiled to closures once so checking it every step is
+ * cheap.
+ */
+
+use crate::disasm::Instruction;
+use crate::vm::MNEMONICS;
+
+// what a filter can see of the instruction about to run
+#[derive(Default)]
+pub struct Step {
+    pub addr: u16,
+    pub opcode: u16,
+    pub operands: [u16; 3],
+    pub registers: [u16; 8],
+    pub steps: u64,
+    pub depth: usize,
+}
+
+impl Step {
+    // an instruction as written rather than as it runs, see above
+    pub fn of_instruction(ins: &Instruction) -> Self {
+        let mut operands = [0; 3];
+        operands[..ins.operands.len()].copy_from_slice(&ins.operands);
+        Self {
+            addr: ins.addr,
+            opcode: ins.opcode,
+            operands,
+            registers: std::array::from_fn(|r| 32768 + r as u16),
+            ..Default::default()
+        }
+    }
+}
+
+type Value = Box<dyn Fn(&Step) -> u64 + Send>;
+type Pred = Box<dyn Fn(&Step) -> bool + Send>;
+
+pub struct TraceFilter {
+    src: String,
+    pred: Pred,
+}
+
+impl TraceFilter {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let tokens = tokenize(src)?;
+        let mut p = Parser { tokens, pos: 0 };
+        let pred = p.or()?;
+        if let Some(t) = p.peek() {
+            return Err(format!("unexpected '{}'", t));
+        }
+        Ok(Self { src: src.to_string(), pred })
+    }
+
+    pub fn matches(&self, step: &Step) -> bool {
+        (self.pred)(step)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_')
+            {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            let two: String =
+                chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["==", "!=", "<=", ">=", "&&", "||"].contains(&two.as_str()) {
+                tokens.push(two);
+                i += 2;
+            } else if "<>!()".contains(c) {
+                tokens.push(c.to_string());
+                i += 1;
+            } else {
+                return Err(format!("unexpected character '{}'", c));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t.ok_or_else(|| "unexpected end of filter".to_string())
+    }
+
+    fn or(&mut self) -> Result<Pred, String> {
+        let mut left = self.and()?;
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            let right = self.and()?;
+            left = Box::new(move |s| left(s) || right(s));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Pred, String> {
+        let mut left = self.not()?;
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            let right = self.not()?;
+            left = Box::new(move |s| left(s) && right(s));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Pred, String> {
+        match self.peek() {
+            Some("!") => {
+                self.pos += 1;
+                let inner = self.not()?;
+                Ok(Box::new(move |s| !inner(s)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                let inner = self.or()?;
+                match self.next()?.as_str() {
+                    ")" => Ok(inner),
+                    t => Err(format!("expected ')', found '{}'", t)),
+                }
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Pred, String> {
+        let left = self.value()?;
+        let op = self.next()?;
+        let right = self.value()?;
+
+        let pred: Pred = match op.as_str() {
+            "==" => Box::new(move |s| left(s) == right(s)),
+            "!=" => Box::new(move |s| left(s) != right(s)),
+            "<" => Box::new(move |s| left(s) < right(s)),
+            "<=" => Box::new(move |s| left(s) <= right(s)),
+            ">" => Box::new(move |s| left(s) > right(s)),
+            ">=" => Box::new(move |s| left(s) >= right(s)),
+            t => return Err(format!("expected a comparison, found '{}'", t)),
+        };
+        Ok(pred)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let t = self.next()?;
+
+        if let Ok(n) = t.parse::<u64>() {
+            return Ok(Box::new(move |_| n));
+        }
+        if let Some(op) = MNEMONICS.iter().position(|m| *m == t) {
+            let op = op as u64;
+            return Ok(Box::new(move |_| op));
+        }
+
+        let value: Value = match t.as_str() {
+            "addr" => Box::new(|s| s.addr as u64),
+            "opcode" => Box::new(|s| s.opcode as u64),
+            "a" => Box::new(|s| s.operands[0] as u64),
+            "b" => Box::new(|s| s.operands[1] as u64),
+            "c" => Box::new(|s| s.operands[2] as u64),
+            "steps" => Box::new(|s| s.steps),
+            "depth" => Box::new(|s| s.depth as u64),
+            r if r.len() == 2
+                && r.starts_with('r')
+                && (b'0'..=b'7').contains(&r.as_bytes()[1]) =>
+            {
+                let i = (r.as_bytes()[1] - b'0') as usize;
+                Box::new(move |s| s.registers[i] as u64)
+            }
+            t => return Err(format!("unknown field '{}'", t)),
+        };
+        Ok(value)
+    }
+}
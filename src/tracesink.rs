@@ -0,0 +1,78 @@
+/*!
+ * Watch a VM run as it goes, instead of writing a trace and reading it back.
+ *
+ * A trace of the whole game is gigabytes, most of it only ever read once by
+ * whatever is going to add it up.  A `TraceSink` added to a VM is handed
+ * each instruction before it runs (as trace filters see it), every call and
+ * return and every `wmem`, so a profiler or a bisector can keep just what it
+ * needs:
+ *
+ * ```
+ * use std::sync::{Arc, Mutex};
+ * use synacor_challenge::asm;
+ * use synacor_challenge::symbols::Symbols;
+ * use synacor_challenge::tracefilter::Step;
+ * use synacor_challenge::tracesink::TraceSink;
+ * use synacor_challenge::vm::VM;
+ *
+ * // instructions run at each address, and where memory was written
+ * #[derive(Default)]
+ * struct Counts {
+ *     runs: Vec<u64>,
+ *     writes: Vec<u16>,
+ * }
+ *
+ * struct Counter(Arc<Mutex<Counts>>);
+ *
+ * impl TraceSink for Counter {
+ *     fn instruction(&mut self, step: &Step) {
+ *         let runs = &mut self.0.lock().unwrap().runs;
+ *         runs.resize(runs.len().max(step.addr as usize + 1), 0);
+ *         runs[step.addr as usize] += 1;
+ *     }
+ *
+ *     fn wmem(&mut self, addr: u16, _: u16, _: u64) {
+ *         self.0.lock().unwrap().writes.push(addr);
+ *     }
+ * }
+ *
+ * let src = "set r0 3; top: wmem 100 r0; add r0 r0 32767; jt r0 top; halt";
+ * let words = asm::assemble(src, 0, &Symbols::default()).unwrap();
+ * let rom: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+ *
+ * let counts = Arc::new(Mutex::new(Counts::default()));
+ * let mut vm = VM::new(rom);
+ * vm.add_trace_sink(Box::new(Counter(counts.clone())));
+ * while !vm.is_halted() {
+ *     vm.step().unwrap();
+ * }
+ *
+ * let counts = counts.lock().unwrap();
+ * assert_eq!(counts.runs[3], 3);
+ * assert_eq!(counts.writes, [100, 100, 100]);
+ * ```
+ *
+ * Every method does nothing unless a sink says otherwise.  Routines still
+ * running when the program ends get a `ret` then, so each call has one, and
+ * `finish` comes last.  A VM with a sink runs one instruction at a time,
+ * never whole superblocks, so it's as slow as tracing.
+ */
+
+use crate::tracefilter::Step;
+
+pub trait TraceSink: Send {
+    // an instruction about to run
+    fn instruction(&mut self, _step: &Step) {}
+
+    // a routine was called, steps being the instructions run so far
+    fn call(&mut self, _routine: u16, _name: &str, _steps: u64) {}
+
+    // the innermost routine returned
+    fn ret(&mut self, _routine: u16, _name: &str, _steps: u64) {}
+
+    // wmem wrote value to addr
+    fn wmem(&mut self, _addr: u16, _value: u16, _steps: u64) {}
+
+    // the program has ended
+    fn finish(&mut self, _steps: u64) {}
+}
@@ -0,0 +1,113 @@
+/*!
+ * Check a ROM looks runnable before running it.
+ *
+ * A truncated download or the wrong file used to get as far as the VM and
+ * panic somewhere in the middle of the run.  Loading a ROM now checks it
+ * first and says what's wrong:
+ *
+ * ```text
+ * challenge.bin is 60099 bytes, an odd number - the last word is cut in half,
+ * is the file truncated?
+ * ```
+ *
+ * The size has to be an even number of bytes and no more than memory holds,
+ * and the first CHECK_INSTRUCTIONS instructions from address 0 (following
+ * jumps) have to decode, with every operand a number or a register.
+ */
+
+use crate::vm::{MNEMONICS, OPERANDS};
+
+// instructions from the entry point to check
+pub const CHECK_INSTRUCTIONS: usize = 64;
+
+// 32768 words of two bytes
+const MAX_BYTES: usize = 65536;
+
+// everything wrong with the ROM, named for messages as name
+pub fn check(name: &str, rom: &[u8]) -> Result<(), String> {
+    if rom.is_empty() {
+        return Err(format!("{} is empty", name));
+    }
+    if rom.len() % 2 == 1 {
+        return Err(format!(
+            "{} is {} bytes, an odd number - the last word is cut in half, \
+             is the file truncated?",
+            name,
+            rom.len()
+        ));
+    }
+    if rom.len() > MAX_BYTES {
+        return Err(format!(
+            "{} is {} bytes, more than the {} bytes memory holds - is it a ROM?",
+            name,
+            rom.len(),
+            MAX_BYTES
+        ));
+    }
+
+    let words: Vec<u16> =
+        rom.chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+
+    let mut addr = 0;
+    let mut seen = vec![];
+    for _ in 0..CHECK_INSTRUCTIONS {
+        if seen.contains(&addr) {
+            break;
+        }
+        seen.push(addr);
+
+        let Some(&opcode) = words.get(addr) else {
+            return Err(format!(
+                "{}: execution reaches {}, past the end of the ROM ({} \
+                 words) - is the file truncated?",
+                name,
+                addr,
+                words.len()
+            ));
+        };
+        let Some(&n) = OPERANDS.get(opcode as usize) else {
+            return Err(format!(
+                "{}: the word at {} is {}, which isn't an instruction{}",
+                name,
+                addr,
+                opcode,
+                if addr == 0 { " - is it a ROM?" } else { "" }
+            ));
+        };
+        let mnemonic = MNEMONICS[opcode as usize];
+        let Some(operands) = words.get(addr + 1..addr + 1 + n as usize) else {
+            return Err(format!(
+                "{}: the {} at {} runs past the end of the ROM ({} words) - \
+                 is the file truncated?",
+                name,
+                mnemonic,
+                addr,
+                words.len()
+            ));
+        };
+        if let Some((i, w)) =
+            operands.iter().enumerate().find(|(_, w)| **w > 32775)
+        {
+            return Err(format!(
+                "{}: operand {} of the {} at {} is {}, more than the largest \
+                 register (32775)",
+                name,
+                i + 1,
+                mnemonic,
+                addr,
+                w
+            ));
+        }
+
+        addr = match opcode {
+            // halt and ret end the path
+            0 | 18 => break,
+            6 if operands[0] < 32768 => operands[0] as usize,
+            // a jump through a register can't be followed
+            6 => break,
+            _ => addr + 1 + n as usize,
+        };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+/*!
+ * Keep the recent values of registers and memory words.
+ *
+ * `/track r7` or `/track 3952` starts recording each change to the value,
+ * with the instruction count it changed at, and `/history r7` prints them:
+ *
+ * ```text
+ * r7: 4 changes, ▁▁▅█
+ *          0  0
+ *     123456  1
+ *     123470  25734
+ *     123502  32767
+ * ```
+ *
+ * Only the last HISTORY_MAX changes of each value are kept.
+ */
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::memedit;
+use crate::ram::Ram;
+use crate::symbols::Symbols;
+
+// changes kept per value
+pub const HISTORY_MAX: usize = 256;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    Reg(u8),
+    Mem(u16),
+}
+
+impl Source {
+    // r0 - r7, or an address or symbol
+    pub fn parse(s: &str, symbols: &Symbols) -> Result<Self, String> {
+        if let Some(r) = s.strip_prefix('r')
+            && let Ok(r) = r.parse::<u8>()
+        {
+            if r > 7 {
+                return Err(format!("no register {}", s));
+            }
+            return Ok(Source::Reg(r));
+        }
+        memedit::parse_addr(s, symbols).map(Source::Mem)
+    }
+
+    fn value(&self, registers: &[u16; 8], ram: &Ram) -> u16 {
+        match self {
+            Source::Reg(r) => registers[*r as usize],
+            Source::Mem(addr) => ram.word(*addr).unwrap_or(0),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Reg(r) => write!(f, "r{}", r),
+            Source::Mem(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+pub struct Tracked {
+    pub source: Source,
+    // (instruction count, value), oldest first
+    pub changes: VecDeque<(u64, u16)>,
+}
+
+impl Tracked {
+    // the values as a line of bars, lowest to highest
+    pub fn sparkline(&self) -> String {
+        let min = self.changes.iter().map(|(_, v)| *v).min().unwrap_or(0);
+        let max = self.changes.iter().map(|(_, v)| *v).max().unwrap_or(0);
+        let range = (max - min).max(1) as usize;
+        self.changes
+            .iter()
+            .map(|(_, v)| {
+                SPARKS[(*v - min) as usize * (SPARKS.len() - 1) / range]
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct ValueHistory {
+    tracked: Vec<Tracked>,
+}
+
+impl ValueHistory {
+    pub fn is_empty(&self) -> bool {
+        self.tracked.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tracked> {
+        self.tracked.iter()
+    }
+
+    pub fn get(&self, source: Source) -> Option<&Tracked> {
+        self.tracked.iter().find(|t| t.source == source)
+    }
+
+    // start recording, with the value it has now.  false if it already was
+    pub fn add(
+        &mut self,
+        source: Source,
+        steps: u64,
+        registers: &[u16; 8],
+        ram: &Ram,
+    ) -> bool {
+        if self.get(source).is_some() {
+            return false;
+        }
+        let value = source.value(registers, ram);
+        self.tracked.push(Tracked {
+            source,
+            changes: VecDeque::from([(steps, value)]),
+        });
+        true
+    }
+
+    pub fn remove(&mut self, source: Source) -> bool {
+        let len = self.tracked.len();
+        self.tracked.retain(|t| t.source != source);
+        self.tracked.len() != len
+    }
+
+    // note any values that changed, at instruction count steps
+    pub fn record(&mut self, steps: u64, registers: &[u16; 8], ram: &Ram) {
+        for tracked in &mut self.tracked {
+            let value = tracked.source.value(registers, ram);
+            if tracked.changes.back().is_some_and(|(_, v)| *v == value) {
+                continue;
+            }
+            if tracked.changes.len() == HISTORY_MAX {
+                tracked.changes.pop_front();
+            }
+            tracked.changes.push_back((steps, value));
+        }
+    }
+}
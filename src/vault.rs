@@ -0,0 +1,113 @@
+/*!
+ * Read the vault grid out of memory for `/vault`, as `solve orb` reads it.
+ *
+ * Where the grid is isn't known for any build (see `offsets`), so it comes
+ * from a `vault_grid` symbol the player has found and added.  The layout
+ * is assumed rather than checked against a ROM: SIZE * SIZE words, row by
+ * row from the north west corner, numbers as themselves and operators as
+ * the ascii codes of `+`, `-` and `*`.  Tiles alternate between numbers and
+ * operators starting with a number in the south west corner, and a word
+ * that doesn't fit is an error, so a wrong address shows up rather than
+ * giving a grid of nonsense:
+ *
+ * ```text
+ * * 8 - 1
+ * 4 * 11 *
+ * + 4 - 18
+ * 22 - 9 *
+ * ```
+ */
+
+use crate::ram::Ram;
+
+// tiles on each side
+pub const SIZE: u16 = 4;
+
+// the grid starting at base, one row per line as `solve orb` reads it
+pub fn grid(ram: &Ram, base: u16) -> Result<String, String> {
+    let mut grid = String::new();
+    for y in 0..SIZE {
+        let mut row = vec![];
+        for x in 0..SIZE {
+            let Some(word) =
+                base.checked_add(y * SIZE + x).and_then(|a| ram.word(a))
+            else {
+                return Err(format!("the grid from {} runs past memory", base));
+            };
+            let addr = base + y * SIZE + x;
+
+            let tile = if (x + y) % 2 == (SIZE - 1) % 2 {
+                match word {
+                    0..=32767 => word.to_string(),
+                    n => {
+                        return Err(format!(
+                            "expected a number at addr {}, found {}",
+                            addr, n
+                        ));
+                    }
+                }
+            } else {
+                match word {
+                    43 => "+".to_string(),
+                    45 => "-".to_string(),
+                    42 => "*".to_string(),
+                    n => {
+                        return Err(format!(
+                            "expected an operator at addr {}, found {}",
+                            addr, n
+                        ));
+                    }
+                }
+            };
+            row.push(tile);
+        }
+        grid += &row.join(" ");
+        grid.push('\n');
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ram(words: &[u16]) -> Ram {
+        let bytes: Vec<u8> =
+            words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        Ram::new(&bytes)
+    }
+
+    #[test]
+    fn reads_the_grid() {
+        let (add, sub, mul) = (43, 45, 42);
+        let words = [
+            7, 7, // whatever is before the grid
+            mul, 8, sub, 1, //
+            4, mul, 11, mul, //
+            add, 4, sub, 18, //
+            22, sub, 9, mul,
+        ];
+        assert_eq!(
+            grid(&ram(&words), 2),
+            Ok("* 8 - 1\n4 * 11 *\n+ 4 - 18\n22 - 9 *\n".to_string())
+        );
+    }
+
+    #[test]
+    fn wrong_address() {
+        let words = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        assert_eq!(
+            grid(&ram(&words), 0),
+            Err("expected an operator at addr 0, found 1".to_string())
+        );
+    }
+
+    #[test]
+    fn past_memory() {
+        let words = [42, 8, 45, 1, 4, 42];
+        assert_eq!(
+            grid(&ram(&words), 0),
+            Err("the grid from 0 runs past memory".to_string())
+        );
+    }
+}
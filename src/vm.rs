@@ -0,0 +1,1971 @@
+/*!
+ * The Synacor VM itself.
+ *
+ * The VM only runs code - it doesn't read the terminal or print anything.
+ * Input is queued with `queue_input`, output collected with `take_output`,
+ * and anything a frontend should react to (breakpoints and such) comes out of
+ * `take_events`.  See `shell` for the interactive frontend.
+ *
+ * A VM's run depends only on its image and the input it's given - nothing
+ * is shared with other VMs, log spans included - so any number can run on
+ * the same thread or on different ones and each plays out the same as if
+ * it were alone:
+ *
+ * ```
+ * use std::thread;
+ * use synacor_challenge::asm;
+ * use synacor_challenge::symbols::Symbols;
+ * use synacor_challenge::vm::VM;
+ *
+ * // echo input up to a '.', summing it in r1
+ * let src = "top: in r0; add r1 r1 r0; out r0; eq r2 r0 '.'; jf r2 top; halt";
+ * let words = asm::assemble(src, 0, &Symbols::default()).unwrap();
+ * let rom: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+ *
+ * // two threads, each stepping two VMs in lockstep
+ * let threads: Vec<_> = (0..2)
+ *     .map(|_| {
+ *         let rom = rom.clone();
+ *         thread::spawn(move || {
+ *             let mut a = VM::new(rom.clone());
+ *             let mut b = VM::new(rom);
+ *             a.queue_input(b"hello.");
+ *             b.queue_input(b"hello.");
+ *             while !a.is_halted() {
+ *                 a.step().unwrap();
+ *                 b.step().unwrap();
+ *                 assert_eq!(a.state_hash(), b.state_hash());
+ *             }
+ *             assert!(b.is_halted());
+ *             (a.state_hash(), a.take_output())
+ *         })
+ *     })
+ *     .collect();
+ * let runs: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+ * assert_eq!(runs[0], runs[1]);
+ * assert_eq!(runs[0].1, b"hello.");
+ * ```
+ */
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use tracing::{Level, debug, info, trace};
+
+use crate::arity::Arity;
+use crate::audit;
+use crate::debugger::{Breakpoints, Event, Frame};
+use crate::disasm;
+use crate::hooks::Hook;
+use crate::journal::{self, JOURNAL_MAX, Journal};
+use crate::logging::CallSpans;
+use crate::machine::Machine;
+use crate::map;
+use crate::ram::Ram;
+use crate::sampler::Sampler;
+use crate::saves::Thumbnail;
+use crate::strings;
+use crate::superblock::{Block, Operand, Superblocks};
+use crate::symbols::Symbols;
+use crate::trace::ChromeTrace;
+use crate::tracefilter::{self, TraceFilter};
+use crate::tracesink::TraceSink;
+use crate::valuehistory::ValueHistory;
+
+// the number of operands each instruction takes, indexed by opcode
+pub const OPERANDS: [u16; 22] =
+    [0, 2, 1, 1, 3, 3, 1, 2, 2, 3, 3, 3, 3, 3, 2, 2, 2, 1, 0, 1, 1, 0];
+
+// bumped whenever what goes into `VM::state_hash` changes, so hashes
+// recorded by one version aren't compared against another
+pub const STATE_HASH_VERSION: u32 = 1;
+
+// instruction names, indexed by opcode
+pub const MNEMONICS: [&str; 22] = [
+    "halt", "set", "push", "pop", "eq", "gt", "jmp", "jt", "jf", "add", "mult",
+    "mod", "and", "or", "not", "rmem", "wmem", "call", "ret", "out", "in",
+    "noop",
+];
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct VM {
+    pub(crate) ram: Ram,
+    pub(crate) registers: [u16; 8],
+    pub(crate) addr: u16, // addr pointer
+    pub(crate) stack: Vec<u16>,
+    pub(crate) running: bool,
+    pub(crate) input_buffer: Vec<u8>,
+    #[serde(skip)]
+    pub(crate) symbols: Symbols,
+    // everything printed since the last take_output
+    #[serde(skip)]
+    output: Vec<u8>,
+    // input characters consumed since the last take_echo
+    #[serde(skip)]
+    echo: Vec<u8>,
+    // things that happened since the last take_events
+    #[serde(skip)]
+    events: Vec<Event>,
+    // set after stopping for a breakpoint or budget so the next step runs the
+    // instruction instead of stopping again
+    #[serde(skip)]
+    resume: bool,
+    // number of instructions executed
+    #[serde(default)]
+    pub(crate) steps: u64,
+    // bitmap of every address an instruction has been executed from
+    #[serde(skip)]
+    coverage: Vec<u64>,
+    // string addresses being watched and their last decoded value
+    #[serde(skip)]
+    pub(crate) watched_strings: Vec<(u16, Option<String>)>,
+    // characters written here with wmem are a debug message, see
+    // `set_debug_port`, and the line so far
+    #[serde(skip)]
+    debug_port: Option<u16>,
+    #[serde(skip)]
+    debug_line: Vec<u8>,
+    // most values the stack can hold, and where to flag a push that didn't
+    // fit, see `set_stack_limit`
+    #[serde(skip)]
+    stack_limit: Option<usize>,
+    #[serde(skip)]
+    stack_flag: Option<u16>,
+    #[serde(skip)]
+    pub(crate) breakpoints: Breakpoints,
+    // routines currently being executed, innermost last
+    #[serde(skip)]
+    pub(crate) frames: Vec<Frame>,
+    // ram as it was when loaded, to tell which words have been modified
+    #[serde(skip)]
+    original: Option<Ram>,
+    // instruction budget for a single call, by routine address
+    #[serde(skip)]
+    pub(crate) budgets: BTreeMap<u16, u64>,
+    // where the player is and what was printed last, to tell saves apart
+    #[serde(default)]
+    pub(crate) thumbnail: Thumbnail,
+    // fault on memory accesses outside the loaded image instead of treating
+    // memory as the full 15-bit address space, and refuse patches outside it
+    #[serde(skip)]
+    strict: bool,
+    // check arithmetic results against the spec, see `audit`
+    #[serde(skip)]
+    audit: bool,
+    // report calls that return with a different stack depth than they were
+    // made with
+    #[serde(skip)]
+    stack_check: bool,
+    // only instructions matching this are logged
+    #[serde(skip)]
+    pub(crate) trace_filter: Option<TraceFilter>,
+    // set when the current instruction didn't match the trace filter
+    #[serde(skip)]
+    quiet: bool,
+    // told about each instruction, call, return and memory write, see
+    // `tracesink`
+    #[serde(skip)]
+    trace_sinks: Vec<Box<dyn TraceSink>>,
+    // call stacks are counted here every so often, see `sampler`
+    #[serde(skip)]
+    sampler: Option<Sampler>,
+    // address just past the last push executed, to spot `push x; ret`
+    #[serde(skip)]
+    pushed: Option<u16>,
+    // a tracing span for each frame, see `logging`
+    #[serde(skip)]
+    spans: CallSpans,
+    // input to type when execution reaches an address
+    #[serde(skip)]
+    pub(crate) hooks: Vec<Hook>,
+    // run hot straight-line code from superblocks, see `superblock`
+    #[serde(skip)]
+    fast: bool,
+    #[serde(skip)]
+    superblocks: Superblocks,
+    // set when the last instruction jumped, superblocks start at jump
+    // targets
+    #[serde(skip)]
+    jumped: bool,
+    // decoded operands by instruction address, see `operands`
+    #[serde(skip)]
+    operand_cache: Vec<Option<[Option<Operand>; 3]>>,
+    // registers and memory whose changes are being recorded
+    #[serde(skip)]
+    pub(crate) value_history: ValueHistory,
+    // how routines use registers and the stack, while it's being worked out
+    #[serde(skip)]
+    arity: Option<Arity>,
+    // what each instruction changed, to step back over it, and how many
+    // instructions it keeps, see `journal`
+    #[serde(skip)]
+    journal: Option<Journal>,
+    #[serde(skip)]
+    journal_max: Option<usize>,
+}
+
+// the VM's state at one moment, to go back to later
+#[derive(Clone)]
+pub struct Snapshot {
+    ram: Ram,
+    registers: [u16; 8],
+    addr: u16,
+    stack: Vec<u16>,
+    running: bool,
+    input_buffer: Vec<u8>,
+    steps: u64,
+    frames: Vec<Frame>,
+    thumbnail: Thumbnail,
+}
+
+impl Snapshot {
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn thumbnail(&self) -> &Thumbnail {
+        &self.thumbnail
+    }
+}
+
+// why an instruction couldn't run, see `step`.  pc is the address of the
+// instruction.  each kind has a code that never changes (see `code`), for
+// scripts to match on instead of the message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VmError {
+    // stepped after the program halted
+    Halted,
+    // pop with nothing on the stack
+    StackUnderflow { pc: u16 },
+    // an operand word that's neither a number nor a register
+    InvalidOperand { pc: u16, addr: u16, word: u16 },
+    // a number where the instruction writes to a register
+    NotARegister { pc: u16, addr: u16, word: u16 },
+    UnknownOpcode { pc: u16, opcode: u16 },
+    // in strict mode, reading or writing outside the loaded image
+    OutOfBoundsMemory { pc: u16, addr: u16 },
+    // mod by zero
+    DivideByZero { pc: u16 },
+    // push or call with the stack at its limit, see `set_stack_limit`
+    StackOverflow { pc: u16, limit: usize },
+}
+
+// the code for each kind of fault, in the order they were given out.  new
+// kinds get new codes at the end, and a code is never reused
+pub const ERROR_CODES: [(&str, &str); 8] = [
+    ("E0001", "unknown opcode"),
+    ("E0002", "stack underflow"),
+    ("E0003", "invalid operand"),
+    ("E0004", "not a register"),
+    ("E0005", "memory out of bounds"),
+    ("E0006", "divide by zero"),
+    ("E0007", "stack overflow"),
+    ("E0008", "halted"),
+];
+
+impl VmError {
+    // ie. "E0002", see ERROR_CODES
+    pub fn code(&self) -> &'static str {
+        let i = match self {
+            VmError::UnknownOpcode { .. } => 0,
+            VmError::StackUnderflow { .. } => 1,
+            VmError::InvalidOperand { .. } => 2,
+            VmError::NotARegister { .. } => 3,
+            VmError::OutOfBoundsMemory { .. } => 4,
+            VmError::DivideByZero { .. } => 5,
+            VmError::StackOverflow { .. } => 6,
+            VmError::Halted => 7,
+        };
+        ERROR_CODES[i].0
+    }
+
+    // the message without the code
+    pub fn message(&self) -> String {
+        match self {
+            VmError::Halted => "the program has halted".to_string(),
+            VmError::StackUnderflow { pc } => {
+                format!("pop from an empty stack at {}", pc)
+            }
+            VmError::InvalidOperand { pc, addr, word } => format!(
+                "invalid operand {} at {} (instruction at {})",
+                word, addr, pc
+            ),
+            VmError::NotARegister { pc, addr, word } => format!(
+                "operand {} at {} should be a register (instruction at {})",
+                word, addr, pc
+            ),
+            VmError::UnknownOpcode { pc, opcode } => {
+                format!("unknown instruction {} at {}", opcode, pc)
+            }
+            VmError::OutOfBoundsMemory { pc, addr } => format!(
+                "access outside of memory at {} (instruction at {})",
+                addr, pc
+            ),
+            VmError::DivideByZero { pc } => format!("mod by zero at {}", pc),
+            VmError::StackOverflow { pc, limit } => format!(
+                "push past the stack limit of {} values at {}",
+                limit, pc
+            ),
+        }
+    }
+
+    // address of the instruction that faulted
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            VmError::Halted => None,
+            VmError::StackUnderflow { pc }
+            | VmError::InvalidOperand { pc, .. }
+            | VmError::NotARegister { pc, .. }
+            | VmError::UnknownOpcode { pc, .. }
+            | VmError::OutOfBoundsMemory { pc, .. }
+            | VmError::DivideByZero { pc }
+            | VmError::StackOverflow { pc, .. } => Some(*pc),
+        }
+    }
+}
+
+// ie. "E0002: pop from an empty stack at 5"
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+// {"code":"E0002","message":"pop from an empty stack at 5","pc":5}, for
+// JSON reports
+impl Serialize for VmError {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut state = s.serialize_struct("VmError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("pc", &self.pc())?;
+        state.end()
+    }
+}
+
+impl std::error::Error for VmError {}
+
+// 64-bit FNV-1a, see `VM::state_hash`
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl VM {
+    pub fn new(rom: Vec<u8>) -> Self {
+        // the two share pages until the program writes to them
+        let ram = Ram::new(&rom);
+        Self {
+            original: Some(ram.clone()),
+            ram,
+            running: true,
+            ..Default::default()
+        }
+    }
+
+    // treat the current ram as the loaded image, ie. after restoring a save
+    pub fn mark_loaded(&mut self) {
+        self.original = Some(self.ram.clone());
+    }
+
+    // memory as it was loaded
+    pub fn image(&self) -> &Ram {
+        self.original.as_ref().unwrap_or(&self.ram)
+    }
+
+    // the instructions in memory as it is now, see `disasm::disassemble`
+    pub fn disassemble(&self, range: Range<u16>) -> Vec<disasm::Instruction> {
+        disasm::disassemble(&self.ram.words(), range)
+    }
+
+    // true if the word at addr differs from the loaded image
+    pub fn modified(&self, addr: u16) -> bool {
+        self.original.as_ref().is_some_and(|o| !o.same_word(&self.ram, addr))
+    }
+
+    pub fn is_halted(&self) -> bool {
+        !self.running
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.addr
+    }
+
+    pub fn ram(&self) -> &Ram {
+        &self.ram
+    }
+
+    pub fn registers(&self) -> [u16; 8] {
+        self.registers
+    }
+
+    // true if an instruction has been executed from this address since the
+    // VM was loaded
+    pub fn executed(&self, addr: u16) -> bool {
+        let addr = addr as usize;
+        self.coverage
+            .get(addr / 64)
+            .is_some_and(|bits| bits & (1 << (addr % 64)) != 0)
+    }
+
+    // everything printed since the last call
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    // input read by the game since the last call
+    pub fn take_echo(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.echo)
+    }
+
+    // breakpoints hit and such since the last call
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    // true if the next instruction is `in` and there is no buffered input
+    // (or hook to provide some) left for it, ie. the game is sitting at a
+    // prompt
+    pub fn wants_input(&self) -> bool {
+        self.running
+            && self.input_buffer.is_empty()
+            && self.get_ram(self.addr) == 20
+            && !self.hooks.iter().any(|h| h.addr == self.addr)
+    }
+
+    fn push_stack(&mut self, value: u16) {
+        trace!("pushing {} onto the stack", value);
+        self.stack.push(value);
+    }
+
+    fn pop_stack(&mut self) -> Result<u16, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow { pc: self.addr })
+    }
+
+    pub fn dump_state(&self) {
+        println!("loaded rom of size {}", self.ram.len());
+        for (i, register) in self.registers.iter().enumerate() {
+            println!("register {}: {}", i, register);
+        }
+        for (i, value) in self.stack.iter().enumerate() {
+            println!("stack {}: {}", i, value);
+        }
+        println!("running={}, addr={}", self.running, self.addr);
+    }
+
+    // a word of memory, where past the end of the image reads as zero.  the
+    // program's own reads go through `read`
+    pub(crate) fn get_ram(&self, addr: u16) -> u16 {
+        let Some(num) = self.ram.word(addr) else {
+            return 0;
+        };
+        trace!("self.get_ram: addr={} num={}", addr, num);
+
+        num
+    }
+
+    // a word of memory being read by the program, which in strict mode has
+    // to be inside the image
+    fn read(&self, addr: u16) -> Result<u16, VmError> {
+        if self.strict && self.ram.word(addr).is_none() {
+            return Err(VmError::OutOfBoundsMemory { pc: self.addr, addr });
+        }
+        Ok(self.get_ram(addr))
+    }
+
+    // write a word of memory for the program, see `read`
+    fn store(&mut self, addr: u16, value: u16) -> Result<(), VmError> {
+        if self.strict && self.ram.word(addr).is_none() {
+            return Err(VmError::OutOfBoundsMemory { pc: self.addr, addr });
+        }
+        self.write_ram(addr, value);
+        Ok(())
+    }
+
+    // the operands of the instruction at the current address, decoded the
+    // first time it runs and cached until something writes over it
+    fn operands(
+        &mut self,
+        opcode: u16,
+    ) -> Result<[Option<Operand>; 3], VmError> {
+        let i = self.addr as usize;
+        if let Some(Some(args)) = self.operand_cache.get(i) {
+            return Ok(*args);
+        }
+
+        let mut args = [None; 3];
+        let n = OPERANDS.get(opcode as usize).copied().unwrap_or(0);
+        for (j, arg) in args.iter_mut().enumerate().take(n as usize) {
+            *arg = Operand::decode(self.read(self.addr + 1 + j as u16)?);
+        }
+
+        if self.operand_cache.is_empty() {
+            self.operand_cache = vec![None; 32768];
+        }
+        if let Some(entry) = self.operand_cache.get_mut(i) {
+            *entry = Some(args);
+        }
+        Ok(args)
+    }
+
+    // forget the decoded operands of any instruction using the word at addr
+    fn invalidate_operands(&mut self, addr: u16) {
+        if self.operand_cache.is_empty() {
+            return;
+        }
+        for a in addr.saturating_sub(3)..=addr {
+            if let Some(entry) = self.operand_cache.get_mut(a as usize) {
+                *entry = None;
+            }
+        }
+    }
+
+    // operand n (from 1) of the current instruction, which has to be a
+    // register
+    fn arg_register(
+        &self,
+        args: &[Option<Operand>; 3],
+        n: u16,
+    ) -> Result<u16, VmError> {
+        match args[n as usize - 1] {
+            Some(Operand::Reg(r)) => Ok(r as u16),
+            Some(Operand::Lit(word)) => Err(VmError::NotARegister {
+                pc: self.addr,
+                addr: self.addr + n,
+                word,
+            }),
+            None => Err(self.invalid_operand(n)),
+        }
+    }
+
+    // the value of operand n (from 1) of the current instruction - either
+    // the literal or the register's value
+    fn arg_value(
+        &self,
+        args: &[Option<Operand>; 3],
+        n: u16,
+    ) -> Result<u16, VmError> {
+        match args[n as usize - 1] {
+            Some(Operand::Reg(r)) => {
+                info!(
+                    "(addr={}) register {} read: {}",
+                    self.addr + n,
+                    r,
+                    self.registers[r as usize]
+                );
+                Ok(self.registers[r as usize])
+            }
+            Some(Operand::Lit(v)) => Ok(v),
+            None => Err(self.invalid_operand(n)),
+        }
+    }
+
+    fn invalid_operand(&self, n: u16) -> VmError {
+        let addr = self.addr + n;
+        let word = self.get_ram(addr);
+        VmError::InvalidOperand { pc: self.addr, addr, word }
+    }
+
+    // type input when execution reaches an address, see `hooks`
+    pub fn add_hooks(&mut self, hooks: Vec<Hook>) {
+        self.hooks.extend(hooks);
+    }
+
+    // queue the input of every hook at the current address that hasn't
+    // fired since the game last waited for input
+    fn fire_hooks(&mut self) {
+        // an `in` with nothing to read is where the game would wait, so
+        // every hook can fire again
+        if self.input_buffer.is_empty() && self.ram.word(self.addr) == Some(20)
+        {
+            for hook in &mut self.hooks {
+                hook.fired = false;
+            }
+        }
+        let here = |h: &&mut Hook| h.addr == self.addr && !h.fired;
+        for hook in self.hooks.iter_mut().filter(here) {
+            hook.fired = true;
+            hook.hits += 1;
+            debug!(
+                "hook at {}: \"{}\"",
+                hook.addr,
+                String::from_utf8_lossy(&hook.input).escape_debug()
+            );
+            self.input_buffer.extend(&hook.input);
+        }
+    }
+
+    pub fn register(&self, register: u16) -> u16 {
+        self.registers[register as usize]
+    }
+
+    // number of routines currently being executed
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    // call the routine at addr as if a `call` instruction were at the
+    // current address, so it returns here
+    pub fn call(&mut self, addr: u16) {
+        self.enter(addr, self.addr);
+    }
+
+    // push a frame and the return address, and jump to the routine
+    fn enter(&mut self, routine: u16, return_addr: u16) {
+        let own = self.budgets.get(&routine).map(|n| self.steps + n);
+        let outer = self.frames.last().and_then(|f| f.deadline);
+        let deadline = match (own, outer) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        self.frames.push(Frame {
+            routine,
+            return_addr,
+            depth: self.stack.len(),
+            entered: self.steps,
+            deadline,
+        });
+        self.push_stack(return_addr);
+        self.jump(routine);
+        self.spans.sync(&self.frames, &self.symbols);
+
+        if !self.trace_sinks.is_empty() {
+            let name = routine_name(&self.symbols, routine);
+            for sink in &mut self.trace_sinks {
+                sink.call(routine, &name, self.steps);
+            }
+        }
+    }
+
+    // pop the frames whose return address is no longer on the stack - the
+    // one a ret just returned from, and any whose routine got rid of its
+    // return address some other way
+    fn drop_returned_frames(&mut self) {
+        while self.frames.last().is_some_and(|f| f.depth >= self.stack.len()) {
+            let frame = self.frames.pop().unwrap();
+            if !self.trace_sinks.is_empty() {
+                let name = routine_name(&self.symbols, frame.routine);
+                for sink in &mut self.trace_sinks {
+                    sink.ret(frame.routine, &name, self.steps);
+                }
+            }
+        }
+        self.spans.sync(&self.frames, &self.symbols);
+    }
+
+    pub fn set_trace_filter(&mut self, filter: Option<TraceFilter>) {
+        self.quiet = false;
+        self.trace_filter = filter;
+    }
+
+    // the instruction about to run, as seen by trace filters
+    fn trace_step(&self) -> tracefilter::Step {
+        let words = |addr: u16| self.ram.word(addr).unwrap_or(0);
+
+        let mut operands = [0; 3];
+        for (i, operand) in operands.iter_mut().enumerate() {
+            let w = words(self.addr.wrapping_add(1 + i as u16));
+            *operand = match w {
+                32768..=32775 => self.registers[(w - 32768) as usize],
+                w => w,
+            };
+        }
+
+        tracefilter::Step {
+            addr: self.addr,
+            opcode: words(self.addr),
+            operands,
+            registers: self.registers,
+            steps: self.steps,
+            depth: self.frames.len(),
+        }
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_audit(&mut self, on: bool) {
+        self.audit = on;
+    }
+
+    // treat characters the program writes to addr as debug output, so a
+    // homebrew ROM can print messages without them going to `out`.  each
+    // line comes out as an Event::Debug, and the write still goes to memory
+    pub fn set_debug_port(&mut self, addr: Option<u16>) {
+        self.debug_port = addr;
+        self.debug_line.clear();
+    }
+
+    // give the stack a size, so a homebrew ROM can be tested running out of
+    // it.  a push or call that doesn't fit faults, or with a flag address is
+    // skipped and 1 written there instead, for the program to notice and
+    // clear
+    pub fn set_stack_limit(&mut self, limit: Option<usize>, flag: Option<u16>) {
+        self.stack_limit = limit;
+        self.stack_flag = flag;
+    }
+
+    // true if a push has to be skipped, see `set_stack_limit`
+    fn stack_full(&mut self) -> Result<bool, VmError> {
+        let Some(limit) = self.stack_limit else {
+            return Ok(false);
+        };
+        if self.stack.len() < limit {
+            return Ok(false);
+        }
+        match self.stack_flag {
+            Some(flag) => {
+                self.store(flag, 1)?;
+                Ok(true)
+            }
+            None => Err(VmError::StackOverflow { pc: self.addr, limit }),
+        }
+    }
+
+    fn debug_write(&mut self, value: u16) {
+        if value == b'\n' as u16 {
+            let line = String::from_utf8_lossy(&self.debug_line).into_owned();
+            self.debug_line.clear();
+            self.events.push(Event::Debug(line));
+        } else {
+            self.debug_line.push(value as u8);
+        }
+    }
+
+    pub fn set_stack_check(&mut self, on: bool) {
+        self.stack_check = on;
+    }
+
+    pub fn stack_check(&self) -> bool {
+        self.stack_check
+    }
+
+    pub fn set_fast(&mut self, fast: bool) {
+        self.fast = fast;
+        self.superblocks.clear();
+    }
+
+    pub fn fast(&self) -> bool {
+        self.fast
+    }
+
+    // start or stop working out routines' arguments, see `arity`.  what was
+    // found so far is kept while it stays on
+    pub fn set_arity(&mut self, on: bool) {
+        match (on, &self.arity) {
+            (true, None) => self.arity = Some(Arity::default()),
+            (false, _) => self.arity = None,
+            _ => (),
+        }
+    }
+
+    pub fn arity(&self) -> Option<&Arity> {
+        self.arity.as_ref()
+    }
+
+    // start or stop noting what each instruction changes, see `journal`.
+    // stopping forgets it
+    pub fn set_journal(&mut self, on: bool) {
+        match (on, &self.journal) {
+            (true, None) => {
+                let max = self.journal_max.unwrap_or(JOURNAL_MAX);
+                self.journal = Some(Journal::new(max));
+            }
+            (false, _) => self.journal = None,
+            _ => (),
+        }
+    }
+
+    // keep the last max instructions in the journal rather than JOURNAL_MAX,
+    // from when it's next turned on
+    pub fn set_journal_max(&mut self, max: usize) {
+        self.journal_max = Some(max);
+        self.journal = None;
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    // what the instruction about to run could change
+    fn journal_entry(&self) -> journal::Entry {
+        let opcode = self.get_ram(self.addr);
+        let value = |addr: u16| match self.get_ram(addr) {
+            w @ 32768..=32775 => self.registers[(w - 32768) as usize],
+            w => w,
+        };
+        // a push that doesn't fit writes the stack flag instead
+        let full = self.stack_limit.is_some_and(|n| self.stack.len() >= n);
+        let written = match opcode {
+            16 => Some(value(self.addr + 1)),
+            2 | 17 if full => self.stack_flag,
+            _ => None,
+        };
+        let memory = written.map(|addr| (addr, self.get_ram(addr)));
+        // the instructions with a register to write as their first operand
+        let register = match (opcode, self.get_ram(self.addr + 1)) {
+            (1 | 3..=5 | 9..=15 | 20, w @ 32768..=32775) => {
+                let r = (w - 32768) as usize;
+                Some((r, self.registers[r]))
+            }
+            _ => None,
+        };
+        journal::Entry {
+            pc: self.addr,
+            steps: self.steps,
+            register,
+            memory,
+            ram_len: self.ram.len(),
+            stack_len: self.stack.len(),
+            stack_top: self.stack.last().copied(),
+            input: match opcode {
+                20 => self.input_buffer.first().copied(),
+                _ => None,
+            },
+            frames: matches!(opcode, 17 | 18).then(|| self.frames.clone()),
+            running: self.running,
+            pushed: self.pushed,
+        }
+    }
+
+    // undo the last instruction in the journal, false if there isn't one
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.journal.as_mut().and_then(|j| j.pop()) else {
+            return false;
+        };
+        self.addr = entry.pc;
+        self.steps = entry.steps;
+        if let Some((r, value)) = entry.register {
+            self.registers[r] = value;
+        }
+        if let Some((addr, word)) = entry.memory {
+            self.write_ram(addr, word);
+            self.ram.resize(entry.ram_len);
+        }
+        if self.stack.len() > entry.stack_len {
+            self.stack.pop();
+        } else if let Some(top) = entry.stack_top
+            && self.stack.len() < entry.stack_len
+        {
+            self.stack.push(top);
+        }
+        if let Some(c) = entry.input {
+            self.input_buffer.insert(0, c);
+        }
+        if let Some(frames) = entry.frames {
+            self.frames = frames;
+            self.spans.sync(&self.frames, &self.symbols);
+        }
+        self.running = entry.running;
+        self.pushed = entry.pushed;
+        // already stopped here, don't stop for a breakpoint again
+        self.resume = true;
+        true
+    }
+
+    // number of superblocks currently decoded
+    pub fn superblocks(&self) -> usize {
+        self.superblocks.len()
+    }
+
+    pub fn thumbnail(&self) -> &Thumbnail {
+        &self.thumbnail
+    }
+
+    // a 64-bit FNV-1a hash of the machine state: STATE_HASH_VERSION, the pc,
+    // the registers, the stack and every word of ram, all little endian.  it
+    // doesn't depend on the platform or the Rust version, so hashes can be
+    // stored and compared later.  input, output and debugger state aren't
+    // included
+    pub fn state_hash(&self) -> u64 {
+        self.state_hash_excluding(&[])
+    }
+
+    // state_hash with the words in the given address ranges hashed as zero,
+    // for ram that changes without mattering (counters and such)
+    pub fn state_hash_excluding(&self, exclude: &[Range<u16>]) -> u64 {
+        let mut h = Fnv::default();
+
+        h.write(&STATE_HASH_VERSION.to_le_bytes());
+        h.write(&self.addr.to_le_bytes());
+        for r in self.registers {
+            h.write(&r.to_le_bytes());
+        }
+        h.write(&(self.stack.len() as u64).to_le_bytes());
+        for v in &self.stack {
+            h.write(&v.to_le_bytes());
+        }
+
+        h.write(&(self.ram.len() as u64).to_le_bytes());
+        for (i, byte) in self.ram.bytes().enumerate() {
+            let addr = (i / 2) as u16;
+            if exclude.iter().any(|r| r.contains(&addr)) {
+                h.write(&[0]);
+            } else {
+                h.write(&[byte]);
+            }
+        }
+
+        h.0
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ram: self.ram.clone(),
+            registers: self.registers,
+            addr: self.addr,
+            stack: self.stack.clone(),
+            running: self.running,
+            input_buffer: self.input_buffer.clone(),
+            steps: self.steps,
+            frames: self.frames.clone(),
+            thumbnail: self.thumbnail.clone(),
+        }
+    }
+
+    // go back to a snapshot.  breakpoints, hooks, coverage and such are left
+    // as they are
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.ram = snapshot.ram;
+        self.registers = snapshot.registers;
+        self.addr = snapshot.addr;
+        self.stack = snapshot.stack;
+        self.running = snapshot.running;
+        self.input_buffer = snapshot.input_buffer;
+        self.steps = snapshot.steps;
+        self.frames = snapshot.frames;
+        self.thumbnail = snapshot.thumbnail;
+        self.spans.clear();
+        self.spans.sync(&self.frames, &self.symbols);
+        self.resume = false;
+        self.output.clear();
+        self.echo.clear();
+        self.superblocks.clear();
+        self.operand_cache.clear();
+        if let Some(arity) = &mut self.arity {
+            arity.clear_calls();
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) -> usize {
+        self.breakpoints.add(addr, false)
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    // input to feed the game before reading any more from the terminal
+    pub fn queue_input(&mut self, input: &[u8]) {
+        self.input_buffer.extend(input);
+    }
+
+    // write call/ret events to the trace from now on
+    pub fn set_chrome_trace(&mut self, trace: ChromeTrace) {
+        self.add_trace_sink(Box::new(trace));
+    }
+
+    // see `tracesink`
+    pub fn add_trace_sink(&mut self, sink: Box<dyn TraceSink>) {
+        self.trace_sinks.push(sink);
+    }
+
+    // count call stacks from now on
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = Some(sampler);
+    }
+
+    pub fn sampler(&self) -> Option<&Sampler> {
+        self.sampler.as_ref()
+    }
+
+    // the program has ended: write out the samples and any unfinished debug
+    // line, and return from any routines still running for the trace sinks
+    // before finishing them
+    fn finish(&mut self) {
+        if let Some(sampler) = &mut self.sampler {
+            sampler.write();
+        }
+        if !self.debug_line.is_empty() {
+            self.debug_write(b'\n' as u16);
+        }
+        let mut sinks = std::mem::take(&mut self.trace_sinks);
+        for sink in &mut sinks {
+            for frame in self.frames.iter().rev() {
+                let name = self.routine_name(frame.routine);
+                sink.ret(frame.routine, &name, self.steps);
+            }
+            sink.finish(self.steps);
+        }
+    }
+
+    // report every active call that has gone over its budget.  each call
+    // only alarms once
+    fn check_budgets(&mut self) {
+        let mut outer = None;
+        for frame in self.frames.iter_mut() {
+            let mut own = None;
+            if let Some(n) = self.budgets.get(&frame.routine) {
+                let ran = self.steps - frame.entered;
+                if ran > *n {
+                    self.events.push(Event::OverBudget {
+                        frame: frame.clone(),
+                        ran,
+                        budget: *n,
+                    });
+                } else {
+                    own = Some(frame.entered + n);
+                }
+            }
+            frame.deadline = match (own, outer) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            outer = frame.deadline;
+        }
+    }
+
+    // set a register to a value
+    pub fn set_register(&mut self, register: u16, value: u16) {
+        //info!("register {} write: {}", register, value);
+        self.registers[register as usize] = value;
+    }
+
+    // carry on from addr without running anything, ie. to skip a check.  a
+    // breakpoint there still stops
+    pub fn set_pc(&mut self, addr: u16) {
+        self.resume = false;
+        self.jump(addr);
+    }
+
+    // return from the routine being run without running the rest of it, with
+    // r0 set to value if given.  whatever the routine pushed is dropped along
+    // with its return address.  returns the address it went back to
+    pub fn force_return(&mut self, value: Option<u16>) -> Result<u16, String> {
+        // without a frame (ie. a restored save), the top of the stack is the
+        // best guess at the return address
+        let depth = match self.frames.last() {
+            Some(frame) => frame.depth,
+            None if self.stack.is_empty() => {
+                return Err("not in a call".to_string());
+            }
+            None => self.stack.len() - 1,
+        };
+        self.stack.truncate(depth + 1);
+        let addr = self.stack.pop().unwrap();
+
+        if let Some(value) = value {
+            self.registers[0] = value;
+        }
+        self.drop_returned_frames();
+        self.set_pc(addr);
+        Ok(addr)
+    }
+
+    // jump to an ADDRESS
+    fn jump(&mut self, addr: u16) {
+        trace!("self.jump: jumping to addr {}", addr);
+        self.addr = addr;
+        self.jumped = true;
+    }
+
+    // write a word of ram, growing it if the address is past the end.  the
+    // program's own writes go through `store`
+    fn write_ram(&mut self, addr: u16, value: u16) {
+        let ptr = (addr * 2) as usize;
+        if ptr + 1 >= self.ram.len() {
+            self.ram.resize(ptr + 2);
+        }
+        self.ram.set_word(addr, value);
+        self.superblocks.invalidate(addr);
+        self.invalidate_operands(addr);
+
+        if !self.watched_strings.is_empty() {
+            self.check_watched_strings();
+        }
+    }
+
+    // change a word of memory from outside the program, ie. patching
+    pub fn write_word(&mut self, addr: u16, value: u16) -> Result<(), String> {
+        self.write_words(addr, &[value])
+    }
+
+    // a range of memory to patch has to fit in memory, and in strict mode
+    // inside the loaded image
+    fn check_range(&self, addr: u16, len: u16) -> Result<(), String> {
+        let end = addr as usize + len as usize;
+        if end > 32768 {
+            return Err(format!(
+                "{} words from {} runs past the end of memory",
+                len, addr
+            ));
+        }
+        if self.strict && end * 2 > self.ram.len() {
+            return Err(format!(
+                "{} words from {} runs past the end of the image",
+                len, addr
+            ));
+        }
+        Ok(())
+    }
+
+    // set len words starting at addr to value
+    pub fn fill(
+        &mut self,
+        addr: u16,
+        len: u16,
+        value: u16,
+    ) -> Result<(), String> {
+        self.check_range(addr, len)?;
+        if value > 32775 {
+            return Err(format!("value {} out of range", value));
+        }
+        for a in addr..addr + len {
+            self.write_ram(a, value);
+        }
+        Ok(())
+    }
+
+    // len words starting at addr
+    pub fn read_words(&self, addr: u16, len: u16) -> Result<Vec<u16>, String> {
+        self.check_range(addr, len)?;
+        Ok((addr..addr + len).map(|a| self.ram.word(a).unwrap_or(0)).collect())
+    }
+
+    // write words starting at addr, ie. a region saved with read_words
+    pub fn write_words(
+        &mut self,
+        addr: u16,
+        words: &[u16],
+    ) -> Result<(), String> {
+        let len = u16::try_from(words.len()).unwrap_or(u16::MAX);
+        self.check_range(addr, len)?;
+        if let Some(word) = words.iter().find(|w| **w > 32775) {
+            return Err(format!("value {} out of range", word));
+        }
+        for (i, word) in words.iter().enumerate() {
+            self.write_ram(addr + i as u16, *word);
+        }
+        Ok(())
+    }
+
+    // copy len words from src to dst.  the ranges can overlap
+    pub fn copy(&mut self, src: u16, dst: u16, len: u16) -> Result<(), String> {
+        self.check_range(src, len)?;
+        self.check_range(dst, len)?;
+        let words: Vec<u16> =
+            (src..src + len).map(|a| self.ram.word(a).unwrap_or(0)).collect();
+        for (i, word) in words.into_iter().enumerate() {
+            self.write_ram(dst + i as u16, word);
+        }
+        Ok(())
+    }
+
+    // replace the instructions in the range with noops, one per word.  the
+    // range has to start and end on instruction boundaries: it's decoded
+    // from the start and has to end exactly at the end, and it can't start
+    // in the middle of an instruction that has been run
+    pub fn nop(&mut self, range: Range<u16>) -> Result<(), String> {
+        self.check_range(range.start, range.end.saturating_sub(range.start))?;
+        let words = self.ram.words();
+
+        for back in 1..=3 {
+            let Some(addr) = range.start.checked_sub(back) else {
+                break;
+            };
+            if self.executed(addr)
+                && let Some(ins) = disasm::decode(&words, addr)
+                && ins.len() > back as usize
+            {
+                return Err(format!(
+                    "{} is in the middle of `{}` at {}",
+                    range.start, ins, addr
+                ));
+            }
+        }
+
+        let mut addr = range.start;
+        while addr < range.end {
+            let Some(ins) = disasm::decode(&words, addr) else {
+                return Err(format!("no valid instruction at {}", addr));
+            };
+            if addr + ins.len() as u16 > range.end {
+                return Err(format!(
+                    "{} is in the middle of `{}` at {}",
+                    range.end, ins, addr
+                ));
+            }
+            addr += ins.len() as u16;
+        }
+
+        for addr in range {
+            self.write_ram(addr, 21);
+        }
+        Ok(())
+    }
+
+    // true if nothing needs to see the instructions one at a time, so a
+    // superblock can run them all at once
+    fn can_run_superblock(&self) -> bool {
+        self.fast
+            && self.breakpoints.is_empty()
+            && self.hooks.is_empty()
+            && self.trace_sinks.is_empty()
+            && self.value_history.is_empty()
+            && self.arity.is_none()
+            && self.journal.is_none()
+            && self.stack_limit.is_none()
+            && self.frames.last().is_none_or(|f| f.deadline.is_none())
+            // register reads are logged at info, and every instruction at
+            // debug
+            && !tracing::enabled!(Level::INFO)
+    }
+
+    fn operand(&self, operand: Operand) -> u16 {
+        match operand {
+            Operand::Reg(r) => self.registers[r as usize],
+            Operand::Lit(n) => n,
+        }
+    }
+
+    // run a superblock from the top.  it stops early if it writes to its own
+    // code, leaving the rest to be fetched again, or at an op that faults
+    fn run_superblock(&mut self, block: &Block) -> Result<(), VmError> {
+        if self.coverage.is_empty() {
+            self.coverage = vec![0; 32768 / 64];
+        }
+
+        for op in &block.ops {
+            self.addr = op.addr;
+            self.steps += 1;
+            let a = op.addr as usize;
+            self.coverage[a / 64] |= 1 << (a % 64);
+
+            let [x, y, z] = op.args;
+            // the destination register of the ops that have one
+            let r = match x {
+                Operand::Reg(r) => r as usize,
+                Operand::Lit(_) => 0,
+            };
+            let b = self.operand(y);
+            let c = self.operand(z);
+
+            match op.opcode {
+                1 => self.registers[r] = b,
+                2 => {
+                    self.push_stack(self.operand(x));
+                    self.pushed = Some(op.addr + 2);
+                }
+                3 => self.registers[r] = self.pop_stack()?,
+                4 => self.registers[r] = (b == c) as u16,
+                5 => self.registers[r] = (b > c) as u16,
+                9 => self.registers[r] = ((b as u32 + c as u32) % 32768) as u16,
+                10 => self.registers[r] = (b as u32 * c as u32 % 32768) as u16,
+                11 if c == 0 => {
+                    return Err(VmError::DivideByZero { pc: op.addr });
+                }
+                11 => self.registers[r] = b % c % 32768,
+                12 => self.registers[r] = (b & c) % 32768,
+                13 => self.registers[r] = (b | c) % 32768,
+                14 => self.registers[r] = !b % 32768,
+                15 => self.registers[r] = self.read(b)?,
+                16 => {
+                    let a = self.operand(x);
+                    self.store(a, b)?;
+                    if self.debug_port == Some(a) {
+                        self.debug_write(b);
+                    }
+                    if (block.start..block.end).contains(&a) {
+                        self.addr = op.addr + 3;
+                        return Ok(());
+                    }
+                }
+                19 => {
+                    let a = self.operand(x);
+                    self.thumbnail.push(a as u8 as char);
+                    self.output.push(a as u8);
+                }
+                _ => {}
+            }
+
+            if self.audit && audit::is_audited(op.opcode) {
+                audit::check(op.addr, op.opcode, b, c, self.registers[r]);
+            }
+        }
+
+        self.addr = block.end;
+        Ok(())
+    }
+
+    fn log_assembly(&self, op: &str) {
+        if self.quiet {
+            return;
+        }
+        // the step is the number of instructions run before this one, the
+        // same as `steps` in trace filters and the count in a save made just
+        // before it
+        debug!(step = self.steps - 1, "{} {}", self.addr, op);
+    }
+
+    // run the next instruction.  one that faults is left where it is,
+    // uncounted, for the frontend to look at (and maybe fix) before trying
+    // it again
+    pub fn step(&mut self) -> Result<(), VmError> {
+        if !self.running {
+            return Err(VmError::Halted);
+        }
+        let span = self.spans.current();
+        let _entered = span.enter();
+        let entry = self.journal.is_some().then(|| self.journal_entry());
+        let steps = self.steps;
+        if let Err(e) = self.execute() {
+            self.steps -= 1;
+            return Err(e);
+        }
+        if let Some(journal) = &mut self.journal
+            && let Some(entry) = entry
+            && self.steps > steps
+        {
+            journal.push(entry);
+        }
+        if let Some(sampler) = &mut self.sampler {
+            sampler.tick(self.steps, &self.frames);
+        }
+        if !self.value_history.is_empty() {
+            self.value_history.record(self.steps, &self.registers, &self.ram);
+        }
+        Ok(())
+    }
+
+    // fails having counted the instruction, but before changing anything
+    fn execute(&mut self) -> Result<(), VmError> {
+        // stop before running the instruction, the frontend steps again to
+        // carry on
+        if self.resume {
+            self.resume = false;
+        } else {
+            if !self.breakpoints.is_empty()
+                && let Some(b) = self.breakpoints.hit(self.addr)
+            {
+                self.events.push(Event::Breakpoint(b));
+                self.resume = true;
+                return Ok(());
+            }
+
+            if let Some(deadline) = self.frames.last().and_then(|f| f.deadline)
+                && self.steps > deadline
+            {
+                self.check_budgets();
+                self.resume = true;
+                return Ok(());
+            }
+        }
+
+        if !self.hooks.is_empty() {
+            self.fire_hooks();
+        }
+
+        // nothing to do until there is input
+        if self.input_buffer.is_empty() && self.ram.word(self.addr) == Some(20)
+        {
+            return Ok(());
+        }
+
+        if let Some(filter) = &self.trace_filter
+            && tracing::enabled!(Level::DEBUG)
+        {
+            self.quiet = !filter.matches(&self.trace_step());
+        }
+
+        if !self.trace_sinks.is_empty() {
+            let step = self.trace_step();
+            for sink in &mut self.trace_sinks {
+                sink.instruction(&step);
+            }
+        }
+
+        if std::mem::take(&mut self.jumped)
+            && self.can_run_superblock()
+            && let Some(block) = self.superblocks.get(&self.ram, self.addr)
+        {
+            return self.run_superblock(&block);
+        }
+
+        self.steps += 1;
+
+        if self.coverage.is_empty() {
+            self.coverage = vec![0; 32768 / 64];
+        }
+        let a = self.addr as usize;
+        self.coverage[a / 64] |= 1 << (a % 64);
+
+        // grab the instruction to process
+        let instruction = self.read(self.addr)?;
+        let args = self.operands(instruction)?;
+
+        if let Some(arity) = &mut self.arity {
+            arity.sync(&self.frames, &self.registers);
+            arity.note(instruction, &args, self.stack.len());
+        }
+
+        // the operands of an audited instruction, to check its result
+        // against once it's run
+        let audited = match args[0] {
+            Some(Operand::Reg(r))
+                if self.audit && audit::is_audited(instruction) =>
+            {
+                let value =
+                    |arg: Option<Operand>| arg.map_or(0, |a| self.operand(a));
+                Some((self.addr, r, value(args[1]), value(args[2])))
+            }
+            _ => None,
+        };
+
+        match instruction {
+            0 => {
+                // halt
+                // stop execution and terminate the program
+                self.log_assembly("halt");
+
+                self.running = false;
+                self.finish();
+            }
+            1 => {
+                // set: 1 a b
+                // set register <a> to the value of <b>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                self.log_assembly(&format!("set <{}> = {}", a, b));
+
+                self.set_register(a, b);
+
+                self.addr += 3;
+            }
+            2 => {
+                // push: 2 a
+                // push <a> onto the stack
+                let a = self.arg_value(&args, 1)?;
+                self.log_assembly(&format!("push {}", a));
+
+                if !self.stack_full()? {
+                    self.push_stack(a);
+                    self.pushed = Some(self.addr + 2);
+                }
+
+                self.addr += 2;
+            }
+            3 => {
+                // pop: 3 a
+                // remove the top element from the stack and write it into <a>;
+                // empty stack = error
+                let a = self.arg_register(&args, 1)?;
+                let elem = self.pop_stack()?;
+
+                self.log_assembly(&format!(
+                    "pop writing {} into <{}>",
+                    elem, a
+                ));
+
+                self.set_register(a, elem);
+
+                self.addr += 2;
+            }
+            4 => {
+                // eq: 4 a b c
+                // set <a> to 1 if <b> is equal to <c>; set it to 0 otherwise
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("eq ({} == {})", b, c));
+
+                if b == c {
+                    self.set_register(a, 1);
+                } else {
+                    self.set_register(a, 0);
+                }
+
+                self.addr += 4;
+            }
+            5 => {
+                // gt: 5 a b c
+                // set <a> to 1 if <b> is greater than <c>; set it to 0 otherwise
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("gt ({} > {})", b, c));
+
+                if b > c {
+                    self.set_register(a, 1);
+                } else {
+                    self.set_register(a, 0);
+                }
+
+                self.addr += 4;
+            }
+            6 => {
+                // jmp: 6 a
+                // jump to <a>
+                let a = self.arg_value(&args, 1)?;
+                self.log_assembly(&format!("jmp <{}>", a));
+
+                self.jump(a);
+            }
+            7 => {
+                // jt: 7 a b
+                // if <a> is nonzero, jump to <b>
+                let a = self.arg_value(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                trace!("jt: a={}, b={}", a, b);
+                self.log_assembly(&format!("jt ({} != 0 -> {})", a, b));
+
+                if a != 0 {
+                    trace!("jt jumped to {}", b);
+                    self.jump(b);
+                } else {
+                    trace!("jt didn't jump");
+                    self.addr += 3;
+                }
+            }
+            8 => {
+                // jf: 8 a b
+                // if <a> is zero, jump to <b>
+                let a = self.arg_value(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                trace!("jf: a={}, b={}", a, b);
+                self.log_assembly(&format!("jf ({} == 0 -> {})", a, b));
+
+                if a == 0 {
+                    trace!("jf jumped to {}", b);
+                    self.jump(b);
+                } else {
+                    trace!("jf didn't jump");
+                    self.addr += 3;
+                }
+            }
+            9 => {
+                // add: 9 a b c
+                // assign into <a> the sum of <b> and <c> (modulo 32768)
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("add <{}> = {} + {}", a, b, c));
+
+                let sum = (b as u32 + c as u32) % 32768;
+                self.set_register(a, sum as u16);
+
+                self.addr += 4;
+            }
+            10 => {
+                // mult: 10 a b c
+                // store into <a> the product of <b> and <c> (modulo 32768)
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("mult <{}> = {} * {}", a, b, c));
+
+                let sum = (b as u32 * c as u32) % 32768;
+                self.set_register(a, sum as u16);
+
+                self.addr += 4;
+            }
+            11 => {
+                // mod: 11 a b c
+                // store into <a> the remainder of <b> divided by <c>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+                if c == 0 {
+                    return Err(VmError::DivideByZero { pc: self.addr });
+                }
+
+                self.log_assembly(&format!("mod <{}> = {} % {}", a, b, c));
+
+                let sum = (b % c) % 32768;
+                self.set_register(a, sum);
+
+                self.addr += 4;
+            }
+            12 => {
+                // and: 12 a b c
+                // stores into <a> the bitwise and of <b> and <c>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("and <{}> = {} & {}", a, b, c));
+
+                let sum = (b & c) % 32768;
+                self.set_register(a, sum);
+
+                self.addr += 4;
+            }
+            13 => {
+                // or: 13 a b c
+                // stores into <a> the bitwise or of <b> and <c>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+                let c = self.arg_value(&args, 3)?;
+
+                self.log_assembly(&format!("or <{}> = {} | {}", a, b, c));
+
+                let sum = (b | c) % 32768;
+                self.set_register(a, sum);
+
+                self.addr += 4;
+            }
+            14 => {
+                // not: 14 a b
+                // stores 15-bit bitwise inverse of <b> in <a>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                self.log_assembly(&format!("not <{}> = ~{}", a, b));
+
+                let b = !b % 32768;
+                self.set_register(a, b);
+
+                self.addr += 3;
+            }
+            15 => {
+                // rmem: 15 a b
+                // read memory at address <b> and write it to <a>
+                let a = self.arg_register(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                let num = self.read(b)?;
+
+                self.log_assembly(&format!("rmem <{}> = {}", a, num));
+
+                self.set_register(a, num);
+
+                self.addr += 3;
+            }
+            16 => {
+                // wmem: 16 a b
+                // write the value from <b> into memory at address <a>
+                let a = self.arg_value(&args, 1)?;
+                let b = self.arg_value(&args, 2)?;
+
+                trace!("setting value {} into ram memory addr {}", b, a);
+
+                self.log_assembly(&format!("wmem {} = {}", a, b));
+
+                self.store(a, b)?;
+                if self.debug_port == Some(a) {
+                    self.debug_write(b);
+                }
+                for sink in &mut self.trace_sinks {
+                    sink.wmem(a, b, self.steps);
+                }
+
+                self.addr += 3;
+            }
+            17 => {
+                // call: 17 a
+                // write the address of the next instruction to the stack and
+                // jump to <a>
+
+                let a = self.arg_value(&args, 1)?;
+
+                self.log_assembly(&format!("call {}", a));
+
+                if a == 6049 {
+                    // LOL - game genie
+                    /*
+                    self.registers[0] = 6;
+                    self.addr += 2;
+                    return;
+                    */
+                }
+
+                if self.stack_full()? {
+                    self.addr += 2;
+                } else {
+                    self.enter(a, self.addr + 2);
+                }
+            }
+            18 => {
+                // ret: 18
+                // remove the top element from the stack and jump to it; empty
+                // stack = halt
+                let Some(addr) = self.stack.pop() else {
+                    self.log_assembly("ret (empty stack)");
+                    self.running = false;
+                    self.finish();
+                    return Ok(());
+                };
+                // `push x; ret` is an indirect jump, not a return - the
+                // routine carries on at x with its frame as it was
+                let jump = self.pushed == Some(self.addr);
+                if self.stack_check
+                    && !jump
+                    && let Some(frame) = self.frames.last()
+                    && frame.depth != self.stack.len()
+                {
+                    // either values were left behind, so this returns to
+                    // whatever is on top, or the routine popped its caller's
+                    // values (and likely its own return address)
+                    self.events.push(Event::StackImbalance {
+                        frame: frame.clone(),
+                        ret: self.addr,
+                        extra: self.stack.len() as isize - frame.depth as isize,
+                    });
+                }
+                if jump {
+                    self.log_assembly(&format!("ret (jump to {})", addr));
+                } else {
+                    self.log_assembly(&format!("ret ({})", addr));
+                }
+                self.drop_returned_frames();
+                self.jump(addr);
+            }
+            19 => {
+                // out: 19 a
+                // write the character represented by ascii code <a> to the
+                // terminal
+                self.log_assembly("out");
+
+                let a = self.arg_value(&args, 1)?;
+                self.thumbnail.push(a as u8 as char);
+                self.output.push(a as u8);
+                trace!("output: {}", a);
+
+                self.addr += 2;
+            }
+            20 => {
+                // in: 20 a
+                // read a character from the terminal and write its ascii
+                // code to <a>; it can be assumed that once input starts, it
+                // will continue until a newline is encountered; this means
+                // that you can safely read whole lines from the keyboard
+                // instead of having to figure out how to read individual
+                // characters
+                self.log_assembly("in");
+
+                let a = self.arg_register(&args, 1)?;
+
+                // step() doesn't get here with the buffer empty
+                let c = self.input_buffer.remove(0);
+                self.echo.push(c);
+
+                self.set_register(a, c as u16);
+
+                self.addr += 2;
+            }
+            21 => {
+                // no-op
+                // no operation
+                self.log_assembly("no-op");
+                self.addr += 1;
+            }
+            n => {
+                // uh oh
+                return Err(VmError::UnknownOpcode {
+                    pc: self.addr,
+                    opcode: n,
+                });
+            }
+        }
+
+        if let Some((addr, r, b, c)) = audited {
+            audit::check(addr, instruction, b, c, self.registers[r as usize]);
+        }
+        Ok(())
+    }
+
+    // name a routine by its symbol if there is one
+    pub(crate) fn routine_name(&self, addr: u16) -> String {
+        routine_name(&self.symbols, addr)
+    }
+
+    pub(crate) fn decode_string(&self, addr: u16) -> Option<String> {
+        // only convert as much ram as the longest string could need
+        let start = (addr as usize * 2).min(self.ram.len());
+        let end =
+            (start + (strings::MAX_LEN as usize + 1) * 2).min(self.ram.len());
+        strings::decode(&map::words(&self.ram.slice(start, end)), 0)
+    }
+
+    // report any watched string that changed since it was last seen
+    fn check_watched_strings(&mut self) {
+        for i in 0..self.watched_strings.len() {
+            let (addr, ref old) = self.watched_strings[i];
+            let new = self.decode_string(addr);
+            if *old != new {
+                self.events.push(Event::StringChanged(addr, new.clone()));
+                self.watched_strings[i].1 = new;
+            }
+        }
+    }
+}
+
+// len words from addr have to fit in the address space
+pub(crate) fn routine_name(symbols: &Symbols, addr: u16) -> String {
+    match symbols.name_of(addr) {
+        Some(name) => format!("{} ({})", addr, name),
+        None => addr.to_string(),
+    }
+}
+
+impl Machine for VM {
+    type Addr = u16;
+    type Error = VmError;
+
+    fn step(&mut self) -> Result<(), VmError> {
+        VM::step(self)
+    }
+
+    fn is_halted(&self) -> bool {
+        VM::is_halted(self)
+    }
+
+    fn wants_input(&self) -> bool {
+        VM::wants_input(self)
+    }
+
+    fn pc(&self) -> u16 {
+        VM::pc(self)
+    }
+
+    fn steps(&self) -> u64 {
+        VM::steps(self)
+    }
+
+    fn send_input(&mut self, input: &[u8]) {
+        self.queue_input(input);
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        VM::take_output(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+    use crate::symbols::Symbols;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    // echo input and keep a running sum of it in r1, until a '.'
+    const ECHO: &str = "\
+        top: in r0; out r0; add r1 r1 r0; eq r2 r0 '.'; jf r2 top; halt";
+
+    const LINES: [&str; 4] = ["north\n", "take lamp\n", "use lamp\n", ".\n"];
+
+    fn rom() -> Vec<u8> {
+        let words = asm::assemble(ECHO, 0, &Symbols::default()).unwrap();
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    // run to each prompt in turn, waiting for the other threads there before
+    // typing the next line, and note the state hash at each
+    fn play(rom: Vec<u8>, barrier: &Barrier) -> Vec<u64> {
+        let mut vm = VM::new(rom);
+        let mut hashes = vec![];
+        let mut lines = LINES.iter();
+        while !vm.is_halted() {
+            if vm.wants_input() {
+                hashes.push(vm.state_hash());
+                barrier.wait();
+                let Some(line) = lines.next() else {
+                    break;
+                };
+                vm.queue_input(line.as_bytes());
+            }
+            vm.step().unwrap();
+        }
+        hashes.push(vm.state_hash());
+        hashes
+    }
+
+    // registers can hold values past 32767 by reading memory that holds
+    // them, and the results of and, or and mod still have to be reduced,
+    // in both engines
+    #[test]
+    fn reduces_out_of_range_registers() {
+        let src = "\
+            set r5 40; \
+            top: rmem r0 1000; rmem r4 1001; \
+            and r1 r0 r0; or r2 r0 0; mod r3 r0 r4; \
+            add r5 r5 32767; jt r5 top; halt";
+        let mut words = asm::assemble(src, 0, &Symbols::default()).unwrap();
+        words.resize(1000, 0);
+        words.extend([40000, 50000]);
+        let rom: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+
+        for fast in [false, true] {
+            let mut vm = VM::new(rom.clone());
+            vm.set_fast(fast);
+            while !vm.is_halted() {
+                vm.step().unwrap();
+            }
+            assert_eq!(vm.register(0), 40000);
+            for r in 1..=3 {
+                assert_eq!(
+                    vm.register(r),
+                    40000 % 32768,
+                    "r{} fast={}",
+                    r,
+                    fast
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn threads_in_lockstep() {
+        let threads = 3;
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let (rom, barrier) = (rom(), barrier.clone());
+                thread::spawn(move || play(rom, &barrier))
+            })
+            .collect();
+        let runs: Vec<_> =
+            handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        // a prompt before each line, and the halt
+        assert_eq!(runs[0].len(), LINES.len() + 1);
+        for run in &runs[1..] {
+            assert_eq!(*run, runs[0]);
+        }
+        // the game moved on between prompts
+        for pair in runs[0].windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+ * Keep track of whether the game is thinking, waiting for input or halted.
+ *
+ * Some puzzles (the teleporter, mostly) have the game compute for a very
+ * long time, and from outside that looks just like it waiting for input.
+ * `/status` says which it is:
+ *
+ * ```text
+ * thinking for 1m12s, 3,412,000,000 instructions (47,388,888 a second)
+ * ```
+ *
+ * To watch a game from somewhere else, `--status-file FILE` keeps a line of
+ * JSON in FILE with the same thing, rewritten whenever it changes and every
+ * few seconds while the game thinks, for a metrics collector or `watch cat`
+ * to read:
+ *
+ * ```text
+ * {"status":"thinking","seconds":72.1,"steps":3412000000,"pc":6027}
+ * ```
+ *
+ * When an instruction faults the line says why until the game gets past it,
+ * with a code from `vm::ERROR_CODES` that stays the same whatever the
+ * message says:
+ *
+ * ```text
+ * {"status":"thinking",...,"fault":{"code":"E0002","message":"pop from an empty stack at 5","pc":5}}
+ * ```
+ *
+ * and `--notify CMD` runs CMD with `sh -c` when the game stops thinking
+ * after a long computation, with `$SYNACOR_STATUS` ("waiting" or "halted"),
+ * `$SYNACOR_SECONDS` and `$SYNACOR_STEPS` set, ie. to send a message when
+ * the teleporter is done.
+ */
+
+use std::fmt;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::saves;
+use crate::shell::group_digits;
+use crate::vm::{VM, VmError};
+
+// thinking for less than this doesn't need a notification
+const NOTIFY_AFTER: Duration = Duration::from_secs(10);
+
+// how often the status file is rewritten while the game thinks
+const WRITE_EVERY: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activity {
+    Thinking,
+    Waiting,
+    Halted,
+}
+
+impl Activity {
+    pub fn of(vm: &VM) -> Self {
+        if vm.is_halted() {
+            Activity::Halted
+        } else if vm.wants_input() {
+            Activity::Waiting
+        } else {
+            Activity::Thinking
+        }
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activity::Thinking => write!(f, "thinking"),
+            Activity::Waiting => write!(f, "waiting for input"),
+            Activity::Halted => write!(f, "halted"),
+        }
+    }
+}
+
+// a line of the status file
+#[derive(Serialize)]
+struct Status {
+    status: Activity,
+    seconds: f64,
+    steps: u64,
+    pc: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fault: Option<VmError>,
+}
+
+pub struct Watchdog {
+    // what the game is doing, since when and the step count then
+    activity: Activity,
+    since: Instant,
+    since_steps: u64,
+    // --status-file and when it was last written
+    file: Option<String>,
+    written: Instant,
+    // --notify
+    notify: Option<String>,
+    // the last fault and the step count it happened at, until the game gets
+    // past it
+    fault: Option<(VmError, u64)>,
+}
+
+impl Watchdog {
+    pub fn new(vm: &VM) -> Self {
+        Self {
+            activity: Activity::of(vm),
+            since: Instant::now(),
+            since_steps: vm.steps(),
+            file: None,
+            written: Instant::now(),
+            notify: None,
+            fault: None,
+        }
+    }
+
+    pub fn set_file(&mut self, file: &str, vm: &VM) {
+        self.file = Some(file.to_string());
+        self.write(vm);
+    }
+
+    pub fn set_notify(&mut self, cmd: &str) {
+        self.notify = Some(cmd.to_string());
+    }
+
+    pub fn activity(&self) -> Activity {
+        self.activity
+    }
+
+    // look at what the game is doing now.  called every so often while it
+    // runs and whenever it may have stopped
+    pub fn update(&mut self, vm: &VM) {
+        if self.fault.as_ref().is_some_and(|(_, steps)| *steps != vm.steps()) {
+            self.fault = None;
+            self.write(vm);
+        }
+        let activity = Activity::of(vm);
+        if activity == self.activity {
+            if self.file.is_some() && self.written.elapsed() >= WRITE_EVERY {
+                self.write(vm);
+            }
+            return;
+        }
+
+        let (was, elapsed) = (self.activity, self.since.elapsed());
+        self.activity = activity;
+        self.since = Instant::now();
+        self.since_steps = vm.steps();
+        self.write(vm);
+        if was == Activity::Thinking && elapsed >= NOTIFY_AFTER {
+            self.run_notify(elapsed, vm.steps());
+        }
+    }
+
+    // an instruction faulted, see `Shell::fault`
+    pub fn fault(&mut self, vm: &VM, e: &VmError) {
+        self.fault = Some((e.clone(), vm.steps()));
+        self.write(vm);
+    }
+
+    // ie. "thinking for 12s, 340,000,000 instructions (28,333,333 a second)"
+    pub fn status(&self, vm: &VM) -> String {
+        let elapsed = self.since.elapsed();
+        let mut s = format!("{} for {}", self.activity, duration(elapsed));
+        if self.activity == Activity::Thinking {
+            let ran = vm.steps().saturating_sub(self.since_steps);
+            let ips = ran as f64 / elapsed.as_secs_f64().max(0.001);
+            s += &format!(
+                ", {} instructions ({} a second)",
+                group_digits(ran),
+                group_digits(ips as u64)
+            );
+        }
+        if let Some((e, _)) = &self.fault {
+            s += &format!(", stopped by fault {}", e.code());
+        }
+        s
+    }
+
+    fn write(&mut self, vm: &VM) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        self.written = Instant::now();
+        let status = Status {
+            status: self.activity,
+            seconds: (self.since.elapsed().as_secs_f64() * 10.0).round() / 10.0,
+            steps: vm.steps(),
+            pc: vm.pc(),
+            fault: self.fault.as_ref().map(|(e, _)| e.clone()),
+        };
+        let data = format!("{}\n", serde_json::to_string(&status).unwrap());
+        if let Err(e) = saves::write(file, data.as_bytes(), true) {
+            println!("{}, not writing the status any more", e);
+            self.file = None;
+        }
+    }
+
+    // run --notify in the background, so a slow one doesn't hold the game up
+    fn run_notify(&self, elapsed: Duration, steps: u64) {
+        let Some(cmd) = self.notify.clone() else {
+            return;
+        };
+        let status = match self.activity {
+            Activity::Halted => "halted",
+            _ => "waiting",
+        };
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", &cmd])
+            .env("SYNACOR_STATUS", status)
+            .env("SYNACOR_SECONDS", elapsed.as_secs().to_string())
+            .env("SYNACOR_STEPS", steps.to_string());
+        thread::spawn(move || {
+            if let Err(e) = command.status() {
+                println!("--notify: {}: {}", cmd, e);
+            }
+        });
+    }
+}
+
+// ie. "4.5s" or "1h2m3s"
+pub(crate) fn duration(d: Duration) -> String {
+    let s = d.as_secs();
+    match s {
+        0..60 => format!("{:.1}s", d.as_secs_f64()),
+        60..3600 => format!("{}m{}s", s / 60, s % 60),
+        _ => format!("{}h{}m{}s", s / 3600, s / 60 % 60, s % 60),
+    }
+}